@@ -2,6 +2,8 @@
 // We use include! to avoid needing the full crate context.
 
 fn main() {
+    emit_build_info();
+
     // Only generate man page if building docs or on release
     if std::env::var("GENERATE_MAN").is_ok() || std::env::var("PROFILE").as_deref() == Ok("release")
     {
@@ -9,6 +11,56 @@ fn main() {
     }
 }
 
+/// Build metadata for --version-file: BUILD_TIMESTAMP (RFC 3339, honouring
+/// SOURCE_DATE_EPOCH for reproducible builds), BUILD_TARGET and BUILD_GIT_HASH.
+fn emit_build_info() {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs() as i64)
+        });
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={}", rfc3339(secs));
+    println!(
+        "cargo:rustc-env=BUILD_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+    let git_hash = std::process::Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=BUILD_GIT_HASH={}", git_hash);
+}
+
+/// Format seconds since the epoch as `YYYY-MM-DDTHH:MM:SSZ` (days-to-civil
+/// conversion from Howard Hinnant's date algorithms).
+fn rfc3339(secs: i64) -> String {
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
 fn generate_man_page() {
     // We need to define a minimal version of the CLI struct here
     // since build scripts can't depend on the crate being built.
@@ -0,0 +1,45 @@
+#![no_main]
+
+use clap::Parser;
+use libfuzzer_sys::fuzz_target;
+use std::fs::{self, File};
+use std::os::unix::io::IntoRawFd;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// -a: every field of the stat reaches fchown, fchmod and futimens.
+fn opts() -> &'static cp::options::CopyOptions {
+    static OPTS: OnceLock<cp::options::CopyOptions> = OnceLock::new();
+    OPTS.get_or_init(|| {
+        let cli = cp::cli::Cli::parse_from(["cp", "-a", "src", "dst"]);
+        cp::options::CopyOptions::from_cli(&cli)
+    })
+}
+
+fn scratch(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cp-fuzz-{}-{}", std::process::id(), name))
+}
+
+fuzz_target!(|data: &[u8]| {
+    const STAT_SIZE: usize = std::mem::size_of::<nix::libc::stat>();
+    if data.len() < STAT_SIZE {
+        return;
+    }
+    // Every bit pattern is a valid stat: it only holds integers
+    let stat: nix::libc::stat = unsafe { std::ptr::read_unaligned(data.as_ptr().cast()) };
+    let contents = &data[STAT_SIZE..];
+
+    let (src, dst) = (scratch("src"), scratch("dst"));
+    fs::write(&src, contents).unwrap();
+    let src_fd = File::open(&src).unwrap().into_raw_fd();
+    let dst_fd = File::create(&dst).unwrap().into_raw_fd();
+
+    // Any stat must end in Ok or a CpError: never a panic or a signal
+    let _ = cp::dir::copy_fds(src_fd, dst_fd, &src, &dst, Some(&stat), opts());
+    if let Ok(copied) = fs::read(&dst) {
+        assert_eq!(copied, contents);
+    }
+
+    // A fuzzed mode may leave the file unreadable for the next run
+    let _ = fs::remove_file(&dst);
+});
@@ -5,11 +5,22 @@ use crate::options::BackupMode;
 /// Make a backup of the destination file if it exists.
 /// Returns the backup path if a backup was created.
 pub fn make_backup(dest: &Path, mode: BackupMode, suffix: &str) -> Option<PathBuf> {
-    if mode == BackupMode::None || !dest.exists() {
+    if !dest.exists() {
         return None;
     }
 
-    let backup_path = match mode {
+    let backup_path = backup_path(dest, mode, suffix)?;
+
+    if std::fs::rename(dest, &backup_path).is_ok() {
+        Some(backup_path)
+    } else {
+        None
+    }
+}
+
+/// Compute the path a backup of `dest` would be renamed to, without renaming.
+pub fn backup_path(dest: &Path, mode: BackupMode, suffix: &str) -> Option<PathBuf> {
+    let path = match mode {
         BackupMode::Simple => simple_backup_path(dest, suffix),
         BackupMode::Numbered => numbered_backup_path(dest),
         BackupMode::Existing => {
@@ -23,11 +34,7 @@ pub fn make_backup(dest: &Path, mode: BackupMode, suffix: &str) -> Option<PathBu
         BackupMode::None => return None,
     };
 
-    if std::fs::rename(dest, &backup_path).is_ok() {
-        Some(backup_path)
-    } else {
-        None
-    }
+    Some(path)
 }
 
 fn simple_backup_path(dest: &Path, suffix: &str) -> PathBuf {
@@ -36,38 +43,42 @@ fn simple_backup_path(dest: &Path, suffix: &str) -> PathBuf {
     PathBuf::from(s)
 }
 
+/// `dest.~N~`, one past the highest number already next to `dest` (a gap
+/// left by a deleted backup is not reused, as in GNU cp).
 fn numbered_backup_path(dest: &Path) -> PathBuf {
-    let mut n = 1u64;
-    loop {
-        let mut s = dest.as_os_str().to_os_string();
-        s.push(format!(".~{}~", n));
-        let candidate = PathBuf::from(s);
-        if !candidate.exists() {
-            return candidate;
-        }
-        n += 1;
-    }
+    let n = highest_backup_number(dest).map_or(1, |n| n + 1);
+    let mut s = dest.as_os_str().to_os_string();
+    s.push(format!(".~{}~", n));
+    PathBuf::from(s)
 }
 
 fn has_numbered_backups(dest: &Path) -> bool {
-    let parent = dest.parent().unwrap_or(Path::new("."));
-    let name = match dest.file_name() {
-        Some(n) => n.to_string_lossy(),
-        None => return false,
+    highest_backup_number(dest).is_some()
+}
+
+/// Highest N among the `NAME.~N~` entries of the directory holding `dest`.
+/// A bare relative `dest` has an empty parent, which is the current directory.
+fn highest_backup_number(dest: &Path) -> Option<u64> {
+    let parent = match dest.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
     };
+    let name = dest.file_name()?.as_encoded_bytes();
 
-    if let Ok(entries) = std::fs::read_dir(parent) {
-        for entry in entries.flatten() {
+    std::fs::read_dir(parent)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
             let entry_name = entry.file_name();
-            let entry_str = entry_name.to_string_lossy();
-            if entry_str.starts_with(name.as_ref())
-                && entry_str.contains(".~")
-                && entry_str.ends_with('~')
-            {
-                return true;
+            let digits = entry_name
+                .as_encoded_bytes()
+                .strip_prefix(name)?
+                .strip_prefix(b".~")?
+                .strip_suffix(b"~")?;
+            if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+                return None;
             }
-        }
-    }
-
-    false
+            std::str::from_utf8(digits).ok()?.parse().ok()
+        })
+        .max()
 }
@@ -0,0 +1,105 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+use serde_json::{Value, json};
+
+/// One failed copy in an --error-checkpoint file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub src: PathBuf,
+    /// Destination path of the copy (not the directory it was copied into).
+    pub dst: PathBuf,
+    /// Message of the last failure.
+    pub error: String,
+    /// Failed --retry-checkpoint attempts so far.
+    pub retry: u32,
+    /// Options of the copy (see `Cli::copy_flags`), which a retry copies
+    /// with; empty in checkpoints written before they were recorded.
+    pub flags: Vec<String>,
+}
+
+impl Entry {
+    /// `{"src":"...","dst":"...","error":"...","retry":0,"flags":["-R"]}`
+    fn to_json(&self) -> String {
+        json!({
+            "src": path_to_json(&self.src),
+            "dst": path_to_json(&self.dst),
+            "error": self.error,
+            "retry": self.retry,
+            "flags": self.flags,
+        })
+        .to_string()
+    }
+
+    fn from_json(line: &str) -> Option<Self> {
+        let v: Value = serde_json::from_str(line).ok()?;
+        Some(Self {
+            src: path_from_json(v.get("src")?)?,
+            dst: path_from_json(v.get("dst")?)?,
+            error: v.get("error")?.as_str()?.to_string(),
+            retry: v.get("retry").and_then(Value::as_u64).unwrap_or(0) as u32,
+            flags: match v.get("flags") {
+                Some(flags) => flags
+                    .as_array()?
+                    .iter()
+                    .map(|f| f.as_str().map(str::to_string))
+                    .collect::<Option<_>>()?,
+                None => Vec::new(),
+            },
+        })
+    }
+}
+
+/// A path as JSON: a string when it is UTF-8, its bytes as an array of
+/// numbers otherwise, so that a Latin-1 name is retried as it was.
+fn path_to_json(path: &Path) -> Value {
+    match path.to_str() {
+        Some(s) => Value::from(s),
+        None => Value::from(path.as_os_str().as_bytes()),
+    }
+}
+
+fn path_from_json(v: &Value) -> Option<PathBuf> {
+    if let Some(s) = v.as_str() {
+        return Some(PathBuf::from(s));
+    }
+    let bytes = v
+        .as_array()?
+        .iter()
+        .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+        .collect::<Option<Vec<u8>>>()?;
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Read a checkpoint: one JSON object per line, blank lines ignored.
+pub fn read(path: &Path) -> io::Result<Vec<Entry>> {
+    let reader = BufReader::new(fs::File::open(path)?);
+    let mut entries = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = Entry::from_json(&line).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: not a checkpoint entry", n + 1),
+            )
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Replace the checkpoint at `path` with `entries` (an empty file when none
+/// are left).
+pub fn write(path: &Path, entries: &[Entry]) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    for entry in entries {
+        writeln!(out, "{}", entry.to_json())?;
+    }
+    out.flush()
+}
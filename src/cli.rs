@@ -1,6 +1,7 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
 
-use clap::{ArgAction, Parser, ValueEnum};
+use clap::{ArgAction, ArgMatches, Parser, ValueEnum};
 
 /// Copy SOURCE to DEST, or multiple SOURCE(s) to DIRECTORY.
 #[derive(Parser, Debug)]
@@ -15,13 +16,26 @@ By default, sparse SOURCE files are detected by a crude heuristic and the \
 corresponding DEST file is made sparse as well.  That is the behavior \
 selected by --sparse=auto.  Specify --sparse=always to create a sparse DEST \
 file whenever the SOURCE file contains a long enough sequence of zero bytes.  \
+--sparse=heuristic does the same only when most of the first 64 KiB of the \
+SOURCE file is zero bytes, without asking the file system for holes.  \
 Use --sparse=never to inhibit creation of sparse files.
 
 When --reflink[=always] is specified, perform a lightweight copy, where the \
 data blocks are copied only when modified.  If this is not possible the copy \
 fails, or if --reflink=auto is specified, fall back to a standard copy.  \
+--reflink=auto does not try files under 256 KiB; --reflink=try-always does.  \
 Use --reflink=never to ensure a standard copy is performed.
 
+Preserved timestamps are the access and modification times: the kernel sets \
+the change time (ctime) of every copy, even with --preserve=all.  \
+--preserve=flags copies chattr inode flags (nodump, append-only, immutable, ...); \
+--preserve=all leaves them out.
+
+--update=older-or-same-size skips a destination that is not older than its \
+source, or that has the same size: a same-size file with different content \
+is NOT copied.  Meant for targets with coarse timestamps (FAT) where a size \
+change is the reliable sign of an update.
+
 The backup suffix is '~', unless set with --suffix or SIMPLE_BACKUP_SUFFIX.  \
 The version control method may be selected via the --backup option or through \
 the VERSION_CONTROL environment variable.  Here are the values:
@@ -84,6 +98,10 @@ pub struct Cli {
     #[arg(short = 'n', long = "no-clobber", action = ArgAction::SetTrue)]
     pub no_clobber: bool,
 
+    /// With -n, exit with status N (1 by default) if any existing destination was skipped
+    #[arg(long = "fail-on-skip", value_name = "N", num_args = 0..=1, default_missing_value = "1", require_equals = true, requires = "no_clobber", value_parser = clap::value_parser!(u8).range(1..))]
+    pub fail_on_skip: Option<u8>,
+
     /// Never follow symlinks in SOURCE
     #[arg(short = 'P', long = "no-dereference", action = ArgAction::SetTrue)]
     pub no_dereference: bool,
@@ -100,10 +118,28 @@ pub struct Cli {
     #[arg(long = "no-preserve", value_name = "ATTR_LIST", value_delimiter = ',')]
     pub no_preserve: Option<Vec<String>>,
 
+    /// Every --preserve (true) and --no-preserve (false) attribute in
+    /// command-line order; filled by `Cli::parse_ordered_from`
+    #[arg(skip)]
+    pub preserve_order: Vec<(bool, String)>,
+
+    /// The options given, for --error-checkpoint to record; filled by
+    /// `Cli::parse_ordered_from`
+    #[arg(skip)]
+    pub copy_flags: Vec<String>,
+
+    /// Fail on any xattr that cannot be copied instead of warning
+    #[arg(long = "strict-xattr", action = ArgAction::SetTrue)]
+    pub strict_xattr: bool,
+
     /// Use full source path under DIRECTORY
     #[arg(long = "parents", action = ArgAction::SetTrue)]
     pub parents: bool,
 
+    /// Create missing parent directories of DEST first (like install -D)
+    #[arg(short = 'D', long = "make-parents", action = ArgAction::SetTrue)]
+    pub make_parents: bool,
+
     /// Copy directories recursively
     #[arg(short = 'R', short_alias = 'r', long = "recursive", action = ArgAction::SetTrue)]
     pub recursive: bool,
@@ -112,14 +148,66 @@ pub struct Cli {
     #[arg(long = "reflink", value_name = "WHEN", num_args = 0..=1, default_missing_value = "always", require_equals = true)]
     pub reflink: Option<ReflinkMode>,
 
+    /// Hint sequential reads for sources of 64 MiB or more, then drop them from the page cache
+    #[arg(long = "fadvise", action = ArgAction::SetTrue)]
+    pub fadvise: bool,
+
+    /// Copy file data with O_DIRECT, bypassing the page cache
+    #[arg(long = "direct", action = ArgAction::SetTrue)]
+    pub direct: bool,
+
+    /// Warn when a reflinked copy does not actually share extents with its source
+    #[arg(long = "cow-verify", action = ArgAction::SetTrue)]
+    pub cow_verify: bool,
+
+    /// Report how many copied bytes share extents with their source (reflinks)
+    #[arg(long = "report-shared", action = ArgAction::SetTrue)]
+    pub report_shared: bool,
+
+    /// Copy engine when --reflink=auto makes no clone
+    #[arg(
+        long = "reflink-fallback",
+        value_name = "STRATEGY",
+        require_equals = true
+    )]
+    pub reflink_fallback: Option<ReflinkFallback>,
+
+    /// Read each SOURCE as an archive of FORMAT and extract it into DEST
+    #[arg(long = "from-format", value_name = "FORMAT", require_equals = true)]
+    pub from_format: Option<InputFormat>,
+
+    /// Hard-link a file to an earlier copy with the same content (=metadata trusts size and mtime alone, unsafe)
+    #[arg(long = "hardlink-across-sources", value_name = "MATCH", num_args = 0..=1, default_missing_value = "content", require_equals = true)]
+    pub hardlink_across_sources: Option<DedupMode>,
+
+    /// Report how DEST differs from SOURCE instead of copying (exit 1 if it does)
+    #[arg(long = "compare", value_name = "BY", num_args = 0..=1, default_missing_value = "content", require_equals = true)]
+    pub compare: Option<CompareBy>,
+
+    /// Hard-link instead of copying files larger than SIZE on the same filesystem
+    #[arg(long = "link-threshold", value_name = "SIZE", value_parser = parse_size)]
+    pub link_threshold: Option<u64>,
+
     /// Remove each existing destination file before copy
     #[arg(long = "remove-destination", action = ArgAction::SetTrue)]
     pub remove_destination: bool,
 
+    /// Order in which directory entries are copied
+    #[arg(long = "sort", value_name = "ORDER", require_equals = true)]
+    pub sort: Option<SortOrder>,
+
     /// Control creation of sparse files
     #[arg(long = "sparse", value_name = "WHEN")]
     pub sparse: Option<SparseMode>,
 
+    /// With --sparse=auto, copy holes only when they make up at least RATIO (0.0-1.0) of the file
+    #[arg(long = "sparse-detect-ratio", value_name = "RATIO", value_parser = parse_ratio)]
+    pub sparse_detect_ratio: Option<f64>,
+
+    /// With --sparse=auto, skip hole detection when copy_file_range copies within one filesystem
+    #[arg(long = "no-sparse-on-copy-file-range", action = ArgAction::SetTrue)]
+    pub no_sparse_on_copy_file_range: bool,
+
     /// Remove trailing slashes from each SOURCE
     #[arg(long = "strip-trailing-slashes", action = ArgAction::SetTrue)]
     pub strip_trailing_slashes: bool,
@@ -140,18 +228,114 @@ pub struct Cli {
     #[arg(short = 'T', long = "no-target-directory", action = ArgAction::SetTrue)]
     pub no_target_directory: bool,
 
+    /// Abort a recursive copy before it copies more than N files
+    #[arg(long = "max-files", value_name = "N")]
+    pub max_files: Option<u64>,
+
+    /// Copy at most N levels below each source directory
+    #[arg(long = "max-depth", value_name = "N")]
+    pub max_depth: Option<usize>,
+
+    /// Abort before the files copied add up to more than SIZE bytes (K, M, G suffixes)
+    #[arg(long = "max-total-size", value_name = "SIZE", value_parser = parse_size)]
+    pub max_total_size: Option<u64>,
+
+    /// With --max-total-size, add up the sources first and copy nothing if they exceed it
+    #[arg(long = "pre-check-size", requires = "max_total_size", action = ArgAction::SetTrue)]
+    pub pre_check_size: bool,
+
+    /// Remove PREFIX from source paths when building destination paths
+    #[arg(long = "strip-prefix", value_name = "PREFIX")]
+    pub strip_prefix: Option<PathBuf>,
+
     /// Copy only when SOURCE is newer or DEST is missing
     #[arg(short = 'u', long = "update", value_name = "CONTROL", num_args = 0..=1, default_missing_value = "older", require_equals = true)]
     pub update: Option<UpdateMode>,
 
-    /// Show progress bar during copy
-    #[arg(long = "progress", action = ArgAction::SetTrue)]
-    pub progress: bool,
+    /// Skip files whose destination exists with the same size (ignores mtime and content)
+    #[arg(long = "size-only", action = ArgAction::SetTrue)]
+    pub size_only: bool,
+
+    /// Compare timestamps for --update at a granularity of SECS seconds (0 = exact)
+    #[arg(
+        long = "timestamp-resolution",
+        value_name = "SECS",
+        default_value_t = 0
+    )]
+    pub timestamp_resolution: u64,
+
+    /// Abort a file copy whose I/O stalls for more than SECS seconds
+    #[arg(long = "io-timeout", value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub io_timeout: Option<u64>,
+
+    /// Copy at most BYTES per copy_file_range/sendfile call (default 64M)
+    #[arg(long = "chunk-size", value_name = "BYTES", value_parser = parse_chunk_size)]
+    pub chunk_size: Option<usize>,
+
+    /// Number of threads for parallel file copies
+    #[arg(long = "jobs", value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Write per-file copy statistics as tab-separated lines to FILE ('-' for stdout)
+    #[arg(long = "stat", value_name = "FILE")]
+    pub stat: Option<PathBuf>,
+
+    /// Write the stat(2) fields of each source and destination as tab-separated lines to FILE
+    #[arg(long = "copy-stat", value_name = "FILE")]
+    pub copy_stat: Option<PathBuf>,
+
+    /// Write the version and build information as JSON to FILE before copying
+    #[arg(long = "version-file", value_name = "FILE")]
+    pub version_file: Option<PathBuf>,
+
+    /// Save the sources that fail to FILE, one JSON object per line; exit 2 if any do
+    #[arg(long = "error-checkpoint", value_name = "FILE")]
+    pub error_checkpoint: Option<PathBuf>,
+
+    /// Copy the operations saved in FILE again, keeping only those that still fail
+    #[arg(
+        long = "retry-checkpoint",
+        value_name = "FILE",
+        conflicts_with = "paths"
+    )]
+    pub retry_checkpoint: Option<PathBuf>,
+
+    /// With --retry-checkpoint, try each operation up to N times
+    #[arg(long = "retry", value_name = "N", requires = "retry_checkpoint")]
+    pub retry: Option<u32>,
+
+    /// After a recursive copy with --preserve=links, check that every hard link group was reproduced
+    #[arg(long = "hardlink-ref-count-check", action = ArgAction::SetTrue)]
+    pub hardlink_ref_count_check: bool,
+
+    /// Print what would be copied as tab-separated 'type src dst size' lines, copying nothing
+    #[arg(long = "list", visible_alias = "list-only", action = ArgAction::SetTrue)]
+    pub list: bool,
+
+    /// Show progress during copy: a bar (default), or plain lines for logs
+    #[arg(long = "progress", value_name = "STYLE", num_args = 0..=1, default_missing_value = "bar", require_equals = true)]
+    pub progress: Option<ProgressMode>,
+
+    /// Where --progress draws: stderr (default), stdout, or file:PATH
+    #[arg(long = "progress-output", value_name = "WHERE", value_parser = parse_progress_output, require_equals = true)]
+    pub progress_output: Option<ProgressOutput>,
 
     /// Explain what is being done
     #[arg(short = 'v', long = "verbose", action = ArgAction::SetTrue)]
     pub verbose: bool,
 
+    /// Like -v, also showing the size of each copied file
+    #[arg(long = "verbose-size", action = ArgAction::SetTrue)]
+    pub verbose_size: bool,
+
+    /// Order of verbose lines in a recursive copy
+    #[arg(long = "verbose-order", value_name = "ORDER", require_equals = true)]
+    pub verbose_order: Option<VerboseOrder>,
+
+    /// Suppress warnings (skipped sockets, attributes not preserved)
+    #[arg(short = 'q', long = "quiet", action = ArgAction::SetTrue)]
+    pub quiet: bool,
+
     /// Stay on this file system
     #[arg(short = 'x', long = "one-file-system", action = ArgAction::SetTrue)]
     pub one_file_system: bool,
@@ -164,27 +348,369 @@ pub struct Cli {
     #[arg(long = "context", value_name = "CTX", num_args = 0..=1, default_missing_value = "")]
     pub context: Option<String>,
 
+    /// Hard-link files unchanged from LINKDIR instead of copying them
+    #[arg(long = "link-dest", value_name = "LINKDIR")]
+    pub link_dest: Option<PathBuf>,
+
+    /// Downgrade copy errors on paths matching PATTERN to warnings (repeatable)
+    #[arg(long = "ignore-errors-from", value_name = "PATTERN", action = ArgAction::Append)]
+    pub ignore_errors_from: Vec<glob::Pattern>,
+
+    /// Set the mode of every directory the copy creates (octal; --preserve=mode wins)
+    #[arg(
+        long = "dir-mode",
+        visible_alias = "out-dir-mode",
+        value_name = "MODE",
+        value_parser = parse_mode
+    )]
+    pub dir_mode: Option<u32>,
+
+    /// Give every destination this owner and/or group, as for chown(1)
+    #[arg(long = "chown", value_name = "[USER][:GROUP]", value_parser = parse_owner)]
+    pub chown: Option<Owner>,
+
+    /// Don't create destination directories that would end up empty
+    #[arg(long = "no-empty-dirs", action = ArgAction::SetTrue)]
+    pub no_empty_dirs: bool,
+
+    /// Replace an existing destination directory atomically
+    #[arg(long = "atomic-dir", action = ArgAction::SetTrue)]
+    pub atomic_dir: bool,
+
     /// Keep directory symlinks in DEST during recursive copy
     #[arg(long = "keep-directory-symlink", action = ArgAction::SetTrue)]
     pub keep_directory_symlink: bool,
 
+    /// Run a quick copy smoke check in $TMPDIR and exit
+    #[arg(long = "selftest", hide = true, action = ArgAction::SetTrue)]
+    pub selftest: bool,
+
+    /// Print how many files and bytes the copy would take, without copying
+    #[arg(long = "estimate", action = ArgAction::SetTrue)]
+    pub estimate: bool,
+
+    /// Print every option for completion scripts and exit
+    #[arg(long = "list-options", hide = true, action = ArgAction::SetTrue)]
+    pub list_options: bool,
+
     /// Source file(s) and destination
-    #[arg(required = true)]
+    #[arg(required_unless_present_any = ["selftest", "list_options", "retry_checkpoint"])]
     pub paths: Vec<PathBuf>,
 }
 
+impl Cli {
+    /// `Cli::parse_from`, also recording the order in which --preserve and
+    /// --no-preserve attributes were given, so the last one naming an
+    /// attribute wins.
+    pub fn parse_ordered_from<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        use clap::{CommandFactory, FromArgMatches};
+
+        let matches = Cli::command().get_matches_from(args);
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        cli.preserve_order = preserve_order(&matches);
+        cli.copy_flags = copy_flags(&matches);
+        cli
+    }
+}
+
+/// The options on the command line, as `--name` and `--name=VALUE` in their
+/// original order, without the paths and the options about this run only
+/// (checkpoint files, --version-file): what a retry has to copy with.
+fn copy_flags(matches: &ArgMatches) -> Vec<String> {
+    use clap::CommandFactory;
+    use clap::parser::ValueSource;
+
+    const RUN_ONLY: &[&str] = &[
+        "paths",
+        "error_checkpoint",
+        "retry_checkpoint",
+        "retry",
+        "version_file",
+    ];
+    let mut flags = Vec::new();
+    for arg in Cli::command().get_arguments() {
+        let id = arg.get_id().as_str();
+        if RUN_ONLY.contains(&id) || matches.value_source(id) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let name = match (arg.get_long(), arg.get_short()) {
+            (Some(long), _) => format!("--{}", long),
+            (None, Some(short)) => format!("-{}", short),
+            (None, None) => continue,
+        };
+        let Some(indices) = matches.indices_of(id) else {
+            continue;
+        };
+        if arg.get_action().takes_values() {
+            let values = matches.get_raw(id).into_iter().flatten();
+            flags.extend(
+                indices
+                    .zip(values)
+                    .map(|(i, v)| (i, format!("{}={}", name, v.to_string_lossy()))),
+            );
+        } else {
+            flags.extend(indices.map(|i| (i, name.clone())));
+        }
+    }
+    flags.sort_by_key(|&(i, _)| i);
+    flags.into_iter().map(|(_, f)| f).collect()
+}
+
+/// --preserve and --no-preserve values sorted by their position on the
+/// command line (each value of a comma list has an index of its own).
+fn preserve_order(matches: &ArgMatches) -> Vec<(bool, String)> {
+    let mut order = Vec::new();
+    for (id, preserve) in [("preserve", true), ("no_preserve", false)] {
+        if let (Some(indices), Some(values)) =
+            (matches.indices_of(id), matches.get_many::<String>(id))
+        {
+            order.extend(indices.zip(values).map(|(i, v)| (i, preserve, v.clone())));
+        }
+    }
+    order.sort_by_key(|&(i, ..)| i);
+    order.into_iter().map(|(_, p, v)| (p, v)).collect()
+}
+
+/// --list-options: one line per visible option, `LONG<TAB>SHORT<TAB>KIND`,
+/// where a missing name is `-` and KIND is `value` or `flag`. Aliases (`-r`
+/// for `-R` too) get lines of their own.
+pub fn list_options() -> String {
+    use clap::CommandFactory;
+
+    let mut cmd = Cli::command();
+    cmd.build();
+    let mut out = String::new();
+    for arg in cmd.get_arguments() {
+        if arg.is_hide_set() || arg.is_positional() {
+            continue;
+        }
+        let kind = if arg.get_action().takes_values() {
+            "value"
+        } else {
+            "flag"
+        };
+        let long = arg
+            .get_long()
+            .map_or("-".to_string(), |l| format!("--{}", l));
+        let short = arg
+            .get_short()
+            .map_or("-".to_string(), |s| format!("-{}", s));
+        out.push_str(&format!("{}\t{}\t{}\n", long, short, kind));
+        for alias in arg.get_all_aliases().unwrap_or_default() {
+            out.push_str(&format!("--{}\t-\t{}\n", alias, kind));
+        }
+        for alias in arg.get_all_short_aliases().unwrap_or_default() {
+            out.push_str(&format!("-\t-{}\t{}\n", alias, kind));
+        }
+    }
+    out
+}
+
+/// A byte count with an optional binary suffix, as for GNU tools:
+/// `4096`, `64K`, `50M`, `2G`, `1T` (also `MiB`-style spellings).
+fn parse_size(s: &str) -> Result<u64, String> {
+    let digits = s.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match s[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KIB" => 1 << 10,
+        "M" | "MIB" => 1 << 20,
+        "G" | "GIB" => 1 << 30,
+        "T" | "TIB" => 1 << 40,
+        _ => return Err(format!("invalid size '{}'", s)),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size '{}'", s))
+}
+
+/// --chunk-size: a non-zero size, as for --link-threshold.
+fn parse_chunk_size(s: &str) -> Result<usize, String> {
+    parse_size(s)
+        .ok()
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("invalid chunk size '{}'", s))
+}
+
+/// A fraction between 0.0 and 1.0.
+fn parse_ratio(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|r| (0.0..=1.0).contains(r))
+        .ok_or_else(|| format!("invalid ratio '{}' (expected 0.0 to 1.0)", s))
+}
+
+/// --progress-output: `stderr`, `stdout` or `file:PATH`.
+fn parse_progress_output(s: &str) -> Result<ProgressOutput, String> {
+    match s {
+        "stderr" => Ok(ProgressOutput::Stderr),
+        "stdout" => Ok(ProgressOutput::Stdout),
+        _ => match s.strip_prefix("file:") {
+            Some(path) if !path.is_empty() => Ok(ProgressOutput::File(PathBuf::from(path))),
+            _ => Err(format!(
+                "invalid progress output '{}' (expected stderr, stdout or file:PATH)",
+                s
+            )),
+        },
+    }
+}
+
+/// Octal permission bits, as for chmod: `755`, `0700`.
+fn parse_mode(s: &str) -> Result<u32, String> {
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!("invalid mode '{}'", s)),
+    }
+}
+
+/// --chown: the ids to set, None for the part left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// `USER`, `USER:GROUP`, `:GROUP` or `USER:` (the user's login group), each
+/// a name or a numeric id, resolved once here rather than for every file.
+fn parse_owner(s: &str) -> Result<Owner, String> {
+    use nix::unistd::{Group, User};
+
+    let (user, group) = match s.split_once(':') {
+        Some((u, g)) => (u, Some(g)),
+        None => (s, None),
+    };
+    if user.is_empty() && group.is_none_or(str::is_empty) {
+        return Err(format!("invalid owner '{}'", s));
+    }
+
+    let mut login_gid = None;
+    let uid = match user {
+        "" => None,
+        name => match User::from_name(name) {
+            Ok(Some(u)) => {
+                login_gid = Some(u.gid.as_raw());
+                Some(u.uid.as_raw())
+            }
+            _ => Some(
+                name.parse::<u32>()
+                    .map_err(|_| format!("invalid user '{}'", name))?,
+            ),
+        },
+    };
+    let gid = match group {
+        None => None,
+        Some("") => Some(login_gid.ok_or_else(|| format!("'{}' has no login group", user))?),
+        Some(name) => match Group::from_name(name) {
+            Ok(Some(g)) => Some(g.gid.as_raw()),
+            _ => Some(
+                name.parse::<u32>()
+                    .map_err(|_| format!("invalid group '{}'", name))?,
+            ),
+        },
+    };
+    Ok(Owner { uid, gid })
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
 pub enum ReflinkMode {
     Always,
     Auto,
+    /// Like auto, but try the clone for small files too
+    TryAlways,
     Never,
 }
 
+/// Data copy engine used when no reflink is made (--reflink-fallback).
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum ReflinkFallback {
+    /// Try copy_file_range, then sendfile, then read/write
+    Auto,
+    CopyFileRange,
+    Sendfile,
+    ReadWrite,
+    /// Fail when the clone fails, like --reflink=always
+    Error,
+}
+
+/// Source archive format (--from-format).
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum InputFormat {
+    Tar,
+}
+
+/// How --progress renders.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Terminal progress bar (only when the output is a TTY)
+    Bar,
+    /// Plain text lines, about once a second, TTY or not
+    Plain,
+}
+
+/// Where progress bars are drawn (--progress-output).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressOutput {
+    Stderr,
+    Stdout,
+    File(PathBuf),
+}
+
+/// How --hardlink-across-sources recognizes a duplicate.
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum DedupMode {
+    /// Same size and mtime, without reading the files: two files that
+    /// differ only in their bytes are linked all the same (unsafe)
+    Metadata,
+    /// Same size, mtime and bytes
+    Content,
+}
+
+/// What --compare checks for regular files present on both sides (a size
+/// difference is always reported).
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum CompareBy {
+    /// Size only
+    Size,
+    /// Size and modification time
+    Mtime,
+    /// Size and bytes
+    Content,
+}
+
+/// Directory entry order (--sort).
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum SortOrder {
+    /// As returned by the filesystem (no extra work)
+    None,
+    Name,
+    /// Inode number: fewer seeks when reading from a spinning disk
+    Inode,
+}
+
+/// Order of --verbose lines in a recursive copy (--verbose-order).
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
+pub enum VerboseOrder {
+    /// Per directory: regular files, special files, symlinks, then
+    /// subdirectories; a directory's own line comes after its contents
+    Grouped,
+    /// Each entry when it is copied, in directory order, a directory before
+    /// its contents (as GNU cp); copies run sequentially
+    Tree,
+}
+
 #[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
 pub enum SparseMode {
     Always,
     Auto,
     Never,
+    /// Like 'always' when the first 64 KiB is mostly zeros, else 'never'
+    Heuristic,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq)]
@@ -198,4 +724,7 @@ pub enum UpdateMode {
     /// Like 'none', also skip if sizes match
     #[value(name = "none-fail")]
     NoneFail,
+    /// Like 'older', also skip when DEST has the size of SOURCE, whatever its content
+    #[value(name = "older-or-same-size")]
+    OlderOrSameSize,
 }
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::cli::CompareBy;
+use crate::options::{CopyOptions, Dereference};
+use crate::util;
+
+/// One way DEST differs from SOURCE under --compare. Paths are the
+/// destination's, whether or not it exists.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// In SOURCE, not in DEST (a missing directory counts once, not per entry)
+    Missing(PathBuf),
+    /// In DEST, not in SOURCE
+    Extra(PathBuf),
+    /// On both sides, but not the same: `type`, `size`, `mtime`, `content`
+    /// or `link target`
+    Differs(PathBuf, &'static str),
+    /// Could not be read on one side or the other
+    Error(PathBuf, String),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Missing(p) => write!(f, "missing: '{}'", p.display()),
+            Difference::Extra(p) => write!(f, "extra: '{}'", p.display()),
+            Difference::Differs(p, what) => write!(f, "differs ({}): '{}'", what, p.display()),
+            Difference::Error(p, e) => write!(f, "cannot compare '{}': {}", p.display(), e),
+        }
+    }
+}
+
+/// --compare: walk `src` and `dst` and list how `dst` differs, in path
+/// order. Entries on both sides must be of the same type; regular files are
+/// then compared by `by`, symlinks by target. Directory metadata isn't
+/// compared. -L follows symlinks as the copy would.
+pub fn compare_trees(src: &Path, dst: &Path, by: CompareBy, opts: &CopyOptions) -> Vec<Difference> {
+    let follow = opts.dereference == Dereference::Always;
+    let follow_root = opts.dereference != Dereference::Never;
+    let stat = |p: &Path, root: bool| util::get_metadata(p, follow || (root && follow_root));
+
+    let mut diffs = Vec::new();
+
+    // SOURCE side: what is missing or different in DEST
+    let mut walker = WalkDir::new(src)
+        .follow_root_links(follow_root)
+        .follow_links(follow)
+        .sort_by_file_name()
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let path = e.path().unwrap_or(src).to_path_buf();
+                diffs.push(Difference::Error(path, e.to_string()));
+                continue;
+            }
+        };
+        let rel = entry.path().strip_prefix(src).unwrap_or(entry.path());
+        let dst_path = join(dst, rel);
+        let root = entry.depth() == 0;
+        let dst_meta = match stat(&dst_path, root) {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                diffs.push(Difference::Missing(dst_path));
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+            Err(e) => {
+                diffs.push(Difference::Error(dst_path, e.to_string()));
+                continue;
+            }
+        };
+        let src_meta = match stat(entry.path(), root) {
+            Ok(m) => m,
+            Err(e) => {
+                diffs.push(Difference::Error(entry.path().to_path_buf(), e.to_string()));
+                continue;
+            }
+        };
+
+        if src_meta.file_type() != dst_meta.file_type() {
+            diffs.push(Difference::Differs(dst_path, "type"));
+            if src_meta.is_dir() {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+        match compare_entry(entry.path(), &dst_path, &src_meta, &dst_meta, by) {
+            Ok(Some(what)) => diffs.push(Difference::Differs(dst_path, what)),
+            Ok(None) => {}
+            Err(e) => diffs.push(Difference::Error(dst_path, e.to_string())),
+        }
+    }
+
+    // DEST side: what SOURCE doesn't have
+    let mut walker = WalkDir::new(dst)
+        .follow_root_links(follow_root)
+        .follow_links(follow)
+        .sort_by_file_name()
+        .min_depth(1)
+        .into_iter();
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            // Already reported from the source side when it matters
+            continue;
+        };
+        let rel = entry.path().strip_prefix(dst).unwrap_or(entry.path());
+        if fs::symlink_metadata(join(src, rel)).is_err() {
+            diffs.push(Difference::Extra(entry.path().to_path_buf()));
+            if entry.file_type().is_dir() {
+                walker.skip_current_dir();
+            }
+        }
+    }
+
+    diffs.sort_by(|a, b| a.path().cmp(b.path()));
+    diffs
+}
+
+impl Difference {
+    pub fn path(&self) -> &Path {
+        match self {
+            Difference::Missing(p)
+            | Difference::Extra(p)
+            | Difference::Differs(p, _)
+            | Difference::Error(p, _) => p,
+        }
+    }
+}
+
+/// `base/rel`, or `base` itself for the root (an empty `rel`).
+fn join(base: &Path, rel: &Path) -> PathBuf {
+    if rel.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(rel)
+    }
+}
+
+/// What differs between two entries of the same type, None when nothing.
+fn compare_entry(
+    src: &Path,
+    dst: &Path,
+    src_meta: &fs::Metadata,
+    dst_meta: &fs::Metadata,
+    by: CompareBy,
+) -> std::io::Result<Option<&'static str>> {
+    if src_meta.file_type().is_symlink() {
+        let same = fs::read_link(src)? == fs::read_link(dst)?;
+        return Ok((!same).then_some("link target"));
+    }
+    if !src_meta.is_file() {
+        return Ok(None);
+    }
+    if src_meta.len() != dst_meta.len() {
+        return Ok(Some("size"));
+    }
+    Ok(match by {
+        CompareBy::Size => None,
+        CompareBy::Mtime => (src_meta.mtime() != dst_meta.mtime()
+            || src_meta.mtime_nsec() != dst_meta.mtime_nsec())
+        .then_some("mtime"),
+        CompareBy::Content => (!util::same_content(src, dst)?).then_some("content"),
+    })
+}
@@ -1,15 +1,19 @@
 use std::fs::{self, File};
-use std::os::unix::fs::{FileTypeExt, MetadataExt};
-use std::path::Path;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
 
 use indicatif::ProgressBar;
+use walkdir::WalkDir;
 
 use crate::backup;
-use crate::cli::{SparseMode, UpdateMode};
+use crate::cli::{ReflinkFallback, ReflinkMode, SparseMode, UpdateMode};
 use crate::engine;
 use crate::error::{CpError, CpResult};
 use crate::metadata;
-use crate::options::CopyOptions;
+use crate::options::{CopyOptions, Dereference};
 use crate::sparse;
 use crate::util;
 
@@ -23,9 +27,204 @@ pub fn is_simple_opts(opts: &CopyOptions) -> bool {
         && !opts.remove_destination
         && opts.update.is_none()
         && opts.backup == crate::options::BackupMode::None
-        && !opts.hard_link
         && !opts.symbolic_link
+        && opts.link_threshold.is_none()
+        && opts.dedup.is_none()
         && !opts.attributes_only
+        && opts.io_timeout.is_none()
+        && opts.link_dest.is_none()
+        && opts.reflink_fallback == crate::cli::ReflinkFallback::Auto
+        && opts.reflink != crate::cli::ReflinkMode::TryAlways
+        && opts.stat.is_none()
+        && opts.copy_stat.is_none()
+        && !opts.no_empty_dirs
+        && opts.max_depth.is_none()
+        && opts.max_total_size.is_none()
+        && !opts.direct
+        && !opts.fadvise
+        && !opts.cow_verify
+        && !opts.strict_xattr
+        && !opts.copy_contents
+        && opts.shared_report.is_none()
+        && !opts.verbose_tree()
+        && opts.chown.is_none()
+        && !(cfg!(feature = "selinux") && (opts.selinux_context.is_some() || opts.preserve_context))
+}
+
+/// --max-total-size: the file data the whole run may copy, shared by every
+/// clone of the options (and so every copy thread).
+#[derive(Debug, Clone)]
+pub struct TotalSizeLimit {
+    limit: u64,
+    used: Arc<AtomicU64>,
+}
+
+impl TotalSizeLimit {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Count `len` more bytes from `path` before copying them, failing
+    /// instead when they would take the total past the limit.
+    pub fn claim(&self, path: &Path, len: u64) -> CpResult<()> {
+        let claimed = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                used.checked_add(len).filter(|&total| total <= self.limit)
+            });
+        claimed.map(|_| ()).map_err(|_| CpError::MaxSizeExceeded {
+            limit: self.limit,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// --pre-check-size: add up the regular files `sources` would copy,
+    /// failing at the first one that takes the total past the limit.
+    pub fn pre_check(&self, sources: &[PathBuf], opts: &CopyOptions) -> CpResult<()> {
+        let mut total: u64 = 0;
+        for source in sources {
+            let mut walk = WalkDir::new(source)
+                .follow_root_links(opts.dereference != Dereference::Never)
+                .follow_links(opts.dereference == Dereference::Always);
+            if !opts.recursive {
+                walk = walk.max_depth(0);
+            } else if let Some(depth) = opts.max_depth {
+                walk = walk.max_depth(depth);
+            }
+            for entry in walk.into_iter().flatten() {
+                let Ok(meta) = entry.metadata() else {
+                    continue;
+                };
+                if !meta.is_file() {
+                    continue;
+                }
+                total = total.saturating_add(meta.len());
+                if total > self.limit {
+                    return Err(CpError::MaxSizeExceeded {
+                        limit: self.limit,
+                        path: entry.path().to_path_buf(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// --max-files: the files the whole run may copy into directories, shared
+/// by every clone of the options (and so every source argument and copy
+/// thread).
+#[derive(Debug, Clone)]
+pub struct FileCountLimit {
+    limit: u64,
+    used: Arc<AtomicU64>,
+}
+
+impl FileCountLimit {
+    pub fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Count one more file before it is created, failing instead once the
+    /// limit is reached. `dir` is the source directory being copied, for
+    /// the error message.
+    pub fn claim(&self, dir: &Path) -> CpResult<()> {
+        if self.used.fetch_add(1, Ordering::Relaxed) >= self.limit {
+            return Err(CpError::MaxFilesExceeded {
+                limit: self.limit,
+                path: dir.to_path_buf(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Whether an existing destination is kept as is: --update, -n and
+/// --size-only. `dst_meta` is None when there is no destination (any more).
+/// --update=none-fail reports the skip as an error.
+pub fn is_up_to_date(
+    src: &Path,
+    dst: &Path,
+    src_meta: &fs::Metadata,
+    dst_meta: Option<&fs::Metadata>,
+    opts: &CopyOptions,
+) -> CpResult<bool> {
+    let Some(dm) = dst_meta else {
+        return Ok(false);
+    };
+
+    match opts.update {
+        Some(UpdateMode::None) => return Ok(true),
+        Some(UpdateMode::NoneFail) => {
+            return Err(CpError::UpdateSkipped {
+                path: dst.to_path_buf(),
+            });
+        }
+        Some(UpdateMode::Older | UpdateMode::OlderOrSameSize) => {
+            // older-or-same-size: a same-size regular file counts as
+            // unchanged, even if its content differs
+            if opts.update == Some(UpdateMode::OlderOrSameSize)
+                && dm.is_file()
+                && src_meta.is_file()
+                && dm.len() == src_meta.len()
+            {
+                return Ok(true);
+            }
+            // Compare at the coarser of the two filesystems' granularity
+            // (or the one given with --timestamp-resolution)
+            let resolution = if opts.timestamp_resolution_secs > 0 {
+                opts.timestamp_resolution_secs
+            } else {
+                util::filesystem_timestamp_resolution(src)
+                    .max(util::filesystem_timestamp_resolution(dst))
+            };
+            let mtime = |m: &fs::Metadata| {
+                m.modified()
+                    .ok()
+                    .map(|t| util::truncate_time(t, resolution))
+            };
+            if mtime(dm) >= mtime(src_meta) {
+                return Ok(true);
+            }
+        }
+        Some(UpdateMode::All) | None => {}
+    }
+
+    if opts.no_clobber {
+        opts.record_skip();
+        return Ok(true);
+    }
+
+    // --size-only: a same-size regular destination counts as up to date,
+    // whatever its mtime or content
+    Ok(opts.size_only && dm.is_file() && src_meta.is_file() && dm.len() == src_meta.len())
+}
+
+/// The destination metadata `is_up_to_date` judges: a symlink destination
+/// that the copy writes through (the source isn't a symlink copied as one,
+/// no --remove-destination) is compared by its target, unless it dangles.
+pub fn update_dest_meta<'a>(
+    src_meta: &fs::Metadata,
+    dst_meta: Option<&'a fs::Metadata>,
+    dst_target_meta: Option<&'a fs::Metadata>,
+    opts: &CopyOptions,
+) -> Option<&'a fs::Metadata> {
+    match dst_meta {
+        Some(dm)
+            if dm.file_type().is_symlink()
+                && !src_meta.file_type().is_symlink()
+                && !opts.remove_destination =>
+        {
+            dst_target_meta.or(dst_meta)
+        }
+        _ => dst_meta,
+    }
 }
 
 /// Copy a single file (regular, symlink, or special).
@@ -46,11 +245,22 @@ pub fn copy_single(
     // Single stat on dest — cache the result to avoid repeated exists()/metadata() calls
     let dst_meta = fs::symlink_metadata(dst).ok();
     let dst_exists = dst_meta.is_some();
-
-    // Dangling symlink check: if dest is a symlink pointing nowhere,
-    // refuse to write through it unless --force or --remove-destination
+    // What a symlink destination points to, None if it dangles
+    let dst_target_meta = dst_meta
+        .as_ref()
+        .filter(|dm| dm.file_type().is_symlink())
+        .and_then(|_| fs::metadata(dst).ok());
+
+    // Dangling symlink check: if dest is a symlink pointing nowhere, refuse
+    // to write through it, as GNU does whatever -P/-L/-H say (those apply to
+    // sources). --force writes through it, creating the target;
+    // --remove-destination replaces the link itself.
     if let Some(ref dm) = dst_meta {
-        if dm.file_type().is_symlink() && !dst.exists() && !opts.force && !opts.remove_destination {
+        if dm.file_type().is_symlink()
+            && dst_target_meta.is_none()
+            && !opts.force
+            && !opts.remove_destination
+        {
             return Err(CpError::DanglingSymlink {
                 path: dst.to_path_buf(),
             });
@@ -69,37 +279,28 @@ pub fn copy_single(
     let dst_exists = backup_path.as_ref().map_or(dst_exists, |_| false);
 
     // Same file check (after backup, so renamed dst won't trigger this)
-    if dst_exists && util::is_same_file(src, dst) {
+    if let Some(ref dm) = dst_meta
+        && dst_exists
+        && is_same_file(src, &src_meta, dm, dst_target_meta.as_ref())
+    {
         return Err(CpError::SameFile {
             src: src.to_path_buf(),
             dst: dst.to_path_buf(),
         });
     }
 
-    // Update check
-    if let Some(update_mode) = opts.update
-        && dst_exists
-    {
-        match update_mode {
-            UpdateMode::None => return Ok(()),
-            UpdateMode::NoneFail => {
-                return Err(CpError::UpdateSkipped {
-                    path: dst.to_path_buf(),
-                });
-            }
-            UpdateMode::Older => {
-                if let Some(ref dm) = dst_meta
-                    && dm.modified().ok() >= src_meta.modified().ok()
-                {
-                    return Ok(());
-                }
-            }
-            UpdateMode::All => {} // always copy
-        }
-    }
-
-    // No-clobber check
-    if opts.no_clobber && dst_exists {
+    if is_up_to_date(
+        src,
+        dst,
+        &src_meta,
+        update_dest_meta(
+            &src_meta,
+            dst_meta.as_ref().filter(|_| dst_exists),
+            dst_target_meta.as_ref(),
+            opts,
+        ),
+        opts,
+    )? {
         return Ok(());
     }
 
@@ -111,7 +312,8 @@ pub fn copy_single(
         return Ok(());
     }
 
-    // Remove destination if requested
+    // Remove destination if requested. When a backup was made, dst was renamed
+    // away above (dst_exists is false), so the backup is never removed here.
     if opts.remove_destination && dst_exists {
         fs::remove_file(dst)
             .or_else(|_| fs::remove_dir_all(dst))
@@ -122,118 +324,510 @@ pub fn copy_single(
     }
 
     let file_type = src_meta.file_type();
+    let started = Instant::now();
+    let mut method: Option<&'static str> = None;
+    // --copy-contents: the kind of special file read into a regular one
+    let mut contents_of: Option<&str> = None;
+
+    // An existing FIFO destination (still in place: not backed up or removed)
+    // is written through rather than replaced
+    let dst_is_fifo = dst_exists
+        && !opts.remove_destination
+        && dst_meta.as_ref().is_some_and(|m| m.file_type().is_fifo());
+    let plain_data_copy = !opts.hard_link && !opts.symbolic_link && !opts.attributes_only;
 
-    if file_type.is_symlink() && !follow {
+    if dst_is_fifo && plain_data_copy && (file_type.is_file() || (follow && src.is_file())) {
+        method = Some(copy_into_fifo(src, dst, &src_meta, opts, pb)?);
+    } else if file_type.is_symlink() && !follow {
         copy_symlink(src, dst, &src_meta, opts)?;
     } else if file_type.is_dir() || (follow && src.is_dir()) {
         return Err(CpError::OmitDirectory {
             path: src.to_path_buf(),
         });
     } else if file_type.is_file() || (follow && src.is_file()) {
-        copy_regular_file(src, dst, &src_meta, opts, pb)?;
+        method = if plain_data_copy && !dst_exists && link_unchanged(dst, &src_meta, opts) {
+            Some("link-dest")
+        } else if plain_data_copy
+            && (link_large(src, dst, &src_meta, dst_exists, opts)
+                || link_duplicate(src, dst, &src_meta, dst_exists, opts))
+        {
+            Some("hard link")
+        } else {
+            if let Some(ref limit) = opts.max_total_size {
+                limit.claim(src, src_meta.len())?;
+            }
+            let method = copy_regular_file(src, dst, &src_meta, opts, pb)?;
+            if let Some(ref dedup) = opts.dedup {
+                dedup.insert(&src_meta, dst);
+            }
+            if let Some(ref report) = opts.shared_report
+                && method.is_some()
+            {
+                report.record(dst, src_meta.len());
+            }
+            method
+        };
+    } else if opts.copy_contents && opts.recursive && is_special(&file_type) {
+        method = copy_regular_file(src, dst, &src_meta, opts, pb)?;
+        contents_of = Some(special_kind(&file_type));
     } else if file_type.is_fifo() {
         copy_fifo(dst, &src_meta, opts)?;
     } else if file_type.is_block_device() || file_type.is_char_device() {
         copy_device(dst, &src_meta, opts)?;
     } else if file_type.is_socket() {
-        eprintln!("cp: warning: cannot copy socket '{}'", src.display());
+        util::warn(opts, format_args!("cannot copy socket '{}'", src.display()));
     } else {
-        copy_regular_file(src, dst, &src_meta, opts, pb)?;
+        method = copy_regular_file(src, dst, &src_meta, opts, pb)?;
+    }
+
+    // A socket was only warned about: there is nothing to label
+    #[cfg(feature = "selinux")]
+    if !file_type.is_socket() {
+        crate::selinux::set_dest_context(dst, opts)?;
+    }
+
+    if let (Some(stat), Some(m)) = (&opts.stat, method) {
+        stat.record(src, dst, &src_meta, m, started.elapsed());
+    }
+    if let Some(ref copy_stat) = opts.copy_stat
+        && !file_type.is_socket()
+    {
+        copy_stat.record_attrs(src, dst, &src_meta);
     }
 
     if opts.verbose {
+        // Data copies get a [size, method, duration] summary; with
+        // --verbose-size the size moves next to the source instead
+        let size_note = if opts.verbose_size && file_type.is_file() {
+            util::size_note(src_meta.len())
+        } else {
+            String::new()
+        };
+        // A special file's contents have no size until read
+        let copied_len = match contents_of {
+            Some(_) => fs::metadata(dst).map(|m| m.len()).unwrap_or(0),
+            None => src_meta.len(),
+        };
+        let detail = method
+            .map(|m| {
+                if opts.verbose_size {
+                    format!(" [{}, {:.2}s]", m, started.elapsed().as_secs_f64())
+                } else {
+                    format!(
+                        " [{}, {}, {:.2}s]",
+                        util::human_size(copied_len),
+                        m,
+                        started.elapsed().as_secs_f64()
+                    )
+                }
+            })
+            .unwrap_or_default();
+        let (src_note, dst_note) = match contents_of {
+            Some(kind) => (format!(" ({})", kind), " (regular file, contents copied)"),
+            None => (String::new(), ""),
+        };
         if let Some(ref bp) = backup_path {
             println!(
-                "'{}' -> '{}' (backup: '{}')",
+                "'{}'{}{} -> '{}'{}{} (backup: '{}')",
                 src.display(),
+                size_note,
+                src_note,
                 dst.display(),
+                dst_note,
+                detail,
                 bp.display()
             );
         } else {
-            println!("'{}' -> '{}'", src.display(), dst.display());
+            println!(
+                "'{}'{}{} -> '{}'{}{}",
+                src.display(),
+                size_note,
+                src_note,
+                dst.display(),
+                dst_note,
+                detail
+            );
         }
     }
 
     Ok(())
 }
 
+/// FIFOs and devices, whose contents --copy-contents reads.
+fn is_special(file_type: &fs::FileType) -> bool {
+    file_type.is_fifo() || file_type.is_char_device() || file_type.is_block_device()
+}
+
+/// How verbose output names a special file.
+fn special_kind(file_type: &fs::FileType) -> &'static str {
+    if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_char_device() {
+        "character device"
+    } else {
+        "block device"
+    }
+}
+
+/// Whether `src` and `dst` are the same file, from the stats copy_single
+/// already has: symlinks on either side are compared by what they point to
+/// (`dst_target_meta` for the destination), like `util::is_same_file`.
+fn is_same_file(
+    src: &Path,
+    src_meta: &fs::Metadata,
+    dst_meta: &fs::Metadata,
+    dst_target_meta: Option<&fs::Metadata>,
+) -> bool {
+    let dst_meta = if dst_meta.file_type().is_symlink() {
+        match dst_target_meta {
+            Some(m) => m,
+            None => return false,
+        }
+    } else {
+        dst_meta
+    };
+    let followed;
+    let src_meta = if src_meta.file_type().is_symlink() {
+        followed = fs::metadata(src);
+        match followed {
+            Ok(ref m) => m,
+            Err(_) => return false,
+        }
+    } else {
+        src_meta
+    };
+    (src_meta.dev(), src_meta.ino()) == (dst_meta.dev(), dst_meta.ino())
+}
+
+/// Copy a regular file's data and metadata.
+/// Returns the data copy method, or None when no data was copied (links, --attributes-only).
 fn copy_regular_file(
     src: &Path,
     dst: &Path,
     src_meta: &fs::Metadata,
     opts: &CopyOptions,
     pb: &ProgressBar,
-) -> CpResult<()> {
-    if opts.hard_link {
-        return do_hard_link(src, dst);
+) -> CpResult<Option<&'static str>> {
+    if opts.hard_link && do_hard_link(src, dst, opts)? {
+        return Ok(None);
     }
 
     if opts.symbolic_link {
-        return do_symbolic_link(src, dst);
+        return do_symbolic_link(src, dst).map(|()| None);
     }
 
     if opts.attributes_only {
+        // A new destination gets the source permissions less the umask, as
+        // with GNU; an existing one keeps its mode unless --preserve=mode
         if !dst.exists() {
-            File::create(dst).map_err(|e| CpError::CreateFile {
-                path: dst.to_path_buf(),
-                source: e,
-            })?;
+            fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .mode(src_meta.mode() & 0o777)
+                .open(dst)
+                .map_err(|e| CpError::CreateFile {
+                    path: dst.to_path_buf(),
+                    source: e,
+                })?;
         }
         metadata::preserve_metadata(src, dst, src_meta, opts, false)?;
-        return Ok(());
+        return Ok(None);
     }
 
-    let size = src_meta.len();
-
     // Open source
     let src_file = File::open(src).map_err(|e| CpError::OpenRead {
         path: src.to_path_buf(),
         source: e,
     })?;
 
-    // Open destination — File::create does open+truncate in one syscall
+    // Open destination — File::create does open+truncate in one syscall.
+    // A destination this copy creates is removed again if the data copy
+    // fails; an existing one is left truncated, as GNU does
+    let dst_existed = fs::symlink_metadata(dst).is_ok();
     let dst_file = open_dest_create(dst, opts)?;
 
+    let method = match copy_regular_data(src, dst, src_meta, src_file, dst_file, opts, pb) {
+        Ok(method) => method,
+        Err(e) => {
+            if !dst_existed {
+                let _ = fs::remove_file(dst);
+            }
+            return Err(e);
+        }
+    };
+
+    if opts.cow_verify && method.starts_with("reflink") && !util::verify_cow(src, dst) {
+        util::warn(
+            opts,
+            format!(
+                "'{}' does not appear to be a CoW clone of '{}'; data was likely fully copied",
+                dst.display(),
+                src.display()
+            ),
+        );
+    }
+
+    metadata::preserve_metadata(src, dst, src_meta, opts, false)?;
+    Ok(Some(method))
+}
+
+/// The data part of `copy_regular_file`, from open files to the method used.
+fn copy_regular_data(
+    src: &Path,
+    dst: &Path,
+    src_meta: &fs::Metadata,
+    src_file: File,
+    dst_file: File,
+    opts: &CopyOptions,
+    pb: &ProgressBar,
+) -> CpResult<&'static str> {
+    let size = src_meta.len();
+    let method;
     if size > 0 {
         // Skip sparse detection for small files — no meaningful holes
-        let use_sparse = opts.sparse != SparseMode::Never && size >= SPARSE_THRESHOLD;
+        let mut use_sparse = opts.sparse != SparseMode::Never && size >= SPARSE_THRESHOLD;
+        if use_sparse
+            && opts.sparse == SparseMode::Auto
+            && opts.no_sparse_on_copy_file_range
+            && copy_file_range_same_fs(src_meta, dst, opts)
+        {
+            use_sparse = false;
+            if opts.debug {
+                eprintln!("cp: sparse: skipped (copy_file_range handles holes natively)");
+            }
+        }
+        // --fadvise: SEQUENTIAL before reading a large source, DONTNEED after
+        let fadvise = opts.fadvise && size >= engine::FADVISE_THRESHOLD;
+        if fadvise {
+            engine::advise_sequential(&src_file);
+        }
 
-        if use_sparse {
-            let mut src_f = src_file;
-            let mut dst_f = dst_file;
-            if sparse::copy_sparse(&mut src_f, &mut dst_f, size, src, dst, opts.sparse, pb)? {
+        if opts.direct {
+            // --direct: bypass the page cache instead of any zero-copy engine
+            method = engine::copy_direct(&src_file, &dst_file, src, dst, opts.io_timeout, pb)?;
+            if opts.debug {
+                eprintln!("cp: copy method: {}", method);
+            }
+        } else if use_sparse {
+            let (sp, dp) = (src.to_path_buf(), dst.to_path_buf());
+            let (mode, ratio) = (opts.sparse, opts.sparse_ratio_threshold);
+            let sparse_done = engine::run_timed(
+                opts.io_timeout,
+                &src_file,
+                &dst_file,
+                src,
+                pb,
+                move |s, d, pb| sparse::copy_sparse(s, d, size, &sp, &dp, mode, ratio, pb),
+            )??;
+            if sparse_done {
                 if opts.debug {
-                    eprintln!("cp: copy method: sparse (SEEK_HOLE/SEEK_DATA)");
+                    let how = if opts.sparse == SparseMode::Auto {
+                        "SEEK_HOLE/SEEK_DATA"
+                    } else {
+                        "zero detection"
+                    };
+                    eprintln!("cp: copy method: sparse ({})", how);
+                }
+                if fadvise {
+                    engine::drop_cache(&src_file);
                 }
-                metadata::preserve_metadata(src, dst, src_meta, opts, false)?;
-                return Ok(());
+                return Ok("sparse");
             }
 
             // Sparse didn't handle it, reopen and do normal copy
-            drop(src_f);
-            drop(dst_f);
+            drop(src_file);
+            drop(dst_file);
             let src_file = File::open(src).map_err(|e| CpError::OpenRead {
                 path: src.to_path_buf(),
                 source: e,
             })?;
             let dst_file = open_dest_create(dst, opts)?;
+            if fadvise {
+                engine::advise_sequential(&src_file);
+            }
 
-            let method =
-                engine::copy_file_data(&src_file, &dst_file, size, src, dst, opts.reflink, pb)?;
+            method = engine::copy_file_data(
+                &src_file,
+                &dst_file,
+                size,
+                src,
+                dst,
+                opts.reflink,
+                opts.reflink_fallback,
+                opts.io_timeout,
+                opts.chunk_size,
+                pb,
+            )?;
             if opts.debug {
-                eprintln!("cp: copy method: {}", method);
+                debug_method(method, size, opts);
+            }
+            if fadvise {
+                engine::drop_cache(&src_file);
             }
         } else {
-            let method =
-                engine::copy_file_data(&src_file, &dst_file, size, src, dst, opts.reflink, pb)?;
+            method = engine::copy_file_data(
+                &src_file,
+                &dst_file,
+                size,
+                src,
+                dst,
+                opts.reflink,
+                opts.reflink_fallback,
+                opts.io_timeout,
+                opts.chunk_size,
+                pb,
+            )?;
             if opts.debug {
-                eprintln!("cp: copy method: {}", method);
+                debug_method(method, size, opts);
             }
+            if fadvise {
+                engine::drop_cache(&src_file);
+            }
+        }
+    } else {
+        method = engine::copy_unsized(&src_file, &dst_file, src, dst, opts.io_timeout, pb)?;
+        if opts.debug && method != "empty" {
+            eprintln!("cp: copy method: {} (size unknown)", method);
         }
     }
 
-    metadata::preserve_metadata(src, dst, src_meta, opts, false)?;
-    Ok(())
+    Ok(method)
+}
+
+/// --debug line for the data copy engine, with the --reflink-fallback reason.
+fn debug_method(method: &str, size: u64, opts: &CopyOptions) {
+    match engine::fallback_note(opts.reflink, opts.reflink_fallback, size) {
+        Some(note) if !method.starts_with("reflink") => {
+            eprintln!("cp: copy method: {} ({})", method, note)
+        }
+        _ => eprintln!("cp: copy method: {}", method),
+    }
+}
+
+/// --link-dest: hard-link `dst` to its counterpart in LINKDIR when that file
+/// is unchanged from the source — same size, mtime, mode and owner, so the
+/// link carries what a copy would. Returns false when the data must be
+/// copied, including when the link fails (e.g. LINKDIR on another device).
+fn link_unchanged(dst: &Path, src_meta: &fs::Metadata, opts: &CopyOptions) -> bool {
+    let (Some(link_dest), Some(root)) = (&opts.link_dest, &opts.link_dest_root) else {
+        return false;
+    };
+    let link_file = util::build_link_dest_path(dst, link_dest, root);
+    let Ok(link_meta) = fs::symlink_metadata(&link_file) else {
+        return false;
+    };
+    let unchanged = link_meta.is_file()
+        && link_meta.len() == src_meta.len()
+        && (link_meta.mtime(), link_meta.mtime_nsec()) == (src_meta.mtime(), src_meta.mtime_nsec())
+        && link_meta.mode() == src_meta.mode()
+        && (link_meta.uid(), link_meta.gid()) == (src_meta.uid(), src_meta.gid());
+
+    unchanged && fs::hard_link(&link_file, dst).is_ok()
+}
+
+/// --link-threshold: hard-link `src` to `dst` instead of copying it when it
+/// is larger than the threshold and `dst` lands on the same filesystem.
+/// An existing `dst` is replaced. False when no link was made (smaller file,
+/// other filesystem, EMLINK...): the caller then copies as usual.
+fn link_large(
+    src: &Path,
+    dst: &Path,
+    src_meta: &fs::Metadata,
+    dst_exists: bool,
+    opts: &CopyOptions,
+) -> bool {
+    let Some(threshold) = opts.link_threshold else {
+        return false;
+    };
+    // linkat doesn't follow symlinks: only link a source that is the file itself
+    if src_meta.len() <= threshold || !fs::symlink_metadata(src).is_ok_and(|m| m.is_file()) {
+        return false;
+    }
+    let dst_dir = match dst.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    if util::get_device(dst_dir).ok() != Some(src_meta.dev()) {
+        return false;
+    }
+
+    if dst_exists && fs::remove_file(dst).is_err() {
+        return false;
+    }
+    fs::hard_link(src, dst).is_ok()
+}
+
+/// --hardlink-across-sources: hard-link `dst` to an earlier copy of an
+/// identical file. An existing `dst` is replaced. False when there is none,
+/// or the link fails (EMLINK, ...): the caller then copies as usual.
+fn link_duplicate(
+    src: &Path,
+    dst: &Path,
+    src_meta: &fs::Metadata,
+    dst_exists: bool,
+    opts: &CopyOptions,
+) -> bool {
+    let Some(first) = opts.dedup.as_ref().and_then(|d| d.find(src, src_meta)) else {
+        return false;
+    };
+    if dst_exists && fs::remove_file(dst).is_err() {
+        return false;
+    }
+    fs::hard_link(first, dst).is_ok()
+}
+
+/// --no-sparse-on-copy-file-range: whether the data copy goes to
+/// copy_file_range (no clone-only or sendfile/read-write fallback forced)
+/// with both files on one filesystem.
+fn copy_file_range_same_fs(src_meta: &fs::Metadata, dst: &Path, opts: &CopyOptions) -> bool {
+    matches!(
+        opts.reflink_fallback,
+        ReflinkFallback::Auto | ReflinkFallback::Error | ReflinkFallback::CopyFileRange
+    ) && opts.reflink != ReflinkMode::Always
+        && util::get_device(dst).is_ok_and(|dev| dev == src_meta.dev())
+}
+
+/// Stream a regular file into an existing FIFO: no truncation, no sparse
+/// holes, and no metadata changes on the FIFO itself.
+fn copy_into_fifo(
+    src: &Path,
+    dst: &Path,
+    src_meta: &fs::Metadata,
+    opts: &CopyOptions,
+    pb: &ProgressBar,
+) -> CpResult<&'static str> {
+    let src_file = File::open(src).map_err(|e| CpError::OpenRead {
+        path: src.to_path_buf(),
+        source: e,
+    })?;
+
+    // Blocks until a reader opens the other end, like writing to it with cat
+    let dst_file =
+        fs::OpenOptions::new()
+            .write(true)
+            .open(dst)
+            .map_err(|e| CpError::CreateFile {
+                path: dst.to_path_buf(),
+                source: e,
+            })?;
+
+    let method = engine::copy_file_data(
+        &src_file,
+        &dst_file,
+        src_meta.len(),
+        src,
+        dst,
+        crate::cli::ReflinkMode::Never,
+        opts.reflink_fallback,
+        opts.io_timeout,
+        opts.chunk_size,
+        pb,
+    )?;
+    if opts.debug {
+        eprintln!("cp: copy method: {} (into FIFO)", method);
+    }
+    Ok(method)
 }
 
 /// Open dest with create+truncate in one syscall.
@@ -284,7 +878,7 @@ fn copy_symlink(
 }
 
 fn copy_fifo(dst: &Path, src_meta: &fs::Metadata, opts: &CopyOptions) -> CpResult<()> {
-    let mode = nix::sys::stat::Mode::from_bits_truncate(src_meta.mode());
+    let mode = nix::sys::stat::Mode::from_bits_truncate(src_meta.mode() as nix::libc::mode_t);
     nix::unistd::mkfifo(dst, mode).map_err(|e| CpError::MkNod {
         path: dst.to_path_buf(),
         source: e,
@@ -296,8 +890,8 @@ fn copy_fifo(dst: &Path, src_meta: &fs::Metadata, opts: &CopyOptions) -> CpResul
 }
 
 fn copy_device(dst: &Path, src_meta: &fs::Metadata, opts: &CopyOptions) -> CpResult<()> {
-    let mode = nix::sys::stat::Mode::from_bits_truncate(src_meta.mode());
-    let dev = src_meta.rdev();
+    let mode = nix::sys::stat::Mode::from_bits_truncate(src_meta.mode() as nix::libc::mode_t);
+    let dev = src_meta.rdev() as nix::libc::dev_t;
 
     let sflag = if src_meta.file_type().is_block_device() {
         nix::sys::stat::SFlag::S_IFBLK
@@ -315,19 +909,33 @@ fn copy_device(dst: &Path, src_meta: &fs::Metadata, opts: &CopyOptions) -> CpRes
     Ok(())
 }
 
-fn do_hard_link(src: &Path, dst: &Path) -> CpResult<()> {
+/// Returns false when `src` and `dst` are on different filesystems, leaving
+/// the caller to copy the data instead.
+fn do_hard_link(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<bool> {
     if dst.exists() {
         fs::remove_file(dst).map_err(|e| CpError::Remove {
             path: dst.to_path_buf(),
             source: e,
         })?;
     }
-    fs::hard_link(src, dst).map_err(|e| CpError::HardLink {
-        src: src.to_path_buf(),
-        dst: dst.to_path_buf(),
-        source: e,
-    })?;
-    Ok(())
+    match fs::hard_link(src, dst) {
+        Ok(()) => Ok(true),
+        Err(e) if e.raw_os_error() == Some(nix::libc::EXDEV) => {
+            util::warn(
+                opts,
+                format_args!(
+                    "cannot hard-link '{}' across filesystems, copying instead",
+                    dst.display()
+                ),
+            );
+            Ok(false)
+        }
+        Err(e) => Err(CpError::HardLink {
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+            source: e,
+        }),
+    }
 }
 
 fn do_symbolic_link(src: &Path, dst: &Path) -> CpResult<()> {
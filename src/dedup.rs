@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::cli::DedupMode;
+use crate::util;
+
+/// --hardlink-across-sources: the files copied so far, by (size, mtime), so
+/// a later identical file becomes a hard link to the first copy. Shared by
+/// every source argument and worker thread, hence the lock.
+#[derive(Clone)]
+pub struct DedupIndex {
+    mode: DedupMode,
+    copies: Arc<Mutex<Copies>>,
+}
+
+/// First destinations by (size, mtime); every one of them with =content.
+type Copies = HashMap<(u64, i64), Vec<PathBuf>>;
+
+impl fmt::Debug for DedupIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DedupIndex")
+    }
+}
+
+impl DedupIndex {
+    pub fn new(mode: DedupMode) -> Self {
+        DedupIndex {
+            mode,
+            copies: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// An earlier copy identical to `src`: same size and mtime, and with
+    /// `=content` the same bytes too.
+    pub fn find(&self, src: &Path, src_meta: &fs::Metadata) -> Option<PathBuf> {
+        let candidates = self.copies.lock().unwrap().get(&key(src_meta)).cloned()?;
+        candidates.into_iter().find(|copy| match self.mode {
+            DedupMode::Metadata => true,
+            DedupMode::Content => util::same_content(src, copy).unwrap_or(false),
+        })
+    }
+
+    /// Remember `dst` as the copy of a file with `src_meta`.
+    pub fn insert(&self, src_meta: &fs::Metadata, dst: &Path) {
+        let mut copies = self.copies.lock().unwrap();
+        let entry = copies.entry(key(src_meta)).or_default();
+        // Size and mtime alone: only the first copy is ever linked to
+        if self.mode == DedupMode::Content || entry.is_empty() {
+            entry.push(dst.to_path_buf());
+        }
+    }
+}
+
+fn key(meta: &fs::Metadata) -> (u64, i64) {
+    (meta.len(), meta.mtime())
+}
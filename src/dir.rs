@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString, OsStr};
 use std::fs;
+use std::io::Write;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::MetadataExt;
-use std::os::unix::io::RawFd;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
 use std::path::{Path, PathBuf};
 
 /// Convert raw bytes to OsStr (safe wrapper — bytes come from kernel dirent).
@@ -12,10 +13,15 @@ fn bytes_to_os(b: &[u8]) -> &OsStr {
     unsafe { OsStr::from_encoded_bytes_unchecked(b) }
 }
 
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
 use indicatif::ProgressBar;
-use walkdir::WalkDir;
+use walkdir::{DirEntryExt, WalkDir};
 
+use crate::backup;
+use crate::cli::SortOrder;
 use crate::copy;
+use crate::engine;
 use crate::error::{CpError, CpResult};
 use crate::metadata;
 use crate::options::{CopyOptions, Dereference};
@@ -25,6 +31,24 @@ use crate::util;
 /// Max chunk for copy_file_range (1 GiB — will return actual bytes for small files).
 const CFR_MAX: usize = 1024 * 1024 * 1024;
 
+/// Buffer for the read/write fallback when copy_file_range can't be used.
+const RW_BUF_SIZE: usize = 256 * 1024;
+
+/// Whiteout entry left by a union mount (<sys/dirent.h>; not in libc).
+#[cfg(target_os = "macos")]
+const DT_WHT: u8 = 14;
+
+/// The calling thread's errno, which readdir leaves as its only error report.
+#[cfg(not(target_os = "macos"))]
+fn errno_ptr() -> *mut nix::libc::c_int {
+    unsafe { nix::libc::__errno_location() }
+}
+
+#[cfg(target_os = "macos")]
+fn errno_ptr() -> *mut nix::libc::c_int {
+    unsafe { nix::libc::__error() }
+}
+
 /// Copy a directory recursively.
 pub fn copy_directory(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<()> {
     // Check for copy-into-self
@@ -35,73 +59,290 @@ pub fn copy_directory(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<()
         });
     }
 
+    let dst_existed = dst.exists();
+
     // Fast path: openat-based raw copy (no walkdir, no PathBuf allocations)
-    if copy::is_simple_opts(opts) && opts.dereference != Dereference::Always {
-        return copy_directory_raw(src, dst, opts);
+    let result = if copy::is_simple_opts(opts) && opts.dereference != Dereference::Always {
+        copy_directory_raw(src, dst, opts).map_err(absolute_paths)
+    } else {
+        // Slow path: walkdir-based copy for complex options
+        copy_directory_walkdir(src, dst, opts)
+    };
+
+    // --max-files/--max-total-size with --remove-destination: don't leave a
+    // partial tree behind
+    if let Err(CpError::MaxFilesExceeded { .. } | CpError::MaxSizeExceeded { .. }) = result
+        && opts.remove_destination
+        && !dst_existed
+    {
+        let _ = fs::remove_dir_all(dst);
+    }
+
+    result
+}
+
+/// Hard link tracking: (dev, ino) -> (first destination, names of the inode
+/// met in the source tree so far).
+type HardLinkMap = HashMap<(u64, u64), (PathBuf, usize)>;
+
+/// `HardLinkMap` shared by the parallel file copy threads.
+type SharedHardLinkMap = DashMap<(u64, u64), (PathBuf, usize)>;
+
+/// Parallel copy: a later link of an inode, created once all copies finished.
+/// (dev, ino), destination path, entry name in the source directory.
+type DeferredLink = ((u64, u64), PathBuf, CString);
+
+/// Replace the existing directory `dst` with a copy of `src` in one step.
+/// The tree is built in a sibling temporary directory (same filesystem), then
+/// swapped in with `renameat2(RENAME_EXCHANGE)`; the old tree is moved to the
+/// backup path when --backup is set, or removed otherwise. A filesystem that
+/// can't swap fails the copy and leaves `dst` as it was.
+pub fn copy_directory_atomic(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<()> {
+    let tmp = create_temp_sibling(dst)?;
+
+    if let Err(e) = copy_directory(src, &tmp, opts) {
+        let _ = fs::remove_dir_all(&tmp);
+        return Err(e);
+    }
+
+    if let Err(e) = exchange_paths(&tmp, dst) {
+        let _ = fs::remove_dir_all(&tmp);
+        let reason = if e.kind() == std::io::ErrorKind::Unsupported {
+            "atomic replace not supported on this filesystem".to_string()
+        } else {
+            format!("cannot replace directory atomically: {}", e)
+        };
+        return Err(CpError::Copy {
+            src: src.to_path_buf(),
+            dst: dst.to_path_buf(),
+            reason,
+        });
+    }
+
+    // `tmp` now holds the previous tree
+    match backup::backup_path(dst, opts.backup, &opts.backup_suffix) {
+        Some(bp) => fs::rename(&tmp, &bp).map_err(|e| CpError::Copy {
+            src: tmp.clone(),
+            dst: bp,
+            reason: e.to_string(),
+        }),
+        None => fs::remove_dir_all(&tmp).map_err(|e| CpError::Remove {
+            path: tmp.clone(),
+            source: e,
+        }),
     }
+}
+
+/// Create an empty `dst.~tmp~N` directory next to `dst`.
+fn create_temp_sibling(dst: &Path) -> CpResult<PathBuf> {
+    let mut n = 1u64;
+    loop {
+        let mut s = dst.as_os_str().to_os_string();
+        s.push(format!(".~tmp~{}", n));
+        let candidate = PathBuf::from(s);
+        match fs::create_dir(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => {
+                return Err(CpError::CreateDir {
+                    path: candidate,
+                    source: e,
+                });
+            }
+        }
+    }
+}
+
+/// Atomically swap two paths. Without RENAME_EXCHANGE (or RENAME_SWAP)
+/// in the kernel or filesystem the error is `ErrorKind::Unsupported`:
+/// swapping with two renames would leave `b` missing in between, which is
+/// what --atomic-dir is there to rule out.
+fn exchange_paths(a: &Path, b: &Path) -> std::io::Result<()> {
+    let ca = CString::new(a.as_os_str().as_bytes())?;
+    let cb = CString::new(b.as_os_str().as_bytes())?;
+    if rename_exchange(&ca, &cb) == 0 {
+        return Ok(());
+    }
+
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(nix::libc::EINVAL | nix::libc::ENOSYS | nix::libc::ENOTSUP) => {
+            Err(std::io::ErrorKind::Unsupported.into())
+        }
+        _ => Err(err),
+    }
+}
+
+/// renameat2(RENAME_EXCHANGE): swap `a` and `b` in one step.
+#[cfg(not(target_os = "macos"))]
+fn rename_exchange(a: &CStr, b: &CStr) -> nix::libc::c_int {
+    unsafe {
+        nix::libc::renameat2(
+            nix::libc::AT_FDCWD,
+            a.as_ptr(),
+            nix::libc::AT_FDCWD,
+            b.as_ptr(),
+            nix::libc::RENAME_EXCHANGE,
+        )
+    }
+}
 
-    // Slow path: walkdir-based copy for complex options
-    copy_directory_walkdir(src, dst, opts)
+/// macOS swaps with renamex_np(RENAME_SWAP).
+#[cfg(target_os = "macos")]
+fn rename_exchange(a: &CStr, b: &CStr) -> nix::libc::c_int {
+    unsafe { nix::libc::renamex_np(a.as_ptr(), b.as_ptr(), nix::libc::RENAME_SWAP) }
 }
 
 /// State shared across the recursive raw copy.
 struct RawCopyState<'a> {
     opts: &'a CopyOptions,
-    hard_link_map: Option<HashMap<(u64, u64), PathBuf>>,
+    hard_link_map: Option<HardLinkMap>,
     src_dev: Option<u64>,
+    /// (dev, ino) of the destination root, skipped if met inside the source (-x)
+    dst_root: Option<(u64, u64)>,
     need_file_meta: bool,
     need_dir_meta: bool,
     /// Deferred directory metadata: (src_path, dst_path, stat)
     dir_meta: Vec<(PathBuf, PathBuf, nix::libc::stat)>,
     /// Progress counter for directory copy
     progress: std::sync::Arc<progress::DirProgressCounter>,
+    /// Directories created by the copy, only tracked for --dir-mode
+    created_dirs: Vec<PathBuf>,
+    /// (dev, ino) of the source directories being copied, root first: a bind
+    /// mount can make a directory its own descendant
+    ancestors: Vec<(u64, u64)>,
+    /// Directory fds the source and destination paths are relative to:
+    /// AT_FDCWD but for `copy_at`
+    bases: (RawFd, RawFd),
+}
+
+impl<'a> RawCopyState<'a> {
+    fn new(
+        opts: &'a CopyOptions,
+        src_dev: Option<u64>,
+        progress: progress::DirProgressCounter,
+    ) -> Self {
+        Self {
+            opts,
+            hard_link_map: if opts.preserve_links {
+                Some(HashMap::new())
+            } else {
+                None
+            },
+            src_dev,
+            dst_root: None,
+            need_file_meta: opts.preserve_mode
+                || opts.preserve_ownership
+                || opts.preserve_timestamps
+                || opts.preserve_xattr
+                || opts.preserve_acl
+                || opts.preserve_flags,
+            need_dir_meta: opts.preserve_mode
+                || opts.preserve_ownership
+                || opts.preserve_timestamps
+                || opts.preserve_flags,
+            dir_meta: Vec::new(),
+            progress: std::sync::Arc::new(progress),
+            created_dirs: Vec::new(),
+            ancestors: Vec::new(),
+            bases: (nix::libc::AT_FDCWD, nix::libc::AT_FDCWD),
+        }
+    }
+
+    /// Count one more file against --max-files, before it is created.
+    /// `dir` is the source directory being copied, for the error message.
+    fn claim_file(&self, dir: &Path) -> CpResult<()> {
+        claim_file(dir, self.opts)
+    }
+
+    /// `tolerate`, warning with the paths of the error made absolute (see
+    /// `absolute_paths`) unless they are relative to `copy_at`'s directory
+    /// fds. --ignore-errors-from matches `src` as given.
+    fn tolerate(&self, err: CpError, src: &Path) -> CpResult<()> {
+        if self.bases == (nix::libc::AT_FDCWD, nix::libc::AT_FDCWD) {
+            tolerate(absolute_paths(err), src, self.opts)
+        } else {
+            tolerate(err, src, self.opts)
+        }
+    }
 }
 
 /// Ultra-fast directory copy using raw libc: openat, readdir, mkdirat.
 /// Zero PathBuf allocations in the hot path — paths only built for errors/metadata.
 fn copy_directory_raw(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<()> {
     // Create destination root
-    if !dst.exists() {
-        fs::create_dir_all(dst).map_err(|e| CpError::CreateDir {
-            path: dst.to_path_buf(),
-            source: e,
-        })?;
-    }
+    let mut created_root = Vec::new();
+    create_dirs(dst, &mut created_root, opts)?;
 
     let src_fd = open_dir_fd(src)?;
-    let dst_fd = open_dir_fd(dst)?;
+    let dst_fd = match open_dir_fd(dst) {
+        Ok(fd) => fd,
+        Err(e) => {
+            unsafe { nix::libc::close(src_fd) };
+            return Err(e);
+        }
+    };
+
+    // src_fd is consumed by the directory stream
+    let bases = (nix::libc::AT_FDCWD, nix::libc::AT_FDCWD);
+    let result = copy_dir_fds(src_fd, dst_fd, src, dst, bases, opts);
+    unsafe { nix::libc::close(dst_fd) };
+
+    result?;
+    apply_dir_mode(nix::libc::AT_FDCWD, &created_root, opts)
+}
+
+/// Name the files of a raw-path error by absolute path, wherever cp was run
+/// from. Only the message changes: the copy itself, -v lines and
+/// --ignore-errors-from keep the paths as given. Symlinks are not resolved.
+fn absolute_paths(mut err: CpError) -> CpError {
+    for path in err.paths_mut() {
+        if let Ok(abs) = std::path::absolute(&*path) {
+            *path = abs;
+        }
+    }
+    err
+}
 
+/// Copy the contents of directory `src_fd` into directory `dst_fd`.
+/// `src`/`dst` name them for messages and for the steps done once the tree
+/// is copied (hard links, directory metadata), relative to `bases`.
+/// Takes ownership of `src_fd` (see `copy_dir_recurse`); `dst_fd` stays with the caller.
+fn copy_dir_fds(
+    src_fd: RawFd,
+    dst_fd: RawFd,
+    src: &Path,
+    dst: &Path,
+    bases: (RawFd, RawFd),
+    opts: &CopyOptions,
+) -> CpResult<()> {
     let src_dev = if opts.one_file_system {
         Some(fstat_dev(src_fd))
     } else {
         None
     };
 
-    let dir_pb = progress::make_dir_progress(&src.display().to_string(), opts.progress);
-    let progress_counter = std::sync::Arc::new(progress::DirProgressCounter::new(dir_pb));
+    let dir_pb = progress::make_dir_progress(
+        &src.display().to_string(),
+        opts.progress,
+        &opts.progress_target,
+    );
+    let mut state = RawCopyState::new(opts, src_dev, progress::DirProgressCounter::new(dir_pb));
+    state.bases = bases;
 
-    let mut state = RawCopyState {
-        opts,
-        hard_link_map: if opts.preserve_links {
-            Some(HashMap::new())
-        } else {
-            None
-        },
-        src_dev,
-        need_file_meta: opts.preserve_mode
-            || opts.preserve_ownership
-            || opts.preserve_timestamps
-            || opts.preserve_xattr
-            || opts.preserve_acl,
-        need_dir_meta: opts.preserve_mode || opts.preserve_ownership || opts.preserve_timestamps,
-        dir_meta: Vec::new(),
-        progress: progress_counter,
-    };
+    // Destination nested inside the source: never descend into it
+    if opts.one_file_system {
+        let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { nix::libc::fstat(dst_fd, &mut stat) } == 0 {
+            state.dst_root = Some(stat_key(&stat));
+        }
+    }
 
     // Save root directory metadata if needed
-    if state.need_dir_meta {
-        let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
-        if unsafe { nix::libc::fstat(src_fd, &mut stat) } == 0 {
+    let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { nix::libc::fstat(src_fd, &mut stat) } == 0 {
+        state.ancestors.push(stat_key(&stat));
+        if state.need_dir_meta {
             state
                 .dir_meta
                 .push((src.to_path_buf(), dst.to_path_buf(), stat));
@@ -110,33 +351,254 @@ fn copy_directory_raw(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<()
 
     copy_dir_recurse(src_fd, dst_fd, src, dst, &mut state)?;
 
-    unsafe {
-        nix::libc::close(src_fd);
-        nix::libc::close(dst_fd);
+    if opts.preserve_links
+        && opts.verbose
+        && let Some(ref hlmap) = state.hard_link_map
+    {
+        verify_hard_links(hlmap, bases.1, opts);
     }
 
     // Apply deferred directory metadata in reverse order (deepest first)
     for (src_path, dst_path, stat) in state.dir_meta.iter().rev() {
-        apply_dir_metadata(dst_path, stat, state.opts)?;
-        // xattr + ACL need path-based (only for directories, rare)
-        if state.opts.preserve_xattr {
-            metadata::preserve_xattr_pub(src_path, dst_path).ok();
-        }
-        if state.opts.preserve_acl {
-            metadata::preserve_acl_pub(src_path, dst_path).ok();
-        }
+        apply_dir_metadata(src_path, dst_path, stat, bases, state.opts)?;
     }
+    apply_dir_mode(bases.1, &state.created_dirs, state.opts)?;
 
     state.progress.finish();
 
     Ok(())
 }
 
+/// Copy `src_name` (relative to directory fd `src_dir_fd`) to `dst_name`
+/// (relative to directory fd `dst_dir_fd`) without resolving absolute paths.
+///
+/// Directories are copied recursively through the openat fast path, so options
+/// needing per-file decisions (-i, -n, -u, --backup) are not consulted.
+/// Messages name `src_name`/`dst_name` and what is below them; the steps
+/// done once the tree is copied (hard links, directory metadata) resolve
+/// those names against the same fds, which stay the only root of resolution.
+pub fn copy_at(
+    src_dir_fd: RawFd,
+    src_name: &Path,
+    dst_dir_fd: RawFd,
+    dst_name: &Path,
+    opts: &CopyOptions,
+) -> CpResult<()> {
+    let src = src_name.to_path_buf();
+    let dst = dst_name.to_path_buf();
+
+    let c_src = CString::new(src_name.as_os_str().as_bytes()).map_err(|_| CpError::OpenRead {
+        path: src.clone(),
+        source: std::io::Error::from_raw_os_error(nix::libc::EINVAL),
+    })?;
+    let c_dst = CString::new(dst_name.as_os_str().as_bytes()).map_err(|_| CpError::CreateFile {
+        path: dst.clone(),
+        source: std::io::Error::from_raw_os_error(nix::libc::EINVAL),
+    })?;
+
+    let flags = if opts.dereference == Dereference::Never {
+        nix::libc::AT_SYMLINK_NOFOLLOW
+    } else {
+        0
+    };
+    let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { nix::libc::fstatat(src_dir_fd, c_src.as_ptr(), &mut stat, flags) } != 0 {
+        return Err(CpError::Stat {
+            path: src,
+            source: std::io::Error::last_os_error(),
+        });
+    }
+
+    match stat.st_mode & nix::libc::S_IFMT {
+        nix::libc::S_IFDIR => {
+            if !opts.recursive {
+                return Err(CpError::OmitDirectory { path: src });
+            }
+
+            let ret = unsafe { nix::libc::mkdirat(dst_dir_fd, c_dst.as_ptr(), 0o777) };
+            if ret != 0 {
+                let err = std::io::Error::last_os_error();
+                if err.raw_os_error() != Some(nix::libc::EEXIST) {
+                    return Err(CpError::CreateDir {
+                        path: dst,
+                        source: err,
+                    });
+                }
+            }
+
+            let src_fd = open_dir_at(src_dir_fd, &c_src, &src)?;
+            let dst_fd = match open_dir_at(dst_dir_fd, &c_dst, &dst) {
+                Ok(fd) => fd,
+                Err(e) => {
+                    unsafe { nix::libc::close(src_fd) };
+                    return Err(e);
+                }
+            };
+
+            // src_fd is consumed by the directory stream
+            let bases = (src_dir_fd, dst_dir_fd);
+            let result = copy_dir_fds(src_fd, dst_fd, &src, &dst, bases, opts);
+            unsafe { nix::libc::close(dst_fd) };
+
+            result
+        }
+        nix::libc::S_IFLNK => {
+            let mut state = RawCopyState::new(
+                opts,
+                None,
+                progress::DirProgressCounter::new(ProgressBar::hidden()),
+            );
+            state.bases = (src_dir_fd, dst_dir_fd);
+            copy_symlink_at(src_dir_fd, dst_dir_fd, &c_src, &c_dst, &src, &dst, &state)
+        }
+        nix::libc::S_IFREG => {
+            let mut dst_stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+            if unsafe { nix::libc::fstatat(dst_dir_fd, c_dst.as_ptr(), &mut dst_stat, 0) } == 0
+                && dst_stat.st_dev == stat.st_dev
+                && dst_stat.st_ino == stat.st_ino
+            {
+                return Err(CpError::SameFile { src, dst });
+            }
+            // Anything at the name, a dangling symlink included
+            let dst_existed = unsafe {
+                nix::libc::fstatat(
+                    dst_dir_fd,
+                    c_dst.as_ptr(),
+                    &mut dst_stat,
+                    nix::libc::AT_SYMLINK_NOFOLLOW,
+                )
+            } == 0;
+
+            let src_fd = unsafe {
+                nix::libc::openat(
+                    src_dir_fd,
+                    c_src.as_ptr(),
+                    nix::libc::O_RDONLY | nix::libc::O_CLOEXEC,
+                )
+            };
+            if src_fd < 0 {
+                return Err(CpError::OpenRead {
+                    path: src,
+                    source: std::io::Error::last_os_error(),
+                });
+            }
+            let dst_fd = unsafe {
+                nix::libc::openat(
+                    dst_dir_fd,
+                    c_dst.as_ptr(),
+                    nix::libc::O_WRONLY
+                        | nix::libc::O_CREAT
+                        | nix::libc::O_TRUNC
+                        | nix::libc::O_CLOEXEC,
+                    0o666,
+                )
+            };
+            if dst_fd < 0 {
+                unsafe { nix::libc::close(src_fd) };
+                return Err(CpError::CreateFile {
+                    path: dst,
+                    source: std::io::Error::last_os_error(),
+                });
+            }
+
+            // Don't leave a partial copy behind, unless it replaced a file
+            let result = copy_fds(src_fd, dst_fd, &src, &dst, Some(&stat), opts);
+            if result.is_err() && !dst_existed {
+                unsafe { nix::libc::unlinkat(dst_dir_fd, c_dst.as_ptr(), 0) };
+            }
+            result
+        }
+        _ => Err(CpError::Copy {
+            src,
+            dst,
+            reason: "unsupported file type".into(),
+        }),
+    }
+}
+
+/// Copy the data of open file `src_fd` into `dst_fd`, then apply the
+/// metadata the options preserve from `stat`, and close both fds.
+/// `src`/`dst` are only used in error messages.
+pub fn copy_fds(
+    src_fd: RawFd,
+    dst_fd: RawFd,
+    src: &Path,
+    dst: &Path,
+    stat: Option<&nix::libc::stat>,
+    opts: &CopyOptions,
+) -> CpResult<()> {
+    let state = RawCopyState::new(
+        opts,
+        None,
+        progress::DirProgressCounter::new(ProgressBar::hidden()),
+    );
+    copy_and_close(
+        src_fd,
+        dst_fd,
+        || (src.to_path_buf(), dst.to_path_buf()),
+        stat,
+        &state,
+        false,
+    )
+}
+
 /// Minimum files in a directory to trigger parallel copy.
 const PARALLEL_THRESHOLD: usize = 64;
 
+/// Owns a directory stream; `closedir` also closes the fd it was opened on.
+struct DirStream(*mut nix::libc::DIR);
+
+impl DirStream {
+    /// Read the directory open as `fd`, taking ownership of it (closed on
+    /// failure too). The same fd keeps serving as an openat base until the
+    /// stream is dropped.
+    fn open(fd: RawFd, path: &Path) -> CpResult<Self> {
+        let dirp = unsafe { nix::libc::fdopendir(fd) };
+        if dirp.is_null() {
+            let err = std::io::Error::last_os_error();
+            unsafe { nix::libc::close(fd) };
+            return Err(CpError::OpenRead {
+                path: path.to_path_buf(),
+                source: err,
+            });
+        }
+        Ok(DirStream(dirp))
+    }
+
+    /// The next entry but `.` and `..`, as (name, d_type, d_ino); None at the
+    /// end. readdir returns NULL both at end-of-directory and on error: only
+    /// errno tells them apart.
+    fn next_entry(&mut self) -> std::io::Result<Option<(&CStr, u8, u64)>> {
+        loop {
+            unsafe { *errno_ptr() = 0 };
+            let entry = unsafe { nix::libc::readdir(self.0) };
+            if entry.is_null() {
+                return match unsafe { *errno_ptr() } {
+                    0 => Ok(None),
+                    errno => Err(std::io::Error::from_raw_os_error(errno)),
+                };
+            }
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            if name.to_bytes() != b"." && name.to_bytes() != b".." {
+                return Ok(Some(unsafe { (name, (*entry).d_type, (*entry).d_ino) }));
+            }
+        }
+    }
+}
+
+impl Drop for DirStream {
+    fn drop(&mut self) {
+        unsafe { nix::libc::closedir(self.0) };
+    }
+}
+
+/// A subdirectory to recurse into: source and destination fds and paths,
+/// and the source's (dev, ino).
+type Subdir = (OwnedFd, OwnedFd, PathBuf, PathBuf, (u64, u64));
+
 /// Recurse into a directory using readdir + openat.
 /// Files are copied in parallel using scoped threads when there are enough entries.
+/// Takes ownership of `src_fd`: it backs the directory stream and is closed with it.
 fn copy_dir_recurse(
     src_fd: RawFd,
     dst_fd: RawFd,
@@ -144,68 +606,94 @@ fn copy_dir_recurse(
     dst_path: &Path,
     state: &mut RawCopyState,
 ) -> CpResult<()> {
-    // dup the fd because fdopendir takes ownership
-    let src_fd_dup = unsafe { nix::libc::dup(src_fd) };
-    if src_fd_dup < 0 {
-        return Err(CpError::OpenRead {
-            path: src_path.to_path_buf(),
-            source: std::io::Error::last_os_error(),
-        });
-    }
-
-    let dirp = unsafe { nix::libc::fdopendir(src_fd_dup) };
-    if dirp.is_null() {
-        unsafe { nix::libc::close(src_fd_dup) };
-        return Err(CpError::OpenRead {
-            path: src_path.to_path_buf(),
-            source: std::io::Error::last_os_error(),
-        });
-    }
+    // The stream takes ownership of src_fd, which stays the openat base
+    let mut stream = DirStream::open(src_fd, src_path)?;
 
     // Phase 1: Read all directory entries (readdir buffer is reused, so we must copy names)
     let mut reg_files: Vec<CString> = Vec::new();
+    // d_ino of each regular file, only collected for --sort=inode
+    let mut reg_inodes: Vec<u64> = Vec::new();
     let mut symlinks: Vec<CString> = Vec::new();
-    let mut subdirs: Vec<(RawFd, RawFd, PathBuf, PathBuf)> = Vec::new();
+    let mut subdirs: Vec<Subdir> = Vec::new();
     let mut special_files: Vec<(CString, u8)> = Vec::new(); // (name, d_type)
 
-    loop {
-        unsafe { *nix::libc::__errno_location() = 0 };
-        let entry = unsafe { nix::libc::readdir(dirp) };
-        if entry.is_null() {
-            break;
-        }
+    let mut read_err: Option<CpError> = None;
 
-        let d_type = unsafe { (*entry).d_type };
-        let d_name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+    loop {
+        let (d_name, d_type, d_ino) = match stream.next_entry() {
+            Ok(Some(entry)) => entry,
+            Ok(None) => break,
+            Err(e) => {
+                // Copy what was read, then report: never a silent partial copy
+                read_err = Some(CpError::ReadDir {
+                    path: src_path.to_path_buf(),
+                    source: e,
+                });
+                break;
+            }
+        };
         let name_bytes = d_name.to_bytes();
 
-        if name_bytes == b"." || name_bytes == b".." {
-            continue;
-        }
-
         match d_type {
             nix::libc::DT_REG => {
                 reg_files.push(d_name.to_owned());
+                if state.opts.sort == SortOrder::Inode {
+                    reg_inodes.push(d_ino);
+                }
             }
             nix::libc::DT_LNK => {
                 symlinks.push(d_name.to_owned());
             }
             nix::libc::DT_DIR => {
-                // One-file-system check
-                if let Some(dev) = state.src_dev {
-                    let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
-                    if unsafe {
-                        nix::libc::fstatat(
-                            src_fd,
-                            d_name.as_ptr(),
-                            &mut stat,
-                            nix::libc::AT_SYMLINK_NOFOLLOW,
-                        )
-                    } == 0
-                        && stat.st_dev != dev
-                    {
-                        continue;
+                let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+                let key = if unsafe {
+                    nix::libc::fstatat(
+                        src_fd,
+                        d_name.as_ptr(),
+                        &mut stat,
+                        nix::libc::AT_SYMLINK_NOFOLLOW,
+                    )
+                } == 0
+                {
+                    stat_key(&stat)
+                } else {
+                    (0, 0)
+                };
+
+                // One-file-system check: the destination never, a mount
+                // point as an empty directory, as GNU does
+                if let Some(dev) = state.src_dev
+                    && key != (0, 0)
+                    && (key.0 != dev || state.dst_root == Some(key))
+                {
+                    if key.0 != dev && state.dst_root != Some(key) {
+                        let ret = unsafe { nix::libc::mkdirat(dst_fd, d_name.as_ptr(), 0o777) };
+                        if ret != 0 {
+                            let err = std::io::Error::last_os_error();
+                            if err.raw_os_error() != Some(nix::libc::EEXIST) {
+                                return Err(CpError::CreateDir {
+                                    path: dst_path.join(bytes_to_os(name_bytes)),
+                                    source: err,
+                                });
+                            }
+                        }
+                        if state.need_dir_meta {
+                            let name = bytes_to_os(name_bytes);
+                            state
+                                .dir_meta
+                                .push((src_path.join(name), dst_path.join(name), stat));
+                        }
                     }
+                    continue;
+                }
+
+                // The same directory further up: copying it would never end.
+                // Skip it, copy the rest, then report
+                if state.ancestors.contains(&key) {
+                    read_err.get_or_insert(CpError::Cycle {
+                        path: src_path.join(bytes_to_os(name_bytes)),
+                    });
+                    continue;
                 }
 
                 // mkdirat — single syscall, ignore EEXIST
@@ -213,12 +701,15 @@ fn copy_dir_recurse(
                 if ret != 0 {
                     let err = std::io::Error::last_os_error();
                     if err.raw_os_error() != Some(nix::libc::EEXIST) {
-                        unsafe { nix::libc::closedir(dirp) };
                         return Err(CpError::CreateDir {
                             path: dst_path.join(bytes_to_os(name_bytes)),
                             source: err,
                         });
                     }
+                } else if state.opts.dir_mode.is_some() {
+                    state
+                        .created_dirs
+                        .push(dst_path.join(bytes_to_os(name_bytes)));
                 }
 
                 let child_src_fd = unsafe {
@@ -249,7 +740,14 @@ fn copy_dir_recurse(
                         }
                     }
 
-                    subdirs.push((child_src_fd, child_dst_fd, child_src, child_dst));
+                    // Owned from here on: closed exactly once, on every path
+                    let (child_src_fd, child_dst_fd) = unsafe {
+                        (
+                            OwnedFd::from_raw_fd(child_src_fd),
+                            OwnedFd::from_raw_fd(child_dst_fd),
+                        )
+                    };
+                    subdirs.push((child_src_fd, child_dst_fd, child_src, child_dst, key));
                 } else {
                     if child_src_fd >= 0 {
                         unsafe { nix::libc::close(child_src_fd) };
@@ -263,23 +761,58 @@ fn copy_dir_recurse(
                 special_files.push((d_name.to_owned(), d_type));
             }
             nix::libc::DT_SOCK => {
-                eprintln!(
-                    "cp: warning: cannot copy socket '{}'",
-                    src_path.join(bytes_to_os(name_bytes)).display()
+                util::warn(
+                    state.opts,
+                    format_args!(
+                        "cannot copy socket '{}'",
+                        src_path.join(bytes_to_os(name_bytes)).display()
+                    ),
+                );
+            }
+            // A name deleted in a union mount's upper layer: nothing to copy
+            #[cfg(target_os = "macos")]
+            DT_WHT => {
+                util::warn(
+                    state.opts,
+                    format_args!(
+                        "skipping whiteout '{}'",
+                        src_path.join(bytes_to_os(name_bytes)).display()
+                    ),
                 );
             }
             _ => {}
         }
     }
 
-    unsafe { nix::libc::closedir(dirp) };
+    match state.opts.sort {
+        SortOrder::None => {}
+        SortOrder::Name => {
+            reg_files.sort_unstable();
+            symlinks.sort_unstable();
+            special_files.sort_unstable();
+            subdirs.sort_unstable_by(|a, b| a.2.cmp(&b.2));
+        }
+        SortOrder::Inode => {
+            // d_ino comes with the dirent: no fstatat needed
+            let mut keyed: Vec<(u64, CString)> =
+                reg_inodes.drain(..).zip(reg_files.drain(..)).collect();
+            keyed.sort_unstable_by_key(|(ino, _)| *ino);
+            reg_files.extend(keyed.into_iter().map(|(_, name)| name));
+        }
+    }
 
     // Phase 2: Copy regular files — parallel when enough entries
     if reg_files.len() >= PARALLEL_THRESHOLD {
         copy_files_parallel(&reg_files, src_fd, dst_fd, src_path, dst_path, state)?;
     } else {
         for name in &reg_files {
-            copy_file_openat(src_fd, dst_fd, name.as_c_str(), src_path, dst_path, state)?;
+            state.claim_file(src_path)?;
+            if let Err(e) =
+                copy_file_openat(src_fd, dst_fd, name.as_c_str(), src_path, dst_path, state)
+            {
+                let src = src_path.join(bytes_to_os(name.as_bytes()));
+                state.tolerate(e, &src)?;
+            }
             state.progress.inc();
         }
     }
@@ -287,16 +820,52 @@ fn copy_dir_recurse(
     if state.opts.verbose {
         for name in &reg_files {
             let nb = name.as_bytes();
+            let dst_file = dst_path.join(bytes_to_os(nb));
+            let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+            let have_stat = (state.opts.verbose_size || state.hard_link_map.is_some())
+                && unsafe {
+                    nix::libc::fstatat(
+                        src_fd,
+                        name.as_ptr(),
+                        &mut stat,
+                        nix::libc::AT_SYMLINK_NOFOLLOW,
+                    )
+                } == 0;
+
+            // A later link of an inode was linked to the first copy, not copied
+            if have_stat
+                && stat.st_nlink > 1
+                && let Some((first, _)) = state
+                    .hard_link_map
+                    .as_ref()
+                    .and_then(|m| m.get(&stat_key(&stat)))
+                && *first != dst_file
+            {
+                println!(
+                    "hard linked '{}' => '{}'",
+                    dst_file.display(),
+                    first.display()
+                );
+                continue;
+            }
+
+            let size_note = if state.opts.verbose_size && have_stat {
+                util::size_note(stat.st_size as u64)
+            } else {
+                String::new()
+            };
             println!(
-                "'{}' -> '{}'",
+                "'{}'{} -> '{}'",
                 src_path.join(bytes_to_os(nb)).display(),
-                dst_path.join(bytes_to_os(nb)).display()
+                size_note,
+                dst_file.display()
             );
         }
     }
 
     // Phase 3: Create special files (FIFOs, devices)
     for (name, dtype) in &special_files {
+        state.claim_file(src_path)?;
         let name_os = bytes_to_os(name.as_bytes());
         let src_special = src_path.join(name_os);
         let dst_special = dst_path.join(name_os);
@@ -361,29 +930,248 @@ fn copy_dir_recurse(
 
     // Phase 4: Copy symlinks (sequential — usually few)
     for name in &symlinks {
-        copy_symlink_at(
+        state.claim_file(src_path)?;
+        let name_os = bytes_to_os(name.as_bytes());
+        let src = src_path.join(name_os);
+        if let Err(e) = copy_symlink_at(
             src_fd,
             dst_fd,
             name.as_c_str(),
-            src_path,
-            dst_path,
-            state.opts,
-        )?;
+            name.as_c_str(),
+            &src,
+            &dst_path.join(name_os),
+            state,
+        ) {
+            state.tolerate(e, &src)?;
+        }
         state.progress.inc();
     }
 
-    // Phase 4: Recurse into subdirectories
-    for (child_src_fd, child_dst_fd, child_src, child_dst) in subdirs {
-        copy_dir_recurse(child_src_fd, child_dst_fd, &child_src, &child_dst, state)?;
-        unsafe {
-            nix::libc::close(child_src_fd);
-            nix::libc::close(child_dst_fd);
+    // Phase 4: Recurse into subdirectories (remaining fds close on drop if one fails)
+    for (child_src_fd, child_dst_fd, child_src, child_dst, key) in subdirs {
+        state.ancestors.push(key);
+        copy_dir_recurse(
+            child_src_fd.into_raw_fd(),
+            child_dst_fd.as_raw_fd(),
+            &child_src,
+            &child_dst,
+            state,
+        )?;
+        state.ancestors.pop();
+    }
+
+    match read_err {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// --estimate: what copying a source would amount to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    /// Entries other than directories: regular files, symlinks, special files
+    pub files: u64,
+    /// Total size of the regular files
+    pub bytes: u64,
+}
+
+impl Estimate {
+    fn add(&mut self, mode: nix::libc::mode_t, size: u64) {
+        match mode & nix::libc::S_IFMT {
+            nix::libc::S_IFDIR | nix::libc::S_IFSOCK => {}
+            nix::libc::S_IFREG => {
+                self.files += 1;
+                self.bytes += size;
+            }
+            _ => self.files += 1,
+        }
+    }
+}
+
+/// --estimate: count what copying `src` would copy, walking it with
+/// readdir and fstatat as the fast path does, without opening a file or
+/// looking at the destination. -R, -x, --max-depth and the dereference
+/// options apply; sockets, which are never copied, don't count.
+pub fn estimate(src: &Path, opts: &CopyOptions) -> CpResult<Estimate> {
+    let meta = util::get_metadata(src, opts.dereference != Dereference::Never).map_err(|e| {
+        CpError::Stat {
+            path: src.to_path_buf(),
+            source: e,
+        }
+    })?;
+    let mut est = Estimate::default();
+    if !meta.is_dir() {
+        est.add(meta.mode() as nix::libc::mode_t, meta.len());
+        return Ok(est);
+    }
+    // Without -R a directory is omitted; --max-depth=0 copies it empty
+    if !opts.recursive || opts.max_depth == Some(0) {
+        return Ok(est);
+    }
+
+    let fd = fs::File::open(src).map_err(|e| CpError::OpenRead {
+        path: src.to_path_buf(),
+        source: e,
+    })?;
+    let mut ancestors = vec![(meta.dev(), meta.ino())];
+    estimate_dir(
+        fd.into(),
+        src,
+        1,
+        meta.dev(),
+        &mut ancestors,
+        opts,
+        &mut est,
+    )?;
+    Ok(est)
+}
+
+/// Add up the entries of the directory open as `dir`, at `depth` below the
+/// source root, then those of its subdirectories.
+fn estimate_dir(
+    dir: OwnedFd,
+    path: &Path,
+    depth: usize,
+    root_dev: u64,
+    ancestors: &mut Vec<(u64, u64)>,
+    opts: &CopyOptions,
+    est: &mut Estimate,
+) -> CpResult<()> {
+    let dir_fd = dir.into_raw_fd();
+    let mut stream = DirStream::open(dir_fd, path)?;
+    let stat_flags = if opts.dereference == Dereference::Always {
+        0
+    } else {
+        nix::libc::AT_SYMLINK_NOFOLLOW
+    };
+
+    let read_dir_err = |e| CpError::ReadDir {
+        path: path.to_path_buf(),
+        source: e,
+    };
+    while let Some((d_name, _, _)) = stream.next_entry().map_err(read_dir_err)? {
+        let name_bytes = d_name.to_bytes();
+        let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { nix::libc::fstatat(dir_fd, d_name.as_ptr(), &mut stat, stat_flags) } != 0 {
+            continue;
+        }
+        // -x: another filesystem is neither entered nor counted
+        if opts.one_file_system && stat.st_dev as u64 != root_dev {
+            continue;
+        }
+        if stat.st_mode & nix::libc::S_IFMT != nix::libc::S_IFDIR {
+            est.add(stat.st_mode, stat.st_size as u64);
+            continue;
+        }
+
+        let key = stat_key(&stat);
+        if opts.max_depth.is_some_and(|max| depth >= max) || ancestors.contains(&key) {
+            continue;
+        }
+        let child_fd = unsafe {
+            nix::libc::openat(
+                dir_fd,
+                d_name.as_ptr(),
+                nix::libc::O_RDONLY | nix::libc::O_DIRECTORY | nix::libc::O_CLOEXEC,
+            )
+        };
+        let child_path = path.join(bytes_to_os(name_bytes));
+        if child_fd < 0 {
+            return Err(CpError::OpenRead {
+                path: child_path,
+                source: std::io::Error::last_os_error(),
+            });
         }
+        ancestors.push(key);
+        let child = unsafe { OwnedFd::from_raw_fd(child_fd) };
+        estimate_dir(
+            child,
+            &child_path,
+            depth + 1,
+            root_dev,
+            ancestors,
+            opts,
+            est,
+        )?;
+        ancestors.pop();
+    }
+    Ok(())
+}
+
+/// `create_dir_all`, appending each directory that did not exist to
+/// `created` (outermost first) when --dir-mode needs to know about them.
+/// With -Z/--context, each new directory is labeled like a copied file.
+fn create_dirs(path: &Path, created: &mut Vec<PathBuf>, opts: &CopyOptions) -> CpResult<()> {
+    if path.exists() {
+        return Ok(());
+    }
+    let label = cfg!(feature = "selinux") && opts.selinux_context.is_some();
+    let mut new: Vec<PathBuf> = Vec::new();
+    if opts.dir_mode.is_some() || label {
+        new.extend(
+            path.ancestors()
+                .take_while(|d| !d.exists())
+                .map(Path::to_path_buf),
+        );
+        new.reverse();
+    }
+    fs::create_dir_all(path).map_err(|e| CpError::CreateDir {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    #[cfg(feature = "selinux")]
+    for dir in &new {
+        crate::selinux::set_dest_context(dir, opts)?;
     }
+    if opts.dir_mode.is_some() {
+        created.extend(new);
+    }
+    Ok(())
+}
 
+/// Give the directories the copy created their --dir-mode, deepest first:
+/// applied once their content is in place, so even a mode without owner
+/// write or search permission doesn't get in the way.
+/// `created` is relative to directory fd `base`.
+fn apply_dir_mode(base: RawFd, created: &[PathBuf], opts: &CopyOptions) -> CpResult<()> {
+    let Some(mode) = opts.dir_mode else {
+        return Ok(());
+    };
+    for dir in created.iter().rev() {
+        let result = c_path(dir).and_then(|c| {
+            if unsafe { nix::libc::fchmodat(base, c.as_ptr(), mode as nix::libc::mode_t, 0) } == 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        });
+        result.map_err(|e| CpError::Chmod {
+            path: dir.clone(),
+            source: e,
+        })?;
+    }
     Ok(())
 }
 
+/// Downgrade a per-file error to a warning when its source matches
+/// --ignore-errors-from; any other error is passed back to abort the copy.
+fn tolerate(err: CpError, src: &Path, opts: &CopyOptions) -> CpResult<()> {
+    if opts.ignores_errors_for(src) {
+        util::warn(opts, format_args!("{} (ignored)", err));
+        Ok(())
+    } else {
+        Err(err)
+    }
+}
+
+/// Count one more file against --max-files, whose count the whole run shares.
+fn claim_file(dir: &Path, opts: &CopyOptions) -> CpResult<()> {
+    match &opts.max_files {
+        Some(limit) => limit.claim(dir),
+        None => Ok(()),
+    }
+}
+
 /// Copy a regular file using openat (relative to directory fd).
 /// No PathBuf allocation in the common case (paths only built on error).
 fn copy_file_openat(
@@ -394,6 +1182,19 @@ fn copy_file_openat(
     dst_dir_path: &Path,
     state: &mut RawCopyState,
 ) -> CpResult<()> {
+    if state.opts.hard_link
+        && link_file_at(
+            src_dir_fd,
+            dst_dir_fd,
+            name,
+            src_dir_path,
+            dst_dir_path,
+            state.opts,
+        )?
+    {
+        return Ok(());
+    }
+
     // openat: relative to directory fd — no path resolution
     let src_fd = unsafe {
         nix::libc::openat(
@@ -427,38 +1228,136 @@ fn copy_file_openat(
     };
 
     // Hard link detection using the fstat we already did
+    let mut first_key = None;
     if let Some(hlmap) = state.hard_link_map.as_mut()
         && let Some(ref s) = stat
         && s.st_nlink > 1
     {
-        let key = (s.st_dev, s.st_ino);
+        let key = stat_key(s);
         let name_os = bytes_to_os(name.to_bytes());
         let dst_file_path = dst_dir_path.join(name_os);
-        if let Some(first_dest) = hlmap.get(&key) {
+        if let Some((first_dest, names)) = hlmap.get_mut(&key) {
+            *names += 1;
             unsafe { nix::libc::close(src_fd) };
             // unlinkat + linkat relative to dir fd
             unsafe {
                 nix::libc::unlinkat(dst_dir_fd, name.as_ptr(), 0);
             }
-            fs::hard_link(first_dest, &dst_file_path).map_err(|e| CpError::HardLink {
+            link_at(state.bases.1, first_dest, &dst_file_path).map_err(|e| CpError::HardLink {
                 src: first_dest.clone(),
                 dst: dst_file_path,
                 source: e,
             })?;
             return Ok(());
         }
-        hlmap.insert(key, dst_file_path);
+        hlmap.insert(key, (dst_file_path, 1));
+        first_key = Some(key);
+    }
+
+    let result = create_and_copy(
+        src_fd,
+        dst_dir_fd,
+        name,
+        src_dir_path,
+        dst_dir_path,
+        stat.as_ref(),
+        state,
+    );
+
+    // A failed first occurrence must not be linked to: forget it so the next
+    // link of the group is copied in full and takes its place
+    if result.is_err()
+        && let Some(key) = first_key
+        && let Some(hlmap) = state.hard_link_map.as_mut()
+    {
+        hlmap.remove(&key);
+    }
+    result
+}
+
+/// -l: hard-link `name` into the destination directory instead of copying
+/// it, replacing a file already there (as copy::do_hard_link does).
+/// Returns false when the two trees are on different filesystems: the caller
+/// then copies the data instead.
+fn link_file_at(
+    src_dir_fd: RawFd,
+    dst_dir_fd: RawFd,
+    name: &CStr,
+    src_dir_path: &Path,
+    dst_dir_path: &Path,
+    opts: &CopyOptions,
+) -> CpResult<bool> {
+    let link =
+        || unsafe { nix::libc::linkat(src_dir_fd, name.as_ptr(), dst_dir_fd, name.as_ptr(), 0) };
+    if link() == 0 {
+        return Ok(true);
+    }
+    let mut err = std::io::Error::last_os_error();
+    if err.raw_os_error() == Some(nix::libc::EEXIST) {
+        unsafe { nix::libc::unlinkat(dst_dir_fd, name.as_ptr(), 0) };
+        if link() == 0 {
+            return Ok(true);
+        }
+        err = std::io::Error::last_os_error();
+    }
+    let name_os = bytes_to_os(name.to_bytes());
+    if err.raw_os_error() == Some(nix::libc::EXDEV) {
+        util::warn(
+            opts,
+            format_args!(
+                "cannot hard-link '{}' across filesystems, copying instead",
+                dst_dir_path.join(name_os).display()
+            ),
+        );
+        return Ok(false);
+    }
+    Err(CpError::HardLink {
+        src: src_dir_path.join(name_os),
+        dst: dst_dir_path.join(name_os),
+        source: err,
+    })
+}
+
+/// Create the destination relative to its directory fd (unlinking and
+/// retrying with --force) and copy `src_fd` into it. Consumes `src_fd`.
+fn create_and_copy(
+    src_fd: RawFd,
+    dst_dir_fd: RawFd,
+    name: &CStr,
+    src_dir_path: &Path,
+    dst_dir_path: &Path,
+    stat: Option<&nix::libc::stat>,
+    state: &RawCopyState,
+) -> CpResult<()> {
+    if state.opts.size_only && same_size_at(src_fd, stat, dst_dir_fd, name) {
+        unsafe { nix::libc::close(src_fd) };
+        return Ok(());
     }
 
-    // Create destination: openat relative to dir fd
-    let dst_fd = unsafe {
+    // Create destination: openat relative to dir fd. O_EXCL first, so a
+    // failed copy knows it may unlink what it created; an existing file is
+    // truncated and left in place
+    let create_flags = nix::libc::O_WRONLY | nix::libc::O_CREAT | nix::libc::O_CLOEXEC;
+    let mut created = true;
+    let mut dst_fd = unsafe {
         nix::libc::openat(
             dst_dir_fd,
             name.as_ptr(),
-            nix::libc::O_WRONLY | nix::libc::O_CREAT | nix::libc::O_TRUNC | nix::libc::O_CLOEXEC,
+            create_flags | nix::libc::O_EXCL,
             0o666,
         )
     };
+    if dst_fd < 0 && std::io::Error::last_os_error().raw_os_error() == Some(nix::libc::EEXIST) {
+        created = false;
+        dst_fd = unsafe {
+            nix::libc::openat(
+                dst_dir_fd,
+                name.as_ptr(),
+                create_flags | nix::libc::O_TRUNC,
+                0o666,
+            )
+        };
+    }
     if dst_fd < 0 {
         let err = std::io::Error::last_os_error();
         if state.opts.force {
@@ -484,8 +1383,17 @@ fn copy_file_openat(
                 });
             }
             // Continue with dst_fd2
-            copy_and_close(src_fd, dst_fd2, stat.as_ref(), state)?;
-            return Ok(());
+            return copy_and_close(
+                src_fd,
+                dst_fd2,
+                || {
+                    let name = bytes_to_os(name.to_bytes());
+                    (src_dir_path.join(name), dst_dir_path.join(name))
+                },
+                stat,
+                state,
+                false,
+            );
         }
         unsafe { nix::libc::close(src_fd) };
         let name_os = bytes_to_os(name.to_bytes());
@@ -495,12 +1403,54 @@ fn copy_file_openat(
         });
     }
 
-    copy_and_close(src_fd, dst_fd, stat.as_ref(), state)
+    copy_and_close(
+        src_fd,
+        dst_fd,
+        || {
+            let name = bytes_to_os(name.to_bytes());
+            (src_dir_path.join(name), dst_dir_path.join(name))
+        },
+        stat,
+        state,
+        created,
+    )
+}
+
+/// --size-only: whether `name` in `dst_dir_fd` is a regular file with the
+/// same size as the open source.
+fn same_size_at(
+    src_fd: RawFd,
+    stat: Option<&nix::libc::stat>,
+    dst_dir_fd: RawFd,
+    name: &CStr,
+) -> bool {
+    let src_size = match stat {
+        Some(s) => s.st_size,
+        None => {
+            let mut st: nix::libc::stat = unsafe { std::mem::zeroed() };
+            if unsafe { nix::libc::fstat(src_fd, &mut st) } != 0 {
+                return false;
+            }
+            st.st_size
+        }
+    };
+
+    let mut dst: nix::libc::stat = unsafe { std::mem::zeroed() };
+    let found = unsafe {
+        nix::libc::fstatat(
+            dst_dir_fd,
+            name.as_ptr(),
+            &mut dst,
+            nix::libc::AT_SYMLINK_NOFOLLOW,
+        )
+    } == 0;
+    found && dst.st_mode & nix::libc::S_IFMT == nix::libc::S_IFREG && dst.st_size == src_size
 }
 
 /// Copy regular files in parallel using scoped threads.
-/// Temporarily takes `hard_link_map` out of `state` for thread-safe Mutex wrapping,
-/// then puts it back after all threads join.
+/// Temporarily moves `hard_link_map` out of `state` into a sharded `DashMap`
+/// so threads only contend on the shard holding an inode, then puts it back
+/// after all threads join.
 fn copy_files_parallel(
     files: &[CString],
     src_fd: RawFd,
@@ -511,17 +1461,26 @@ fn copy_files_parallel(
 ) -> CpResult<()> {
     use std::sync::Mutex;
 
-    let n_threads = std::thread::available_parallelism()
-        .map(|n| n.get().min(8))
-        .unwrap_or(4);
+    let n_threads = state.opts.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get().min(8))
+            .unwrap_or(4)
+    });
     let chunk_size = files.len().div_ceil(n_threads);
 
-    // Take hard_link_map out so the rest of state is immutable + Sync
-    let hlmap = state.hard_link_map.take().map(Mutex::new);
+    // Take hard_link_map out so the rest of state is immutable + Sync.
+    // Four shards per thread keeps collisions rare (shard count must be a
+    // power of two greater than one)
+    let shards = (n_threads * 4).next_power_of_two().max(2);
+    let hlmap = state.hard_link_map.take().map(|m| {
+        let mut dm = DashMap::with_capacity_and_shard_amount(m.len(), shards);
+        dm.extend(m);
+        dm
+    });
     let state_ref: &RawCopyState = &*state;
     let first_err: Mutex<Option<CpError>> = Mutex::new(None);
     // Deferred hard links: created after all files are copied to avoid races
-    let deferred_links: Mutex<Vec<(PathBuf, PathBuf)>> = Mutex::new(Vec::new());
+    let deferred_links: Mutex<Vec<DeferredLink>> = Mutex::new(Vec::new());
 
     let hlmap_ref = hlmap.as_ref();
     let err_ref = &first_err;
@@ -535,6 +1494,13 @@ fn copy_files_parallel(
                     if err_ref.lock().map_or(true, |g| g.is_some()) {
                         return;
                     }
+                    if let Err(e) = state_ref.claim_file(src_path) {
+                        let mut g = err_ref.lock().unwrap();
+                        if g.is_none() {
+                            *g = Some(e);
+                        }
+                        return;
+                    }
                     if let Err(e) = copy_file_openat_mt(
                         src_fd,
                         dst_fd,
@@ -545,11 +1511,14 @@ fn copy_files_parallel(
                         hlmap_ref,
                         deferred_ref,
                     ) {
-                        let mut g = err_ref.lock().unwrap();
-                        if g.is_none() {
-                            *g = Some(e);
+                        let src = src_path.join(bytes_to_os(name.as_bytes()));
+                        if let Err(e) = state_ref.tolerate(e, &src) {
+                            let mut g = err_ref.lock().unwrap();
+                            if g.is_none() {
+                                *g = Some(e);
+                            }
+                            return;
                         }
-                        return;
                     }
                     progress_ref.inc();
                 }
@@ -558,27 +1527,45 @@ fn copy_files_parallel(
     });
 
     // Restore hard_link_map
-    state.hard_link_map = hlmap.map(|m| m.into_inner().unwrap());
+    state.hard_link_map = hlmap.map(|m| m.into_iter().collect());
 
     if let Some(e) = first_err.into_inner().unwrap() {
         return Err(e);
     }
 
-    // Phase 2: Create deferred hard links now that all originals exist
-    for (src, dst) in deferred_links.into_inner().unwrap() {
-        // Remove any placeholder file created by parallel copy
-        let _ = fs::remove_file(&dst);
-        fs::hard_link(&src, &dst).map_err(|e| CpError::HardLink {
-            src: src.clone(),
-            dst: dst.clone(),
-            source: e,
-        })?;
+    // Phase 2: Create deferred hard links now that all originals exist. A
+    // group whose first occurrence failed has no registered destination any
+    // more: copy its next link in full, which then registers as the first.
+    for (key, dst, name) in deferred_links.into_inner().unwrap() {
+        let first = state
+            .hard_link_map
+            .as_mut()
+            .and_then(|m| m.get_mut(&key))
+            .map(|(first, names)| {
+                *names += 1;
+                first.clone()
+            });
+        let result = match first {
+            Some(first) => {
+                // Remove any placeholder file created by parallel copy
+                unlink_at(state.bases.1, &dst);
+                link_at(state.bases.1, &first, &dst).map_err(|e| CpError::HardLink {
+                    src: first.clone(),
+                    dst: dst.clone(),
+                    source: e,
+                })
+            }
+            None => copy_file_openat(src_fd, dst_fd, &name, src_path, dst_path, state),
+        };
+        if let Err(e) = result {
+            state.tolerate(e, &src_path.join(bytes_to_os(name.as_bytes())))?;
+        }
     }
 
     Ok(())
 }
 
-/// Thread-safe file copy via openat. Like `copy_file_openat` but uses Mutex for hard link map.
+/// Thread-safe file copy via openat. Like `copy_file_openat` but uses a `DashMap` for hard link map.
 /// Hard links are deferred: the first occurrence of an inode is copied normally and registered
 /// in the map; subsequent occurrences push to `deferred_links` for creation after all copies finish.
 #[allow(clippy::too_many_arguments)]
@@ -589,9 +1576,22 @@ fn copy_file_openat_mt(
     src_dir_path: &Path,
     dst_dir_path: &Path,
     state: &RawCopyState,
-    hlmap: Option<&std::sync::Mutex<HashMap<(u64, u64), PathBuf>>>,
-    deferred_links: &std::sync::Mutex<Vec<(PathBuf, PathBuf)>>,
+    hlmap: Option<&SharedHardLinkMap>,
+    deferred_links: &std::sync::Mutex<Vec<DeferredLink>>,
 ) -> CpResult<()> {
+    if state.opts.hard_link
+        && link_file_at(
+            src_dir_fd,
+            dst_dir_fd,
+            name,
+            src_dir_path,
+            dst_dir_path,
+            state.opts,
+        )?
+    {
+        return Ok(());
+    }
+
     let src_fd = unsafe {
         nix::libc::openat(
             src_dir_fd,
@@ -620,102 +1620,127 @@ fn copy_file_openat_mt(
         None
     };
 
-    // Hard link detection with Mutex — defer link creation to avoid race conditions
+    // Hard link detection — defer link creation to avoid race conditions
     if let Some(hlm) = hlmap
         && let Some(ref s) = stat
         && s.st_nlink > 1
     {
-        let key = (s.st_dev, s.st_ino);
+        let key = stat_key(s);
         let name_os = bytes_to_os(name.to_bytes());
         let dst_file = dst_dir_path.join(name_os);
-        let mut guard = hlm.lock().unwrap();
-        if let Some(first) = guard.get(&key) {
-            // Another thread already claimed this inode — defer the hard link
-            let first = first.clone();
-            drop(guard);
-            unsafe { nix::libc::close(src_fd) };
-            deferred_links.lock().unwrap().push((first, dst_file));
-            return Ok(());
-        }
-        // First occurrence: register in map, then copy the file below
-        guard.insert(key, dst_file);
-        drop(guard);
-    }
-
-    let dst_fd = unsafe {
-        nix::libc::openat(
-            dst_dir_fd,
-            name.as_ptr(),
-            nix::libc::O_WRONLY | nix::libc::O_CREAT | nix::libc::O_TRUNC | nix::libc::O_CLOEXEC,
-            0o666,
-        )
-    };
-    if dst_fd < 0 {
-        let err = std::io::Error::last_os_error();
-        if state.opts.force {
-            unsafe { nix::libc::unlinkat(dst_dir_fd, name.as_ptr(), 0) };
-            let dst_fd2 = unsafe {
-                nix::libc::openat(
-                    dst_dir_fd,
-                    name.as_ptr(),
-                    nix::libc::O_WRONLY
-                        | nix::libc::O_CREAT
-                        | nix::libc::O_TRUNC
-                        | nix::libc::O_CLOEXEC,
-                    0o666,
-                )
-            };
-            if dst_fd2 < 0 {
+        match hlm.entry(key) {
+            Entry::Occupied(claimed) => {
+                // Another thread already claimed this inode — defer the hard link
+                drop(claimed);
                 unsafe { nix::libc::close(src_fd) };
-                return Err(CpError::CreateFile {
-                    path: dst_dir_path.join(bytes_to_os(name.to_bytes())),
-                    source: std::io::Error::last_os_error(),
-                });
+                deferred_links
+                    .lock()
+                    .unwrap()
+                    .push((key, dst_file, name.to_owned()));
+                return Ok(());
+            }
+            // First occurrence: register in map, then copy the file below
+            Entry::Vacant(slot) => {
+                slot.insert((dst_file, 1));
             }
-            return copy_and_close(src_fd, dst_fd2, stat.as_ref(), state);
         }
-        unsafe { nix::libc::close(src_fd) };
-        return Err(CpError::CreateFile {
-            path: dst_dir_path.join(bytes_to_os(name.to_bytes())),
-            source: err,
+    }
+
+    let result = create_and_copy(
+        src_fd,
+        dst_dir_fd,
+        name,
+        src_dir_path,
+        dst_dir_path,
+        stat.as_ref(),
+        state,
+    );
+
+    // Unregister a failed first occurrence (unless another thread already
+    // replaced it) so deferred links to it fall back to a full copy
+    if result.is_err()
+        && let Some(hlm) = hlmap
+        && let Some(ref s) = stat
+    {
+        let key = stat_key(s);
+        let dst_file = dst_dir_path.join(bytes_to_os(name.to_bytes()));
+        hlm.remove_if(&key, |_, (first, _)| *first == dst_file);
+    }
+    result
+}
+
+/// Loop copy_file_range until EOF. Returns whether any data was copied,
+/// and whether the kernel refused the pair (the rest is then read/written).
+#[cfg(not(target_os = "macos"))]
+fn copy_fds_in_kernel(src_fd: RawFd, dst_fd: RawFd, chunk: usize) -> (bool, bool) {
+    let mut copied = false;
+    loop {
+        let ret = util::retry_eintr(|| unsafe {
+            nix::libc::copy_file_range(
+                src_fd,
+                std::ptr::null_mut(),
+                dst_fd,
+                std::ptr::null_mut(),
+                chunk,
+                0,
+            )
         });
+        if ret < 0 {
+            return (copied, true);
+        }
+        if ret == 0 {
+            return (copied, false);
+        }
+        copied = true;
     }
+}
 
-    copy_and_close(src_fd, dst_fd, stat.as_ref(), state)
+/// macOS has no copy_file_range: fcopyfile(3) copies the data in one call.
+#[cfg(target_os = "macos")]
+fn copy_fds_in_kernel(src_fd: RawFd, dst_fd: RawFd, _chunk: usize) -> (bool, bool) {
+    let ret = unsafe {
+        nix::libc::fcopyfile(
+            src_fd,
+            dst_fd,
+            std::ptr::null_mut(),
+            nix::libc::COPYFILE_DATA,
+        )
+    };
+    (ret == 0, ret != 0)
 }
 
 /// Copy file data + metadata using raw fds, then close both.
 #[inline]
+/// `paths` gives (src, dst) for error messages, only built when needed.
+/// `created`: the copy made the destination, so a failed data copy removes it.
 fn copy_and_close(
     src_fd: RawFd,
     dst_fd: RawFd,
+    paths: impl Fn() -> (PathBuf, PathBuf),
     stat: Option<&nix::libc::stat>,
     state: &RawCopyState,
+    created: bool,
 ) -> CpResult<()> {
-    // Copy data: loop copy_file_range until EOF
-    loop {
-        let ret = unsafe {
-            nix::libc::copy_file_range(
-                src_fd,
-                std::ptr::null_mut(),
-                dst_fd,
-                std::ptr::null_mut(),
-                CFR_MAX,
-                0,
-            )
-        };
-        if ret <= 0 {
-            break;
+    let (copied, refused) =
+        copy_fds_in_kernel(src_fd, dst_fd, state.opts.chunk_size.unwrap_or(CFR_MAX));
+
+    // copy_file_range refuses some pairs (EXDEV across filesystems on recent
+    // kernels, procfs), and a pseudo file reporting size 0 may still have
+    // content: read/write what's left, until EOF
+    let mut result = Ok(());
+    if refused || (!copied && stat.is_none_or(|s| s.st_size == 0)) {
+        result = read_write_fds(src_fd, dst_fd, &paths);
+        // Don't leave a partial copy behind, unless it replaced a file
+        if result.is_err() && created {
+            unlink_at(state.bases.1, &paths().1);
         }
     }
 
     // Preserve metadata using fd-based syscalls
-    if state.need_file_meta
+    if result.is_ok()
+        && state.need_file_meta
         && let Some(s) = stat
     {
-        if state.opts.preserve_xattr {
-            preserve_xattr_fd(src_fd, dst_fd);
-        }
         if state.opts.preserve_ownership {
             unsafe {
                 nix::libc::fchown(dst_fd, s.st_uid, s.st_gid);
@@ -726,6 +1751,18 @@ fn copy_and_close(
                 nix::libc::fchmod(dst_fd, s.st_mode);
             }
         }
+        if state.opts.preserve_xattr
+            && metadata::xattr_supported(false)
+            && let Err(e) = copy_xattrs_fd(src_fd, dst_fd, &paths().1, state.opts)
+        {
+            result = Err(CpError::Xattr {
+                path: paths().1,
+                source: e,
+            });
+        }
+        if state.opts.preserve_acl {
+            preserve_acl_fd(src_fd, dst_fd);
+        }
         if state.opts.preserve_timestamps {
             let atime = nix::libc::timespec {
                 tv_sec: s.st_atime,
@@ -735,13 +1772,13 @@ fn copy_and_close(
                 tv_sec: s.st_mtime,
                 tv_nsec: s.st_mtime_nsec,
             };
-            let times = [atime, mtime];
-            unsafe {
-                nix::libc::futimens(dst_fd, times.as_ptr());
-            }
+            let _ = metadata::set_times_fd(dst_fd, atime, mtime);
         }
-        if state.opts.preserve_acl {
-            preserve_acl_fd(src_fd, dst_fd);
+        // Last: immutable or append-only would refuse the steps above
+        if state.opts.preserve_flags
+            && let Err(e) = metadata::preserve_flags_fd(src_fd, dst_fd)
+        {
+            metadata::warn_flags(&paths().1, &e, state.opts);
         }
     }
 
@@ -750,31 +1787,56 @@ fn copy_and_close(
         nix::libc::close(dst_fd);
     }
 
-    Ok(())
+    result
+}
+
+/// Read/write copy of the rest of `src_fd` into `dst_fd`, until EOF.
+fn read_write_fds(
+    src_fd: RawFd,
+    dst_fd: RawFd,
+    paths: &impl Fn() -> (PathBuf, PathBuf),
+) -> CpResult<()> {
+    // Borrowed: copy_and_close owns and closes the fds
+    let mut src = std::mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(src_fd) });
+    let mut dst = std::mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(dst_fd) });
+    let mut buf = vec![0u8; RW_BUF_SIZE];
+    loop {
+        let n = util::read_retry(&mut *src, &mut buf).map_err(|e| CpError::Read {
+            path: paths().0,
+            source: e,
+        })?;
+        if n == 0 {
+            return Ok(());
+        }
+        dst.write_all(&buf[..n])
+            .map_err(|e| engine::write_error(&paths().1, e))?;
+    }
 }
 
-/// Copy a symlink using readlinkat + symlinkat.
+/// Copy a symlink using readlinkat + symlinkat: `src_name` in `src_dir_fd`
+/// to `dst_name` in `dst_dir_fd`. `src`/`dst` name them for messages.
 fn copy_symlink_at(
     src_dir_fd: RawFd,
     dst_dir_fd: RawFd,
-    name: &CStr,
-    src_dir_path: &Path,
-    dst_dir_path: &Path,
-    opts: &CopyOptions,
+    src_name: &CStr,
+    dst_name: &CStr,
+    src: &Path,
+    dst: &Path,
+    state: &RawCopyState,
 ) -> CpResult<()> {
+    let opts = state.opts;
     let mut buf = [0u8; 4096];
     let len = unsafe {
         nix::libc::readlinkat(
             src_dir_fd,
-            name.as_ptr(),
+            src_name.as_ptr(),
             buf.as_mut_ptr() as *mut nix::libc::c_char,
             buf.len(),
         )
     };
     if len < 0 {
-        let name_os = bytes_to_os(name.to_bytes());
         return Err(CpError::ReadLink {
-            path: src_dir_path.join(name_os),
+            path: src.to_path_buf(),
             source: std::io::Error::last_os_error(),
         });
     }
@@ -787,7 +1849,7 @@ fn copy_symlink_at(
 
     // Remove existing symlink if present
     unsafe {
-        nix::libc::unlinkat(dst_dir_fd, name.as_ptr(), 0);
+        nix::libc::unlinkat(dst_dir_fd, dst_name.as_ptr(), 0);
     }
 
     // symlinkat
@@ -795,55 +1857,135 @@ fn copy_symlink_at(
         nix::libc::symlinkat(
             target_z.as_ptr() as *const nix::libc::c_char,
             dst_dir_fd,
-            name.as_ptr(),
+            dst_name.as_ptr(),
         )
     };
     if ret != 0 {
-        let name_os = bytes_to_os(name.to_bytes());
         return Err(CpError::Symlink {
-            dst: dst_dir_path.join(name_os),
+            dst: dst.to_path_buf(),
             source: std::io::Error::last_os_error(),
         });
     }
 
-    // Preserve symlink metadata if needed
-    if opts.preserve_timestamps || opts.preserve_ownership {
-        let name_os = bytes_to_os(name.to_bytes());
-        let src_path = src_dir_path.join(name_os);
-        let dst_path = dst_dir_path.join(name_os);
-        if let Ok(meta) = fs::symlink_metadata(&src_path) {
-            metadata::preserve_metadata(&src_path, &dst_path, &meta, opts, true)?;
+    // Preserve symlink metadata if needed, relative to the directory fds
+    if !(opts.preserve_timestamps || opts.preserve_ownership || opts.preserve_xattr) {
+        return Ok(());
+    }
+    let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe {
+        nix::libc::fstatat(
+            src_dir_fd,
+            src_name.as_ptr(),
+            &mut stat,
+            nix::libc::AT_SYMLINK_NOFOLLOW,
+        )
+    } != 0
+    {
+        return Ok(());
+    }
+    if opts.preserve_ownership
+        && unsafe {
+            nix::libc::fchownat(
+                dst_dir_fd,
+                dst_name.as_ptr(),
+                stat.st_uid,
+                stat.st_gid,
+                nix::libc::AT_SYMLINK_NOFOLLOW,
+            )
+        } != 0
+    {
+        // Without the privilege to, the link keeps the caller's ids
+        let err = nix::Error::last();
+        if err != nix::Error::EPERM {
+            return Err(CpError::Chown {
+                path: dst.to_path_buf(),
+                source: err,
+            });
         }
     }
-
-    if opts.verbose {
-        let name_os = bytes_to_os(name.to_bytes());
-        println!(
-            "'{}' -> '{}'",
-            src_dir_path.join(name_os).display(),
-            dst_dir_path.join(name_os).display()
-        );
+    // No *at call reaches the xattrs of a symlink: they go by path, which
+    // only names the link when the copy is relative to the working directory
+    if opts.preserve_xattr && state.bases == (nix::libc::AT_FDCWD, nix::libc::AT_FDCWD) {
+        metadata::preserve_xattr_pub(src, dst, true, opts)?;
+    }
+    if opts.preserve_timestamps {
+        let atime = nix::libc::timespec {
+            tv_sec: stat.st_atime,
+            tv_nsec: stat.st_atime_nsec,
+        };
+        let mtime = nix::libc::timespec {
+            tv_sec: stat.st_mtime,
+            tv_nsec: stat.st_mtime_nsec,
+        };
+        metadata::set_times_at(dst_dir_fd, dst_name, atime, mtime, true).map_err(|e| {
+            CpError::Timestamps {
+                path: dst.to_path_buf(),
+                source: e,
+            }
+        })?;
     }
 
     Ok(())
 }
 
-/// Apply deferred directory metadata from raw stat.
-fn apply_dir_metadata(dst: &Path, stat: &nix::libc::stat, opts: &CopyOptions) -> CpResult<()> {
+/// Apply deferred directory metadata from raw stat, through fds opened on
+/// `src`/`dst` relative to `bases`. They are opened before the mode is set:
+/// a mode without read permission would keep them from being opened after.
+fn apply_dir_metadata(
+    src: &Path,
+    dst: &Path,
+    stat: &nix::libc::stat,
+    bases: (RawFd, RawFd),
+    opts: &CopyOptions,
+) -> CpResult<()> {
+    let open = |base: RawFd, path: &Path| {
+        let c = c_path(path).map_err(|e| CpError::OpenRead {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        open_dir_at(base, &c, path).map(|fd| unsafe { OwnedFd::from_raw_fd(fd) })
+    };
+    let dst_fd = match open(bases.1, dst) {
+        Ok(fd) => fd,
+        Err(e) => {
+            util::warn(
+                opts,
+                format_args!("cannot preserve attributes of '{}': {}", dst.display(), e),
+            );
+            return Ok(());
+        }
+    };
+    let dst_fd = dst_fd.as_raw_fd();
+    let src_fd = if opts.preserve_xattr || opts.preserve_acl || opts.preserve_flags {
+        open(bases.0, src).ok()
+    } else {
+        None
+    };
+    let src_fd = src_fd.as_ref().map(|fd| fd.as_raw_fd());
+
     if opts.preserve_ownership {
-        let c_path = CString::new(dst.as_os_str().as_bytes()).ok();
-        if let Some(c) = c_path {
-            unsafe {
-                nix::libc::chown(c.as_ptr(), stat.st_uid, stat.st_gid);
-            }
+        unsafe {
+            nix::libc::fchown(dst_fd, stat.st_uid, stat.st_gid);
         }
     }
 
     if opts.preserve_mode {
-        use std::os::unix::fs::PermissionsExt;
-        fs::set_permissions(dst, fs::Permissions::from_mode(stat.st_mode)).ok();
+        unsafe {
+            nix::libc::fchmod(dst_fd, stat.st_mode & 0o7777);
+        }
+    }
+
+    if let Some(src_fd) = src_fd {
+        if opts.preserve_xattr && metadata::xattr_supported(false) {
+            let _ = copy_xattrs_fd(src_fd, dst_fd, dst, opts);
+        }
+        if opts.preserve_acl && metadata::acl_supported(false) {
+            preserve_acl_fd(src_fd, dst_fd);
+            preserve_default_acl_fd(src_fd, dst_fd);
+        }
     }
 
+    // Timestamps last: every step above may touch the directory's ctime/mtime
     if opts.preserve_timestamps {
         let atime = nix::libc::timespec {
             tv_sec: stat.st_atime,
@@ -853,18 +1995,45 @@ fn apply_dir_metadata(dst: &Path, stat: &nix::libc::stat, opts: &CopyOptions) ->
             tv_sec: stat.st_mtime,
             tv_nsec: stat.st_mtime_nsec,
         };
-        let c_path = CString::new(dst.as_os_str().as_bytes()).ok();
-        if let Some(c) = c_path {
-            let times = [atime, mtime];
-            unsafe {
-                nix::libc::utimensat(nix::libc::AT_FDCWD, c.as_ptr(), times.as_ptr(), 0);
-            }
-        }
+        let _ = metadata::set_times_fd(dst_fd, atime, mtime);
+    }
+    if opts.preserve_flags
+        && let Some(src_fd) = src_fd
+        && let Err(e) = metadata::preserve_flags_fd(src_fd, dst_fd)
+    {
+        metadata::warn_flags(dst, &e, opts);
     }
 
     Ok(())
 }
 
+/// Post-copy check that every source hard-link group ended up linked in the
+/// destination. Emits a warning for each group whose destination has fewer
+/// or more links than the names the copy met in the source tree; links to
+/// files outside the tree don't count. The destinations are relative to
+/// directory fd `base`.
+fn verify_hard_links(hlmap: &HardLinkMap, base: RawFd, opts: &CopyOptions) {
+    for ((dev, ino), (first_dst, src_names)) in hlmap {
+        let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
+        let found = c_path(first_dst).is_ok_and(|c| unsafe {
+            nix::libc::fstatat(base, c.as_ptr(), &mut stat, nix::libc::AT_SYMLINK_NOFOLLOW) == 0
+        });
+        if !found {
+            continue;
+        }
+        let dst_nlink = stat.st_nlink as usize;
+        if dst_nlink != *src_names {
+            util::warn(
+                opts,
+                format_args!(
+                    "hardlink group (dev {}, ino {}) has {} links in source but {} in destination",
+                    dev, ino, src_names, dst_nlink
+                ),
+            );
+        }
+    }
+}
+
 /// Open a directory fd for openat operations.
 fn open_dir_fd(path: &Path) -> CpResult<RawFd> {
     let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| CpError::OpenRead {
@@ -886,11 +2055,59 @@ fn open_dir_fd(path: &Path) -> CpResult<RawFd> {
     Ok(fd)
 }
 
+/// Open a directory relative to a directory fd.
+fn open_dir_at(dir_fd: RawFd, name: &CStr, path: &Path) -> CpResult<RawFd> {
+    let fd = unsafe {
+        nix::libc::openat(
+            dir_fd,
+            name.as_ptr(),
+            nix::libc::O_RDONLY | nix::libc::O_DIRECTORY | nix::libc::O_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(CpError::OpenRead {
+            path: path.to_path_buf(),
+            source: std::io::Error::last_os_error(),
+        });
+    }
+    Ok(fd)
+}
+
+/// `path` for the `*at` calls. Only a NUL byte can make this fail, and no
+/// name read from a directory holds one.
+fn c_path(path: &Path) -> std::io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| std::io::Error::from_raw_os_error(nix::libc::EINVAL))
+}
+
+/// Hard-link `first` to `dst`, both relative to directory fd `base`.
+fn link_at(base: RawFd, first: &Path, dst: &Path) -> std::io::Result<()> {
+    let (first, dst) = (c_path(first)?, c_path(dst)?);
+    if unsafe { nix::libc::linkat(base, first.as_ptr(), base, dst.as_ptr(), 0) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+/// Unlink `path` relative to directory fd `base`, if it is there.
+fn unlink_at(base: RawFd, path: &Path) {
+    if let Ok(c) = c_path(path) {
+        unsafe { nix::libc::unlinkat(base, c.as_ptr(), 0) };
+    }
+}
+
+/// The (dev, ino) pair identifying the file `stat` describes.
+#[allow(clippy::unnecessary_cast)] // dev_t is an i32 on macOS
+fn stat_key(stat: &nix::libc::stat) -> (u64, u64) {
+    (stat.st_dev as u64, stat.st_ino)
+}
+
 /// Get device number from an open fd.
 fn fstat_dev(fd: RawFd) -> u64 {
     let mut stat: nix::libc::stat = unsafe { std::mem::zeroed() };
     if unsafe { nix::libc::fstat(fd, &mut stat) } == 0 {
-        stat.st_dev
+        stat.st_dev as u64
     } else {
         0
     }
@@ -914,20 +2131,57 @@ fn copy_directory_walkdir(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResul
         None
     };
 
-    let need_dir_meta = opts.preserve_mode || opts.preserve_ownership || opts.preserve_timestamps;
+    let need_dir_meta = opts.preserve_mode
+        || opts.preserve_ownership
+        || opts.preserve_timestamps
+        || opts.preserve_xattr
+        || opts.preserve_acl
+        || opts.preserve_flags
+        || opts.preserve_context
+        || opts.chown.is_some();
     let mut dir_metadata: Vec<(PathBuf, PathBuf, fs::Metadata)> = Vec::new();
 
-    let dir_pb = progress::make_dir_progress(&src.display().to_string(), opts.progress);
+    let dir_pb = progress::make_dir_progress(
+        &src.display().to_string(),
+        opts.progress,
+        &opts.progress_target,
+    );
     let dir_progress = progress::DirProgressCounter::new(dir_pb);
 
     let mut pb: Option<ProgressBar> = None;
 
-    let walker = WalkDir::new(src).follow_links(follow_links).min_depth(0);
+    // --max-depth: WalkDir counts levels along the logical path, so levels
+    // reached through a followed symlink to a directory count too
+    let mut walk = WalkDir::new(src).follow_links(follow_links).min_depth(0);
+    if let Some(depth) = opts.max_depth {
+        walk = walk.max_depth(depth);
+    }
+    let mut walker = match opts.sort {
+        SortOrder::None => walk,
+        SortOrder::Name => walk.sort_by_file_name(),
+        SortOrder::Inode => walk.sort_by_key(|e| e.ino()),
+    }
+    .into_iter();
 
     let mut dest_path = PathBuf::with_capacity(dst.as_os_str().len() + 64);
     let mut last_parent: Option<PathBuf> = None;
+    // (dev, ino) of the destination root, skipped if met inside the source (-x)
+    let mut dst_root: Option<(u64, u64)> = None;
+
+    // Parallel mode: files are collected during the walk and copied afterwards;
+    // hard links to already-seen inodes are created once all copies finished.
+    let parallel = opts.can_parallelize();
+    let mut jobs: Vec<(PathBuf, PathBuf)> = Vec::new();
+    // (dev, ino), source path, destination path
+    let mut deferred_links: Vec<((u64, u64), PathBuf, PathBuf)> = Vec::new();
+
+    // Directories created by the copy, only tracked for --dir-mode
+    let mut created_dirs: Vec<PathBuf> = Vec::new();
+
+    // --max-files: the walk stops at the limit, what was collected is still copied
+    let mut limit_err: Option<CpError> = None;
 
-    for result in walker {
+    while let Some(result) = walker.next() {
         let entry = match result {
             Ok(e) => e,
             Err(e) => {
@@ -951,16 +2205,42 @@ fn copy_directory_walkdir(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResul
         if ft.is_dir() {
             if let Some(dev) = src_dev
                 && let Ok(m) = fs::metadata(path)
-                && m.dev() != dev
             {
-                continue;
+                if dst_root == Some((m.dev(), m.ino())) {
+                    walker.skip_current_dir();
+                    continue;
+                }
+                // A mount point: created empty, as GNU does, and not entered
+                if m.dev() != dev {
+                    walker.skip_current_dir();
+                    if !opts.no_empty_dirs {
+                        create_dirs(&dest_path, &mut created_dirs, opts)?;
+                        if opts.verbose_tree() {
+                            println!("'{}' -> '{}'", path.display(), dest_path.display());
+                        }
+                        if need_dir_meta {
+                            dir_metadata.push((path.to_path_buf(), dest_path.clone(), m));
+                        }
+                    }
+                    continue;
+                }
             }
 
-            if !dest_path.exists() {
-                fs::create_dir_all(&dest_path).map_err(|e| CpError::CreateDir {
-                    path: dest_path.clone(),
-                    source: e,
-                })?;
+            // --no-empty-dirs: directories are created on demand, when the
+            // first non-directory entry below them is copied
+            if !opts.no_empty_dirs {
+                create_dirs(&dest_path, &mut created_dirs, opts)?;
+                // The root's line was printed by the caller
+                if opts.verbose_tree() && entry.depth() > 0 {
+                    println!("'{}' -> '{}'", path.display(), dest_path.display());
+                }
+            }
+
+            if src_dev.is_some()
+                && entry.depth() == 0
+                && let Ok(m) = fs::metadata(&dest_path)
+            {
+                dst_root = Some((m.dev(), m.ino()));
             }
 
             if need_dir_meta {
@@ -990,75 +2270,237 @@ fn copy_directory_walkdir(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResul
             }
         }
 
+        if let Err(e) = claim_file(path.parent().unwrap_or(src), opts) {
+            limit_err = Some(e);
+            break;
+        }
+
         if let Some(parent) = dest_path.parent() {
             let need_check = match last_parent {
                 Some(ref lp) => lp != parent,
                 None => true,
             };
             if need_check {
-                if !parent.exists() {
-                    fs::create_dir_all(parent).map_err(|e| CpError::CreateDir {
-                        path: parent.to_path_buf(),
-                        source: e,
-                    })?;
-                }
+                create_dirs(parent, &mut created_dirs, opts)?;
                 last_parent = Some(parent.to_path_buf());
             }
         }
 
         // Handle hard links in slow path
+        let mut first_key = None;
         if let Some(ref mut hlmap) = hard_link_map
             && !ft.is_symlink()
             && let Ok(meta) = fs::symlink_metadata(path)
             && meta.nlink() > 1
         {
             let key = (meta.dev(), meta.ino());
-            if let Some(first_dest) = hlmap.get(&key) {
-                if dest_path.exists() {
-                    let _ = fs::remove_file(&dest_path);
+            if hlmap.contains_key(&key) {
+                if parallel {
+                    deferred_links.push((key, path.to_path_buf(), dest_path.clone()));
+                } else if let Err(e) = link_or_copy(hlmap, key, path, &dest_path, opts) {
+                    tolerate(e, path, opts)?;
                 }
-                fs::hard_link(first_dest, &dest_path).map_err(|e| CpError::HardLink {
-                    src: first_dest.clone(),
-                    dst: dest_path.clone(),
-                    source: e,
-                })?;
                 continue;
             }
             hlmap.insert(key, dest_path.clone());
+            first_key = Some(key);
+        }
+
+        if parallel {
+            jobs.push((path.to_path_buf(), dest_path.clone()));
+            continue;
         }
 
         let slow_pb = pb.get_or_insert_with(ProgressBar::hidden);
-        copy::copy_single(path, &dest_path, opts, false, slow_pb)?;
+        if let Err(e) = copy::copy_single(path, &dest_path, opts, false, slow_pb) {
+            // A failed first occurrence must not be linked to: forget it so
+            // the next link of the group is copied in full and takes its place
+            if let (Some(key), Some(hlmap)) = (first_key, hard_link_map.as_mut()) {
+                hlmap.remove(&key);
+            }
+            tolerate(e, path, opts)?;
+        }
         dir_progress.inc();
     }
 
+    let failed = copy_entries_parallel(&jobs, opts, &dir_progress)?;
+
+    // Deferred hard links, now that all first occurrences exist. Those that
+    // failed may have left a partial file: their groups are copied in full.
+    if let Some(ref mut hlmap) = hard_link_map {
+        hlmap.retain(|_, first| !failed.contains(first));
+        for (key, src_path, link) in deferred_links {
+            if let Err(e) = link_or_copy(hlmap, key, &src_path, &link, opts) {
+                tolerate(e, &src_path, opts)?;
+            }
+        }
+    }
+
     dir_progress.finish();
 
     for (src_path, dst_path, meta) in dir_metadata.iter().rev() {
+        if opts.no_empty_dirs && !dst_path.exists() {
+            continue;
+        }
         metadata::preserve_metadata(src_path, dst_path, meta, opts, false)?;
     }
+    apply_dir_mode(nix::libc::AT_FDCWD, &created_dirs, opts)?;
+
+    match limit_err {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Hard link `dst` to the first copy of its group, or copy `src` in full
+/// when the group has none (its first occurrence failed) or that copy can't
+/// be linked to (-u left something else in its place). A full copy becomes
+/// the group's first.
+fn link_or_copy(
+    hlmap: &mut HashMap<(u64, u64), PathBuf>,
+    key: (u64, u64),
+    src: &Path,
+    dst: &Path,
+    opts: &CopyOptions,
+) -> CpResult<()> {
+    if let Some(first) = hlmap.get(&key)
+        && link_to_first(first, dst, opts).is_ok()
+    {
+        return Ok(());
+    }
+    copy::copy_single(src, dst, opts, false, &ProgressBar::hidden())?;
+    hlmap.insert(key, dst.to_path_buf());
+    Ok(())
+}
 
+/// Replace `link` with a hard link to `first`, copied earlier in the walk.
+fn link_to_first(first: &Path, link: &Path, opts: &CopyOptions) -> CpResult<()> {
+    if link.exists() {
+        let _ = fs::remove_file(link);
+    }
+    fs::hard_link(first, link).map_err(|e| CpError::HardLink {
+        src: first.to_path_buf(),
+        dst: link.to_path_buf(),
+        source: e,
+    })?;
+    if opts.verbose {
+        println!("hard linked '{}' => '{}'", link.display(), first.display());
+    }
     Ok(())
 }
 
+/// Copy collected walkdir entries on a Rayon pool (sized by --jobs when given).
+/// Small batches stay sequential — thread dispatch isn't worth it.
+/// Returns the destinations whose copy failed with a tolerated error.
+fn copy_entries_parallel(
+    entries: &[(PathBuf, PathBuf)],
+    opts: &CopyOptions,
+    dir_progress: &progress::DirProgressCounter,
+) -> CpResult<HashSet<PathBuf>> {
+    use rayon::prelude::*;
+
+    let failed = std::sync::Mutex::new(HashSet::new());
+    let copy_one = |(src, dst): &(PathBuf, PathBuf)| -> CpResult<()> {
+        if let Err(e) = copy::copy_single(src, dst, opts, false, &ProgressBar::hidden()) {
+            tolerate(e, src, opts)?;
+            failed.lock().unwrap().insert(dst.clone());
+        }
+        dir_progress.inc();
+        Ok(())
+    };
+
+    if entries.len() < PARALLEL_THRESHOLD {
+        entries.iter().try_for_each(copy_one)?;
+    } else {
+        match opts
+            .jobs
+            .and_then(|n| rayon::ThreadPoolBuilder::new().num_threads(n).build().ok())
+        {
+            Some(pool) => pool.install(|| entries.par_iter().try_for_each(copy_one))?,
+            None => entries.par_iter().try_for_each(copy_one)?,
+        }
+    }
+    Ok(failed.into_inner().unwrap())
+}
+
 // ─── fd-based helpers ────────────────────────────────────────────────────────
 
+/// Copy the xattrs of `src_fd` onto `dst_fd`, warning about skipped ones.
+/// A destination without xattr support (ENOTSUP) turns them off for the
+/// rest of the copy, with one warning if they were asked for by name.
+fn copy_xattrs_fd(src_fd: i32, dst_fd: i32, dst: &Path, opts: &CopyOptions) -> std::io::Result<()> {
+    match preserve_xattr_fd(src_fd, dst_fd) {
+        Ok(skipped) => {
+            metadata::warn_xattrs_skipped(dst, skipped, opts);
+            Ok(())
+        }
+        Err(e) if metadata::note_xattr_error(&e, false) => {
+            if opts.require_xattr {
+                util::warn(
+                    opts,
+                    format_args!(
+                        "cannot preserve extended attributes for '{}': {}",
+                        dst.display(),
+                        e
+                    ),
+                );
+            }
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+use nix::libc::{fgetxattr, flistxattr, fsetxattr};
+
+/// The fd xattr calls with their Linux signatures: macOS adds an options
+/// argument, and a position that only resource forks use.
+#[cfg(target_os = "macos")]
+unsafe fn flistxattr(fd: RawFd, list: *mut nix::libc::c_char, size: usize) -> nix::libc::ssize_t {
+    unsafe { nix::libc::flistxattr(fd, list, size, 0) }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn fgetxattr(
+    fd: RawFd,
+    name: *const nix::libc::c_char,
+    value: *mut nix::libc::c_void,
+    size: usize,
+) -> nix::libc::ssize_t {
+    unsafe { nix::libc::fgetxattr(fd, name, value, size, 0, 0) }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn fsetxattr(
+    fd: RawFd,
+    name: *const nix::libc::c_char,
+    value: *const nix::libc::c_void,
+    size: usize,
+    flags: nix::libc::c_int,
+) -> nix::libc::c_int {
+    unsafe { nix::libc::fsetxattr(fd, name, value, size, 0, flags) }
+}
+
 /// Preserve xattrs using fd-based syscalls (no path resolution).
-fn preserve_xattr_fd(src_fd: i32, dst_fd: i32) {
-    use nix::libc::{c_char, c_void, fgetxattr, flistxattr, fsetxattr, ssize_t};
+/// Returns how many were skipped (see `metadata::xattr_skippable`); other
+/// failures, ENOTSUP included, are errors.
+fn preserve_xattr_fd(src_fd: i32, dst_fd: i32) -> std::io::Result<usize> {
+    use nix::libc::{c_char, c_void, ssize_t};
 
     let size: ssize_t = unsafe { flistxattr(src_fd, std::ptr::null_mut(), 0) };
     if size <= 0 {
-        return;
+        return Ok(0);
     }
 
     let mut list = vec![0u8; size as usize];
     let size = unsafe { flistxattr(src_fd, list.as_mut_ptr() as *mut c_char, list.len()) };
     if size <= 0 {
-        return;
+        return Ok(0);
     }
 
     let mut val_buf: Vec<u8> = Vec::with_capacity(256);
+    let mut skipped = 0;
 
     for name in list[..size as usize].split(|&b| b == 0) {
         if name.is_empty() {
@@ -1071,38 +2513,44 @@ fn preserve_xattr_fd(src_fd: i32, dst_fd: i32) {
         let name_ptr = name_z.as_ptr() as *const c_char;
 
         let val_size = unsafe { fgetxattr(src_fd, name_ptr, std::ptr::null_mut(), 0) };
-        if val_size < 0 {
-            continue;
-        }
-
-        if val_size == 0 {
-            unsafe { fsetxattr(dst_fd, name_ptr, std::ptr::null(), 0, 0) };
-            continue;
-        }
-
-        val_buf.resize(val_size as usize, 0);
-        let got = unsafe {
-            fgetxattr(
-                src_fd,
-                name_ptr,
-                val_buf.as_mut_ptr() as *mut c_void,
-                val_buf.len(),
-            )
+        let ret = if val_size < 0 {
+            -1
+        } else if val_size == 0 {
+            unsafe { fsetxattr(dst_fd, name_ptr, std::ptr::null(), 0, 0) }
+        } else {
+            val_buf.resize(val_size as usize, 0);
+            let got = unsafe {
+                fgetxattr(
+                    src_fd,
+                    name_ptr,
+                    val_buf.as_mut_ptr() as *mut c_void,
+                    val_buf.len(),
+                )
+            };
+            if got < 0 {
+                -1
+            } else {
+                unsafe {
+                    fsetxattr(
+                        dst_fd,
+                        name_ptr,
+                        val_buf.as_ptr() as *const c_void,
+                        got as usize,
+                        0,
+                    )
+                }
+            }
         };
-        if got < 0 {
-            continue;
-        }
 
-        unsafe {
-            fsetxattr(
-                dst_fd,
-                name_ptr,
-                val_buf.as_ptr() as *const c_void,
-                got as usize,
-                0,
-            );
+        if ret < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(nix::libc::ENOTSUP) || !metadata::xattr_skippable(&err) {
+                return Err(err);
+            }
+            skipped += 1;
         }
     }
+    Ok(skipped)
 }
 
 /// Preserve ACL using fd-based syscalls (no path resolution).
@@ -1123,3 +2571,36 @@ fn preserve_acl_fd(src_fd: i32, dst_fd: i32) {
         acl_free(acl);
     }
 }
+
+/// The default ACL of directory `src_fd` onto `dst_fd`: acl_get_fd only
+/// reads the access ACL, the default one is the system.posix_acl_default
+/// xattr.
+fn preserve_default_acl_fd(src_fd: i32, dst_fd: i32) {
+    use nix::libc::c_void;
+
+    let name = c"system.posix_acl_default";
+    let size = unsafe { fgetxattr(src_fd, name.as_ptr(), std::ptr::null_mut(), 0) };
+    if size <= 0 {
+        return;
+    }
+    let mut value = vec![0u8; size as usize];
+    let size = unsafe {
+        fgetxattr(
+            src_fd,
+            name.as_ptr(),
+            value.as_mut_ptr() as *mut c_void,
+            value.len(),
+        )
+    };
+    if size > 0 {
+        unsafe {
+            fsetxattr(
+                dst_fd,
+                name.as_ptr(),
+                value.as_ptr() as *const c_void,
+                size as usize,
+                0,
+            )
+        };
+    }
+}
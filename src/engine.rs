@@ -1,17 +1,24 @@
+use std::collections::BTreeSet;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::AsRawFd;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use indicatif::ProgressBar;
 
-use crate::cli::ReflinkMode;
+use crate::cli::{ReflinkFallback, ReflinkMode};
 use crate::error::{CpError, CpResult};
+use crate::timeout;
+use crate::util;
 
-/// Size of chunks for copy_file_range (64 MiB).
+/// Size of chunks for copy_file_range (64 MiB), unless --chunk-size.
 const COPY_FILE_RANGE_CHUNK: usize = 64 * 1024 * 1024;
 
-/// Size of chunks for sendfile (64 MiB).
+/// Size of chunks for sendfile (64 MiB), unless --chunk-size.
 const SENDFILE_CHUNK: usize = 64 * 1024 * 1024;
 
 /// Buffer size for read/write fallback (256 KiB).
@@ -20,12 +27,43 @@ const RW_BUF_SIZE: usize = 256 * 1024;
 /// FICLONE ioctl number (from linux/fs.h: _IOW(0x94, 9, int))
 const FICLONE: nix::libc::c_ulong = 0x40049409;
 
+/// --debug name of the in-kernel copy `try_copy_file_range` makes.
+#[cfg(not(target_os = "macos"))]
+const COPY_FILE_RANGE_METHOD: &str = "copy_file_range";
+#[cfg(target_os = "macos")]
+const COPY_FILE_RANGE_METHOD: &str = "fcopyfile";
+
+/// FS_IOC_FIEMAP ioctl number (from linux/fs.h: _IOWR('f', 11, struct fiemap))
+const FS_IOC_FIEMAP: nix::libc::c_ulong = 0xC020660B;
+const FIEMAP_FLAG_SYNC: u32 = 0x1;
+const FIEMAP_EXTENT_LAST: u32 = 0x1;
+const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+
+/// Extents fetched per FIEMAP call.
+const FIEMAP_BATCH: usize = 64;
+
 /// Threshold below which FICLONE is skipped for reflink=auto.
 /// The ioctl overhead isn't worth it for tiny files on non-CoW fs.
 const FICLONE_THRESHOLD: u64 = 256 * 1024;
 
+/// Destination devices whose filesystem answered FICLONE with "not
+/// supported". reflink=auto and try-always stop asking them for the rest of
+/// the run, instead of paying a failed ioctl per file; another device (a
+/// CoW filesystem mounted inside the destination) is still tried.
+static FICLONE_UNSUPPORTED: Mutex<BTreeSet<u64>> = Mutex::new(BTreeSet::new());
+
+/// Set once FICLONE_UNSUPPORTED holds a device: until then there is no need
+/// to fstat the destination to look its device up.
+static FICLONE_ANY_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+
 /// Copy file data using the optimal kernel mechanism.
 /// Returns the method used as a string (for --debug).
+/// With `io_timeout`, each data-transfer step is aborted if it stalls longer.
+/// `chunk_size` bounds each copy_file_range and sendfile call, and so the
+/// progress bar's steps.
+/// `fallback` picks the engine when no reflink is made; a forced engine that
+/// fails is an error rather than a reason to try the next one.
+#[allow(clippy::too_many_arguments)]
 pub fn copy_file_data(
     src: &File,
     dst: &File,
@@ -33,22 +71,37 @@ pub fn copy_file_data(
     src_path: &Path,
     dst_path: &Path,
     reflink: ReflinkMode,
+    fallback: ReflinkFallback,
+    io_timeout: Option<Duration>,
+    chunk_size: Option<usize>,
     pb: &ProgressBar,
 ) -> CpResult<&'static str> {
+    let cfr_chunk = chunk_size.unwrap_or(COPY_FILE_RANGE_CHUNK);
+    let sendfile_chunk = chunk_size.unwrap_or(SENDFILE_CHUNK);
+    let clone_required = reflink == ReflinkMode::Always
+        || (matches!(reflink, ReflinkMode::Auto | ReflinkMode::TryAlways)
+            && fallback == ReflinkFallback::Error);
+
     // Step 1: Try FICLONE (reflink/CoW)
     // Skip for small files with reflink=auto — the ioctl syscall cost isn't worthwhile
     let try_reflink = match reflink {
         ReflinkMode::Never => false,
         ReflinkMode::Always => true,
-        ReflinkMode::Auto => size >= FICLONE_THRESHOLD,
+        ReflinkMode::Auto => {
+            (size >= FICLONE_THRESHOLD && !ficlone_known_unsupported(dst)) || clone_required
+        }
+        ReflinkMode::TryAlways => !ficlone_known_unsupported(dst) || clone_required,
     };
     if try_reflink {
-        match try_ficlone(src, dst) {
+        let cloned = run_timed(io_timeout, src, dst, src_path, pb, |s, d, _| {
+            try_ficlone(s, d)
+        })?;
+        match cloned {
             Ok(()) => {
                 pb.inc(size);
                 return Ok("reflink (FICLONE)");
             }
-            Err(_) if reflink == ReflinkMode::Always => {
+            Err(_) if clone_required => {
                 return Err(CpError::Copy {
                     src: src_path.to_path_buf(),
                     dst: dst_path.to_path_buf(),
@@ -59,44 +112,342 @@ pub fn copy_file_data(
         }
     }
 
+    let forced_failure = |engine: &str| CpError::Copy {
+        src: src_path.to_path_buf(),
+        dst: dst_path.to_path_buf(),
+        reason: format!(
+            "{} failed (--reflink-fallback={})",
+            engine,
+            engine.replace('_', "-")
+        ),
+    };
+    match fallback {
+        ReflinkFallback::CopyFileRange => {
+            let cfr = run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+                try_copy_file_range(s, d, size, cfr_chunk, pb)
+            })?;
+            return match cfr {
+                Ok(copied) if copied == size => Ok(COPY_FILE_RANGE_METHOD),
+                Err(e) if is_quota(&e) => Err(write_error(dst_path, e)),
+                _ => Err(forced_failure("copy_file_range")),
+            };
+        }
+        ReflinkFallback::Sendfile => {
+            let sent = run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+                try_sendfile(s, d, size, sendfile_chunk, pb)
+            })?;
+            return match sent {
+                Ok(()) => Ok("sendfile"),
+                Err(()) => Err(forced_failure("sendfile")),
+            };
+        }
+        ReflinkFallback::ReadWrite => {
+            read_write_timed(src, dst, src_path, dst_path, io_timeout, pb)?;
+            return Ok("read/write");
+        }
+        ReflinkFallback::Auto | ReflinkFallback::Error => {}
+    }
+
     // Step 2: Try copy_file_range (zero-copy kernel)
-    match try_copy_file_range(src, dst, size, pb) {
-        Ok(copied) if copied == size => return Ok("copy_file_range"),
+    let cfr = run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+        try_copy_file_range(s, d, size, cfr_chunk, pb)
+    })?;
+    match cfr {
+        Ok(copied) if copied == size => return Ok(COPY_FILE_RANGE_METHOD),
         Ok(copied) if copied > 0 => {
             // Partial success, finish with sendfile or read/write
             let remaining = size - copied;
-            if try_sendfile(src, dst, remaining, pb).is_ok() {
+            let sent = run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+                try_sendfile(s, d, remaining, sendfile_chunk, pb)
+            })?;
+            if sent.is_ok() {
                 return Ok("copy_file_range+sendfile");
             }
-            do_read_write(src, dst, src_path, dst_path, pb)?;
+            read_write_timed(src, dst, src_path, dst_path, io_timeout, pb)?;
             return Ok("copy_file_range+read/write");
         }
+        // Another engine won't get past the quota either
+        Err(e) if is_quota(&e) => return Err(write_error(dst_path, e)),
         _ => {}
     }
 
     // Step 3: Try sendfile
-    if try_sendfile(src, dst, size, pb).is_ok() {
+    let sent = run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+        try_sendfile(s, d, size, sendfile_chunk, pb)
+    })?;
+    if sent.is_ok() {
         return Ok("sendfile");
     }
 
     // Step 4: Fallback to read/write
-    do_read_write(src, dst, src_path, dst_path, pb)?;
+    read_write_timed(src, dst, src_path, dst_path, io_timeout, pb)?;
     Ok("read/write")
 }
 
+/// struct fiemap, with room for FIEMAP_BATCH extents.
+#[repr(C)]
+struct Fiemap {
+    start: u64,
+    length: u64,
+    flags: u32,
+    mapped_extents: u32,
+    extent_count: u32,
+    reserved: u32,
+    extents: [FiemapExtent; FIEMAP_BATCH],
+}
+
+/// struct fiemap_extent.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FiemapExtent {
+    logical: u64,
+    physical: u64,
+    length: u64,
+    reserved64: [u64; 2],
+    flags: u32,
+    reserved: [u32; 3],
+}
+
+/// Bytes of `file` in extents it shares with other files, per FIEMAP: on
+/// btrfs/XFS, what a reflink (or a copy_file_range turned into one) left
+/// shared with the source.
+pub fn shared_bytes(file: &File) -> std::io::Result<u64> {
+    let size = file.metadata()?.len();
+    let mut shared = 0;
+    let mut start = 0;
+    while start < size {
+        let mut map = Fiemap {
+            start,
+            length: u64::MAX - start,
+            flags: FIEMAP_FLAG_SYNC,
+            mapped_extents: 0,
+            extent_count: FIEMAP_BATCH as u32,
+            reserved: 0,
+            extents: [FiemapExtent::default(); FIEMAP_BATCH],
+        };
+        let ret = unsafe { nix::libc::ioctl(file.as_raw_fd(), FS_IOC_FIEMAP, &mut map) };
+        if ret != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let extents = &map.extents[..map.mapped_extents as usize];
+        let Some(last) = extents.last() else {
+            break;
+        };
+        for ext in extents {
+            if ext.flags & FIEMAP_EXTENT_SHARED != 0 {
+                // The last extent may run past the end of the file
+                let end = std::cmp::min(ext.logical + ext.length, size);
+                shared += end.saturating_sub(ext.logical);
+            }
+        }
+        if last.flags & FIEMAP_EXTENT_LAST != 0 {
+            break;
+        }
+        start = last.logical + last.length;
+    }
+    Ok(shared)
+}
+
+/// --report-shared: bytes copied so far, and how many of them share extents
+/// with another file instead of taking new space.
+#[derive(Clone, Default)]
+pub struct SharedReport {
+    copied: Arc<AtomicU64>,
+    shared: Arc<AtomicU64>,
+}
+
+impl std::fmt::Debug for SharedReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedReport")
+    }
+}
+
+impl SharedReport {
+    /// Count `dst`, a fresh copy of `size` bytes. A filesystem without
+    /// FIEMAP counts it as newly allocated.
+    pub fn record(&self, dst: &Path, size: u64) {
+        let shared = File::open(dst).and_then(|f| shared_bytes(&f)).unwrap_or(0);
+        self.copied.fetch_add(size, Ordering::Relaxed);
+        self.shared.fetch_add(shared.min(size), Ordering::Relaxed);
+    }
+
+    /// `shared 1.0 MiB of 1.0 MiB copied (100%), 0 B newly allocated`
+    pub fn summary(&self) -> String {
+        let copied = self.copied.load(Ordering::Relaxed);
+        let shared = self.shared.load(Ordering::Relaxed);
+        let percent = (shared * 100).checked_div(copied).unwrap_or(0);
+        format!(
+            "shared {} of {} copied ({}%), {} newly allocated",
+            util::human_size(shared),
+            util::human_size(copied),
+            percent,
+            util::human_size(copied - shared)
+        )
+    }
+}
+
+/// Copy a file reporting size 0 — pseudo files under /proc and /sys have
+/// content all the same — with read/write until EOF, as GNU cp does.
+/// Returns the method, "empty" when there was nothing to copy.
+pub fn copy_unsized(
+    src: &File,
+    dst: &File,
+    src_path: &Path,
+    dst_path: &Path,
+    io_timeout: Option<Duration>,
+    pb: &ProgressBar,
+) -> CpResult<&'static str> {
+    read_write_timed(src, dst, src_path, dst_path, io_timeout, pb)?;
+    let written = dst.metadata().map(|m| m.len()).unwrap_or(0);
+    Ok(if written > 0 { "read/write" } else { "empty" })
+}
+
+/// --debug note on why a forced --reflink-fallback engine was used, e.g.
+/// `reflink failed, copy_file_range disabled by --reflink-fallback`.
+/// None when the normal engine chain applies.
+pub fn fallback_note(reflink: ReflinkMode, fallback: ReflinkFallback, size: u64) -> Option<String> {
+    let disabled = match fallback {
+        ReflinkFallback::CopyFileRange => "sendfile, read/write",
+        ReflinkFallback::Sendfile => "copy_file_range, read/write",
+        ReflinkFallback::ReadWrite => "copy_file_range, sendfile",
+        ReflinkFallback::Auto | ReflinkFallback::Error => return None,
+    };
+    let reflink_state = match reflink {
+        ReflinkMode::Auto if size >= FICLONE_THRESHOLD => "reflink failed",
+        ReflinkMode::TryAlways => "reflink failed",
+        ReflinkMode::Auto => "reflink skipped for small file",
+        _ => "reflink disabled",
+    };
+    Some(format!(
+        "{}, {} disabled by --reflink-fallback",
+        reflink_state, disabled
+    ))
+}
+
+/// Run a data-transfer step, bounded by `io_timeout` when set: it times out
+/// once no chunk has completed for that long. The step runs on duplicated
+/// fds so it can outlive the caller on timeout; those are then cut off (see
+/// `revoke`), so whatever it was stalled in, it copies nothing more.
+pub fn run_timed<T, F>(
+    io_timeout: Option<Duration>,
+    src: &File,
+    dst: &File,
+    path: &Path,
+    pb: &ProgressBar,
+    f: F,
+) -> CpResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&File, &File, &ProgressBar) -> T + Send + 'static,
+{
+    let Some(duration) = io_timeout else {
+        return Ok(f(src, dst, pb));
+    };
+
+    let dup = |f: &File| {
+        f.try_clone().map(Arc::new).map_err(|e| CpError::Read {
+            path: path.to_path_buf(),
+            source: e,
+        })
+    };
+    let (s, d) = (dup(src)?, dup(dst)?);
+    // The step counts its chunks on a bar of its own: each one that moves
+    // pushes the deadline back, and is passed on to `pb`
+    let step_pb = ProgressBar::hidden();
+    let (ws, wd, wpb) = (Arc::clone(&s), Arc::clone(&d), step_pb.clone());
+    let mut seen = 0;
+    let mut forward = || {
+        let pos = step_pb.position();
+        let moved = pos != seen;
+        pb.inc(pos - seen);
+        seen = pos;
+        moved
+    };
+
+    let result = timeout::with_idle_timeout(duration, &mut forward, move || f(&ws, &wd, &wpb));
+    forward();
+    result.map_err(|_| {
+        revoke(&s, File::options().write(true));
+        revoke(&d, File::options().read(true));
+        CpError::Timeout {
+            path: path.to_path_buf(),
+            duration,
+        }
+    })
+}
+
+/// Point the fd of `file`, which a timed-out step may still be using, at
+/// /dev/null opened as `null` says: opened for the other direction, it makes
+/// the step's next read or write fail instead of touching the file.
+fn revoke(file: &File, null: &std::fs::OpenOptions) {
+    if let Ok(null) = null.open("/dev/null") {
+        unsafe { nix::libc::dup2(null.as_raw_fd(), file.as_raw_fd()) };
+    }
+}
+
+/// `do_read_write` bounded by `io_timeout`.
+fn read_write_timed(
+    src: &File,
+    dst: &File,
+    src_path: &Path,
+    dst_path: &Path,
+    io_timeout: Option<Duration>,
+    pb: &ProgressBar,
+) -> CpResult<()> {
+    let (sp, dp) = (src_path.to_path_buf(), dst_path.to_path_buf());
+    run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+        do_read_write(s, d, &sp, &dp, pb)
+    })?
+}
+
 /// Try to clone via FICLONE ioctl.
+/// A filesystem without clone support is remembered in FICLONE_UNSUPPORTED;
+/// EXDEV or EINVAL only say something about this pair of files.
 fn try_ficlone(src: &File, dst: &File) -> Result<(), ()> {
     let ret = unsafe { nix::libc::ioctl(dst.as_raw_fd(), FICLONE, src.as_raw_fd()) };
-    if ret == 0 { Ok(()) } else { Err(()) }
+    if ret == 0 {
+        return Ok(());
+    }
+    if let Some(nix::libc::EOPNOTSUPP | nix::libc::ENOTTY | nix::libc::ENOSYS) =
+        std::io::Error::last_os_error().raw_os_error()
+        && let Ok(meta) = dst.metadata()
+    {
+        let mut devices = FICLONE_UNSUPPORTED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        devices.insert(meta.dev());
+        FICLONE_ANY_UNSUPPORTED.store(true, Ordering::Relaxed);
+    }
+    Err(())
+}
+
+/// Whether FICLONE already failed as unsupported on the device `dst` is on.
+pub fn ficlone_known_unsupported(dst: &File) -> bool {
+    FICLONE_ANY_UNSUPPORTED.load(Ordering::Relaxed)
+        && dst.metadata().is_ok_and(|m| {
+            FICLONE_UNSUPPORTED
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .contains(&m.dev())
+        })
 }
 
 /// Try copy_file_range syscall in a loop, feeding progress.
-fn try_copy_file_range(src: &File, dst: &File, size: u64, pb: &ProgressBar) -> Result<u64, ()> {
+/// Returns the bytes copied, which may fall short of `size` when the kernel
+/// gives up midway; an error when nothing was copied or the quota ran out.
+#[cfg(not(target_os = "macos"))]
+fn try_copy_file_range(
+    src: &File,
+    dst: &File,
+    size: u64,
+    max_chunk: usize,
+    pb: &ProgressBar,
+) -> Result<u64, std::io::Error> {
     let mut copied: u64 = 0;
 
     while copied < size {
-        let chunk = std::cmp::min((size - copied) as usize, COPY_FILE_RANGE_CHUNK);
-        let ret = unsafe {
+        let chunk = std::cmp::min((size - copied) as usize, max_chunk);
+        let ret = util::retry_eintr(|| unsafe {
             nix::libc::copy_file_range(
                 src.as_raw_fd(),
                 std::ptr::null_mut(),
@@ -105,19 +456,11 @@ fn try_copy_file_range(src: &File, dst: &File, size: u64, pb: &ProgressBar) -> R
                 chunk,
                 0,
             )
-        };
+        });
         if ret < 0 {
             let err = std::io::Error::last_os_error();
-            let errno = err.raw_os_error().unwrap_or(0);
-            if errno == nix::libc::ENOSYS || errno == nix::libc::EXDEV || errno == nix::libc::EINVAL
-            {
-                if copied == 0 {
-                    return Err(());
-                }
-                break;
-            }
-            if copied == 0 {
-                return Err(());
+            if copied == 0 || is_quota(&err) {
+                return Err(err);
             }
             break;
         } else if ret == 0 {
@@ -132,20 +475,83 @@ fn try_copy_file_range(src: &File, dst: &File, size: u64, pb: &ProgressBar) -> R
     Ok(copied)
 }
 
+/// macOS has no copy_file_range: fcopyfile(3) copies the whole data in one
+/// call, without a byte count, so progress jumps to `size` when it returns.
+#[cfg(target_os = "macos")]
+fn try_copy_file_range(
+    src: &File,
+    dst: &File,
+    size: u64,
+    _max_chunk: usize,
+    pb: &ProgressBar,
+) -> Result<u64, std::io::Error> {
+    let ret = unsafe {
+        nix::libc::fcopyfile(
+            src.as_raw_fd(),
+            dst.as_raw_fd(),
+            std::ptr::null_mut(),
+            nix::libc::COPYFILE_DATA,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    pb.inc(size);
+    Ok(size)
+}
+
+/// Copy `len` bytes from the current offset of `src` to the current offset
+/// of `dst` with copy_file_range, advancing both. Returns the bytes copied,
+/// 0 when the kernel won't do it, so the caller finishes with read/write.
+/// Running out of quota is an error.
+#[cfg(not(target_os = "macos"))]
+pub fn copy_range(
+    src: &File,
+    dst: &File,
+    len: u64,
+    dst_path: &Path,
+    pb: &ProgressBar,
+) -> CpResult<u64> {
+    match try_copy_file_range(src, dst, len, COPY_FILE_RANGE_CHUNK, pb) {
+        Ok(copied) => Ok(copied),
+        Err(e) if is_quota(&e) => Err(write_error(dst_path, e)),
+        Err(_) => Ok(0),
+    }
+}
+
+/// fcopyfile(3) copies whole files only: ranges go through read/write.
+#[cfg(target_os = "macos")]
+pub fn copy_range(
+    _src: &File,
+    _dst: &File,
+    _len: u64,
+    _dst_path: &Path,
+    _pb: &ProgressBar,
+) -> CpResult<u64> {
+    Ok(0)
+}
+
 /// Try sendfile syscall in a loop, feeding progress.
-fn try_sendfile(src: &File, dst: &File, size: u64, pb: &ProgressBar) -> Result<(), ()> {
+#[cfg(not(target_os = "macos"))]
+fn try_sendfile(
+    src: &File,
+    dst: &File,
+    size: u64,
+    max_chunk: usize,
+    pb: &ProgressBar,
+) -> Result<(), ()> {
     let mut remaining = size;
 
     while remaining > 0 {
-        let chunk = std::cmp::min(remaining as usize, SENDFILE_CHUNK);
-        let ret = unsafe {
+        let chunk = std::cmp::min(remaining as usize, max_chunk);
+        let ret = util::retry_eintr(|| unsafe {
             nix::libc::sendfile64(
                 dst.as_raw_fd(),
                 src.as_raw_fd(),
                 std::ptr::null_mut(),
                 chunk,
             )
-        };
+        });
         if ret < 0 {
             if remaining == size {
                 return Err(());
@@ -163,6 +569,191 @@ fn try_sendfile(src: &File, dst: &File, size: u64, pb: &ProgressBar) -> Result<(
     Ok(())
 }
 
+/// macOS sendfile(2) only writes to sockets: files go through read/write.
+#[cfg(target_os = "macos")]
+fn try_sendfile(
+    _src: &File,
+    _dst: &File,
+    _size: u64,
+    _max_chunk: usize,
+    _pb: &ProgressBar,
+) -> Result<(), ()> {
+    Err(())
+}
+
+/// --direct: O_DIRECT transfers need buffer, offset and length aligned to the
+/// logical block size; 4 KiB covers the devices in use.
+const DIRECT_ALIGN: usize = 4096;
+const DIRECT_BUF_SIZE: usize = 1024 * 1024;
+
+/// A zeroed heap buffer aligned to DIRECT_ALIGN.
+struct AlignedBuf {
+    ptr: std::ptr::NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+impl AlignedBuf {
+    fn new(size: usize) -> Self {
+        let layout = std::alloc::Layout::from_size_align(size, DIRECT_ALIGN).unwrap();
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        let ptr =
+            std::ptr::NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        AlignedBuf { ptr, layout }
+    }
+}
+
+impl std::ops::Deref for AlignedBuf {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl std::ops::DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+/// Turn O_DIRECT on or off for an open file. False when the filesystem
+/// refuses it (EINVAL, e.g. on older tmpfs).
+#[cfg(not(target_os = "macos"))]
+fn set_direct(file: &File, on: bool) -> bool {
+    let fd = file.as_raw_fd();
+    let flags = unsafe { nix::libc::fcntl(fd, nix::libc::F_GETFL) };
+    if flags < 0 {
+        return false;
+    }
+    let flags = if on {
+        flags | nix::libc::O_DIRECT
+    } else {
+        flags & !nix::libc::O_DIRECT
+    };
+    unsafe { nix::libc::fcntl(fd, nix::libc::F_SETFL, flags) == 0 }
+}
+
+/// macOS has no O_DIRECT: F_NOCACHE keeps the file's data out of the cache.
+#[cfg(target_os = "macos")]
+fn set_direct(file: &File, on: bool) -> bool {
+    unsafe { nix::libc::fcntl(file.as_raw_fd(), nix::libc::F_NOCACHE, on as i32) == 0 }
+}
+
+/// --fadvise: sources from this size on get access-pattern hints.
+pub const FADVISE_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// --fadvise, before copying: `src` is read front to back, so the kernel can
+/// read further ahead.
+#[cfg(not(target_os = "macos"))]
+pub fn advise_sequential(src: &File) {
+    unsafe { nix::libc::posix_fadvise(src.as_raw_fd(), 0, 0, nix::libc::POSIX_FADV_SEQUENTIAL) };
+}
+
+#[cfg(target_os = "macos")]
+pub fn advise_sequential(_src: &File) {}
+
+/// Drop `file`'s pages from the page cache.
+#[cfg(not(target_os = "macos"))]
+pub fn drop_cache(file: &File) {
+    unsafe { nix::libc::posix_fadvise(file.as_raw_fd(), 0, 0, nix::libc::POSIX_FADV_DONTNEED) };
+}
+
+#[cfg(target_os = "macos")]
+pub fn drop_cache(_file: &File) {}
+
+fn is_einval(err: &std::io::Error) -> bool {
+    err.raw_os_error() == Some(nix::libc::EINVAL)
+}
+
+/// --direct: read/write through an aligned buffer with O_DIRECT on both
+/// files, so a large streaming copy leaves the page cache alone. A side that
+/// can't do O_DIRECT is copied buffered and dropped from the cache with
+/// posix_fadvise(DONTNEED) afterwards. The destination turns O_DIRECT off
+/// for an unaligned tail.
+pub fn copy_direct(
+    src: &File,
+    dst: &File,
+    src_path: &Path,
+    dst_path: &Path,
+    io_timeout: Option<Duration>,
+    pb: &ProgressBar,
+) -> CpResult<&'static str> {
+    let (sp, dp) = (src_path.to_path_buf(), dst_path.to_path_buf());
+    run_timed(io_timeout, src, dst, src_path, pb, move |s, d, pb| {
+        do_direct(s, d, &sp, &dp, pb)
+    })?
+}
+
+/// `copy_direct` without the timeout.
+fn do_direct(
+    src: &File,
+    dst: &File,
+    src_path: &Path,
+    dst_path: &Path,
+    pb: &ProgressBar,
+) -> CpResult<&'static str> {
+    let mut src_direct = set_direct(src, true);
+    let mut dst_direct = set_direct(dst, true);
+    // Whether any data went through with O_DIRECT (the tail never does)
+    let mut dst_used_direct = false;
+    let mut buf = AlignedBuf::new(DIRECT_BUF_SIZE);
+    let mut reader = src;
+    let mut writer = dst;
+    let mut copied = 0u64;
+
+    loop {
+        let n = match util::read_retry(&mut reader, &mut buf) {
+            // Accepted by fcntl but not by the filesystem's read path
+            Err(e) if src_direct && copied == 0 && is_einval(&e) => {
+                src_direct = !set_direct(src, false);
+                continue;
+            }
+            r => r.map_err(|e| CpError::Read {
+                path: src_path.to_path_buf(),
+                source: e,
+            })?,
+        };
+        if n == 0 {
+            break;
+        }
+        if dst_direct && n % DIRECT_ALIGN != 0 {
+            dst_direct = !set_direct(dst, false);
+        }
+        match writer.write_all(&buf[..n]) {
+            Err(e) if dst_direct && copied == 0 && is_einval(&e) => {
+                dst_direct = !set_direct(dst, false);
+                writer
+                    .write_all(&buf[..n])
+                    .map_err(|e| write_error(dst_path, e))?;
+            }
+            r => r.map_err(|e| write_error(dst_path, e))?,
+        }
+        dst_used_direct |= dst_direct;
+        copied += n as u64;
+        pb.inc(n as u64);
+    }
+
+    // Whatever went through the page cache, drop it (dirty pages first)
+    if !src_direct {
+        drop_cache(src);
+    }
+    if !dst_direct {
+        let _ = dst.sync_data();
+        drop_cache(dst);
+    }
+
+    Ok(match (src_direct, dst_used_direct) {
+        (true, true) => "O_DIRECT",
+        (false, false) => "read/write (fadvise DONTNEED)",
+        _ => "O_DIRECT (one side fadvise DONTNEED)",
+    })
+}
+
 /// Fallback: read/write in userspace.
 fn do_read_write(
     src: &File,
@@ -176,19 +767,38 @@ fn do_read_write(
     let mut buf = vec![0u8; RW_BUF_SIZE];
 
     loop {
-        let n = reader.read(&mut buf).map_err(|e| CpError::Read {
+        let n = util::read_retry(&mut reader, &mut buf).map_err(|e| CpError::Read {
             path: src_path.to_path_buf(),
             source: e,
         })?;
         if n == 0 {
             break;
         }
-        writer.write_all(&buf[..n]).map_err(|e| CpError::Write {
-            path: dst_path.to_path_buf(),
-            source: e,
-        })?;
+        writer
+            .write_all(&buf[..n])
+            .map_err(|e| write_error(dst_path, e))?;
         pb.inc(n as u64);
     }
 
     Ok(())
 }
+
+/// The error for a failed write to `path`: EDQUOT gets its own variant so a
+/// full quota isn't mistaken for a full disk.
+pub fn write_error(path: &Path, source: std::io::Error) -> CpError {
+    if is_quota(&source) {
+        CpError::QuotaExceeded {
+            path: path.to_path_buf(),
+            source,
+        }
+    } else {
+        CpError::Write {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+fn is_quota(err: &std::io::Error) -> bool {
+    err.raw_os_error() == Some(nix::libc::EDQUOT)
+}
@@ -28,6 +28,12 @@ pub enum CpError {
         source: std::io::Error,
     },
 
+    #[error("error reading directory '{path}': {source}")]
+    ReadDir {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[error("failed to read from '{path}': {source}")]
     Read {
         path: PathBuf,
@@ -46,6 +52,12 @@ pub enum CpError {
     #[error("cannot copy a directory, '{path}', into itself, '{dest}'")]
     CopyIntoSelf { path: PathBuf, dest: PathBuf },
 
+    #[error("cannot copy cyclic structure at '{path}'")]
+    Cycle { path: PathBuf },
+
+    #[error("'{path}' is not under --strip-prefix '{prefix}'")]
+    StripPrefix { path: PathBuf, prefix: PathBuf },
+
     #[error("-r not specified; omitting directory '{path}'")]
     OmitDirectory { path: PathBuf },
 
@@ -58,9 +70,18 @@ pub enum CpError {
     #[error("target '{path}' is not a directory")]
     NotADirectory { path: PathBuf },
 
+    #[error("target directory '{path}': {source}")]
+    TargetDirectory {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[error("cannot overwrite non-directory '{dst}' with directory '{src}'")]
     OverwriteNonDir { src: PathBuf, dst: PathBuf },
 
+    #[error("will not overwrite just-created '{dst}' with '{src}'")]
+    WillNotOverwrite { src: PathBuf, dst: PathBuf },
+
     #[error("cannot copy '{src}' to '{dst}': {reason}")]
     Copy {
         src: PathBuf,
@@ -71,6 +92,9 @@ pub enum CpError {
     #[error("failed to preserve ownership of '{path}': {source}")]
     Chown { path: PathBuf, source: nix::Error },
 
+    #[error("cannot change ownership of '{path}': {source}")]
+    SetOwner { path: PathBuf, source: nix::Error },
+
     #[error("failed to preserve permissions of '{path}': {source}")]
     Chmod {
         path: PathBuf,
@@ -126,6 +150,38 @@ pub enum CpError {
     #[error("not replacing '{path}'")]
     UpdateSkipped { path: PathBuf },
 
+    #[cfg(feature = "selinux")]
+    #[error("failed to set the security context of '{path}': {msg}")]
+    Selinux { path: PathBuf, msg: String },
+
+    #[error("I/O on '{path}' timed out after {}s", .duration.as_secs())]
+    Timeout {
+        path: PathBuf,
+        duration: std::time::Duration,
+    },
+
+    #[error("too many files (limit {limit} exceeded at '{path}')")]
+    MaxFilesExceeded { limit: u64, path: PathBuf },
+
+    #[error("total size limit of {limit} bytes exceeded at '{path}'; aborting")]
+    MaxSizeExceeded { limit: u64, path: PathBuf },
+
+    #[error("{groups} hard link group(s) of '{src}' not reproduced in '{dst}'")]
+    HardlinkCountMismatch {
+        src: PathBuf,
+        dst: PathBuf,
+        groups: usize,
+    },
+
+    #[error("cannot extract '{path}': {msg}")]
+    Archive { path: PathBuf, msg: String },
+
+    #[error("cannot write to '{path}': disk quota exceeded")]
+    QuotaExceeded {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
     #[error("failed to seek in '{path}': {source}")]
     Seek {
         path: PathBuf,
@@ -133,4 +189,54 @@ pub enum CpError {
     },
 }
 
+impl CpError {
+    /// The file paths the message names, to rewrite before it is reported.
+    pub fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+        use CpError::*;
+
+        match self {
+            Stat { path, .. }
+            | OpenRead { path, .. }
+            | CreateFile { path, .. }
+            | CreateDir { path, .. }
+            | ReadDir { path, .. }
+            | Read { path, .. }
+            | Write { path, .. }
+            | Cycle { path }
+            | StripPrefix { path, .. }
+            | OmitDirectory { path }
+            | NotADirectory { path }
+            | TargetDirectory { path, .. }
+            | Chown { path, .. }
+            | SetOwner { path, .. }
+            | Chmod { path, .. }
+            | Timestamps { path, .. }
+            | Xattr { path, .. }
+            | Acl { path, .. }
+            | MkNod { path, .. }
+            | ReadLink { path, .. }
+            | DanglingSymlink { path }
+            | Remove { path, .. }
+            | UpdateSkipped { path }
+            | Timeout { path, .. }
+            | MaxFilesExceeded { path, .. }
+            | MaxSizeExceeded { path, .. }
+            | Archive { path, .. }
+            | QuotaExceeded { path, .. }
+            | Seek { path, .. } => vec![path],
+            #[cfg(feature = "selinux")]
+            Selinux { path, .. } => vec![path],
+            Symlink { dst, .. } => vec![dst],
+            CopyIntoSelf { path, dest } => vec![path, dest],
+            SameFile { src, dst }
+            | OverwriteNonDir { src, dst }
+            | WillNotOverwrite { src, dst }
+            | Copy { src, dst, .. }
+            | HardLink { src, dst, .. }
+            | HardlinkCountMismatch { src, dst, .. } => vec![src, dst],
+            MissingDestination { .. } | MissingOperand => Vec::new(),
+        }
+    }
+}
+
 pub type CpResult<T> = Result<T, CpError>;
@@ -1,11 +1,24 @@
 pub mod backup;
+pub mod checkpoint;
 pub mod cli;
+pub mod compare;
 pub mod copy;
+pub mod dedup;
 pub mod dir;
 pub mod engine;
 pub mod error;
+pub mod list;
 pub mod metadata;
 pub mod options;
 pub mod progress;
+pub mod selftest;
+#[cfg(feature = "selinux")]
+pub mod selinux;
 pub mod sparse;
+pub mod stat;
+pub mod tar_src;
+pub mod timeout;
 pub mod util;
+pub mod verify;
+
+pub use dir::copy_at;
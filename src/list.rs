@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::copy;
+use crate::error::{CpError, CpResult};
+use crate::options::{CopyOptions, Dereference};
+use crate::util;
+
+/// One entry a copy would create.
+struct Entry {
+    kind: &'static str,
+    src: PathBuf,
+    dst: PathBuf,
+    size: u64,
+}
+
+/// --list: print what copying `source` to `target` would copy, without
+/// touching the destination. One tab-separated line per entry:
+/// `type  src  dst  size`, type being file, dir, symlink or special (size 0
+/// but for files). Entries a copy would skip (-u, -n, --size-only, -x,
+/// --no-empty-dirs, --max-depth) are left out.
+pub fn list(source: &Path, target: &Path, opts: &CopyOptions) -> CpResult<()> {
+    let follow_root = util::should_follow_symlink(source, opts.dereference, true);
+    let root_meta = util::get_metadata(source, follow_root).map_err(|e| CpError::Stat {
+        path: source.to_path_buf(),
+        source: e,
+    })?;
+
+    let mut entries = Vec::new();
+    if root_meta.is_dir() {
+        walk(source, target, opts, &mut entries);
+    } else if let Some(entry) = entry(source, target, &root_meta, opts) {
+        entries.push(entry);
+    }
+
+    if opts.no_empty_dirs {
+        // Keep the directories that end up holding something
+        let populated: HashSet<PathBuf> = entries
+            .iter()
+            .filter(|e| e.kind != "dir")
+            .flat_map(|e| e.dst.ancestors().skip(1).map(Path::to_path_buf))
+            .collect();
+        entries.retain(|e| e.kind != "dir" || populated.contains(&e.dst));
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for e in &entries {
+        let _ = writeln!(
+            out,
+            "{}\t{}\t{}\t{}",
+            e.kind,
+            e.src.display(),
+            e.dst.display(),
+            e.size
+        );
+    }
+    Ok(())
+}
+
+fn walk(src: &Path, dst: &Path, opts: &CopyOptions, entries: &mut Vec<Entry>) {
+    let follow_links = opts.dereference == Dereference::Always;
+    let src_dev = opts
+        .one_file_system
+        .then(|| util::get_device(src).unwrap_or(0));
+    // An existing destination inside the source is never descended into
+    let dst_root = fs::metadata(dst).ok().map(|m| (m.dev(), m.ino()));
+
+    let mut walk = WalkDir::new(src).follow_links(follow_links);
+    if let Some(depth) = opts.max_depth {
+        walk = walk.max_depth(depth);
+    }
+    let mut walker = walk.into_iter();
+    while let Some(result) = walker.next() {
+        let entry = match result {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!("cp: {}", e);
+                continue;
+            }
+        };
+        let path = entry.path();
+        let meta = match util::get_metadata(path, follow_links || entry.depth() == 0) {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("cp: cannot stat '{}': {}", path.display(), e);
+                continue;
+            }
+        };
+
+        if let Some(dev) = src_dev
+            && meta.dev() != dev
+        {
+            if meta.is_dir() {
+                walker.skip_current_dir();
+            }
+            continue;
+        }
+        if meta.is_dir() && entry.depth() > 0 && dst_root == Some((meta.dev(), meta.ino())) {
+            walker.skip_current_dir();
+            continue;
+        }
+
+        let relative = path.strip_prefix(src).unwrap_or(path);
+        let dest_path = if relative.as_os_str().is_empty() {
+            dst.to_path_buf()
+        } else {
+            dst.join(relative)
+        };
+        if let Some(e) = self::entry(path, &dest_path, &meta, opts) {
+            entries.push(e);
+        }
+    }
+}
+
+/// The line for one source entry, or None if a copy would leave its
+/// destination alone.
+fn entry(src: &Path, dst: &Path, meta: &fs::Metadata, opts: &CopyOptions) -> Option<Entry> {
+    let ft = meta.file_type();
+    if ft.is_socket() {
+        // Not copied either (cp warns about it)
+        return None;
+    }
+    let kind = if ft.is_dir() {
+        "dir"
+    } else if ft.is_symlink() {
+        "symlink"
+    } else if ft.is_file() {
+        "file"
+    } else {
+        "special"
+    };
+
+    if kind != "dir" {
+        let dst_meta = fs::symlink_metadata(dst).ok();
+        let dst_target_meta = dst_meta
+            .as_ref()
+            .filter(|dm| dm.file_type().is_symlink())
+            .and_then(|_| fs::metadata(dst).ok());
+        let compared =
+            copy::update_dest_meta(meta, dst_meta.as_ref(), dst_target_meta.as_ref(), opts);
+        if copy::is_up_to_date(src, dst, meta, compared, opts).unwrap_or(true) {
+            return None;
+        }
+    }
+
+    Some(Entry {
+        kind,
+        src: src.to_path_buf(),
+        dst: dst.to_path_buf(),
+        size: if kind == "file" { meta.len() } else { 0 },
+    })
+}
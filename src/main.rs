@@ -1,28 +1,58 @@
 mod backup;
+mod checkpoint;
 mod cli;
+mod compare;
 mod copy;
+mod dedup;
 mod dir;
 mod engine;
 mod error;
+mod list;
 mod metadata;
 mod options;
 mod progress;
+mod selftest;
+#[cfg(feature = "selinux")]
+mod selinux;
 mod sparse;
+mod stat;
+mod tar_src;
+mod timeout;
 mod util;
+mod verify;
 
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::Ordering;
 
-use clap::Parser;
-
-use crate::cli::Cli;
+use crate::cli::{Cli, InputFormat, ProgressMode, ProgressOutput};
 use crate::error::CpError;
-use crate::options::CopyOptions;
+use crate::options::{BackupMode, CopyOptions};
 
 fn main() {
-    let cli = Cli::parse();
+    let cli = Cli::parse_ordered_from(std::env::args_os());
+    if cli.selftest {
+        process::exit(if selftest::run() { 0 } else { 1 });
+    }
+    if cli.list_options {
+        print!("{}", cli::list_options());
+        process::exit(0);
+    }
     let opts = CopyOptions::from_cli(&cli);
+    if let Some(ref path) = cli.version_file
+        && let Err(e) = write_version_file(path)
+    {
+        util::warn(
+            &opts,
+            format!("cannot write version file '{}': {}", path.display(), e),
+        );
+    }
+    if let Some(ref path) = cli.retry_checkpoint {
+        process::exit(retry_checkpoint(path, cli.retry.unwrap_or(1), &opts));
+    }
 
     let exit_code = run(&cli, &opts);
     let _ = std::io::stdout().flush();
@@ -30,19 +60,59 @@ fn main() {
     process::exit(exit_code);
 }
 
+/// --version-file: version, build time, target triple, git commit and
+/// enabled features as one JSON object, replacing any existing file.
+fn write_version_file(path: &Path) -> std::io::Result<()> {
+    let features: &[&str] = &[
+        #[cfg(feature = "selinux")]
+        "selinux",
+    ];
+    let features = features
+        .iter()
+        .map(|f| format!("\"{}\"", f))
+        .collect::<Vec<_>>()
+        .join(",");
+    std::fs::write(
+        path,
+        format!(
+            "{{\"version\":\"{}\",\"build\":\"{}\",\"target\":\"{}\",\"git\":\"{}\",\"features\":[{}]}}\n",
+            env!("CARGO_PKG_VERSION"),
+            env!("BUILD_TIMESTAMP"),
+            env!("BUILD_TARGET"),
+            env!("BUILD_GIT_HASH"),
+            features
+        ),
+    )
+}
+
 fn run(cli: &Cli, opts: &CopyOptions) -> i32 {
-    // Resolve sources and destination
-    let paths: Vec<PathBuf> = if opts.strip_trailing_slashes {
-        cli.paths
+    // Resolve sources and destination (without copying the list: xargs may
+    // pass tens of thousands of them)
+    let stripped: Vec<PathBuf>;
+    let paths: &[PathBuf] = if opts.strip_trailing_slashes {
+        stripped = cli
+            .paths
             .iter()
             .map(|p| util::strip_trailing_slashes(p))
-            .collect()
+            .collect();
+        &stripped
     } else {
-        cli.paths.clone()
+        &cli.paths
     };
 
+    // --compare: SOURCE against DEST itself, not where a copy would land
+    if let Some(by) = cli.compare {
+        return match paths {
+            [src, dst] if opts.target_directory.is_none() => compare(src, dst, by, opts),
+            _ => {
+                eprintln!("cp: --compare takes exactly one SOURCE and one DEST");
+                1
+            }
+        };
+    }
+
     let (sources, dest) =
-        match util::resolve_target(&paths, &opts.target_directory, opts.no_target_directory) {
+        match util::resolve_target(paths, &opts.target_directory, opts.no_target_directory) {
             Ok(r) => r,
             Err(e) => {
                 eprintln!("cp: {}", e);
@@ -59,24 +129,384 @@ fn run(cli: &Cli, opts: &CopyOptions) -> i32 {
         return 1;
     }
 
+    if cli.estimate {
+        return print_estimate(sources, opts);
+    }
+
+    let mut opts = opts.clone();
+
+    // --link-dest mirrors the destination: a directory target, or the parent
+    // of a single file target
+    if opts.link_dest.is_some() {
+        let root = if dest_is_dir || sources.iter().any(|s| s.is_dir()) {
+            dest.clone()
+        } else {
+            dest.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        opts.link_dest_root = Some(root);
+    }
+
+    for (spec, sink) in [
+        (&cli.stat, &mut opts.stat),
+        (&cli.copy_stat, &mut opts.copy_stat),
+    ] {
+        if let Some(spec) = spec {
+            match stat::StatSink::open(spec) {
+                Ok(s) => *sink = Some(s),
+                Err(e) => {
+                    eprintln!("cp: cannot open '{}' for writing: {}", spec.display(), e);
+                    return 1;
+                }
+            }
+        }
+    }
+
+    if let Some(ProgressOutput::File(ref path)) = cli.progress_output {
+        match progress::ProgressFile::create(path) {
+            Ok(f) => opts.progress_target = progress::ProgressTarget::File(f),
+            Err(e) => {
+                eprintln!("cp: cannot open '{}' for writing: {}", path.display(), e);
+                return 1;
+            }
+        }
+    }
+
+    // The kernel sets ctime on every copy: say so once, not per file
+    if cli
+        .preserve
+        .iter()
+        .flatten()
+        .any(|attr| attr == "timestamps=ctime")
+    {
+        util::warn(
+            &opts,
+            "ctime cannot be preserved; it will reflect the time of the copy operation",
+        );
+    }
+
+    if opts.pre_check_size
+        && let Some(ref limit) = opts.max_total_size
+        && let Err(e) = limit.pre_check(sources, &opts)
+    {
+        eprintln!("cp: {}", e);
+        return 1;
+    }
+
     let mut exit_code = 0;
+    // --error-checkpoint: the sources that failed, to copy again later
+    let mut failed: Vec<checkpoint::Entry> = Vec::new();
+    let mut record = |source: &Path, e: &CpError| {
+        if cli.error_checkpoint.is_some() {
+            let dst = util::build_dest_path(
+                source,
+                &dest,
+                dest_is_dir,
+                opts.parents,
+                opts.strip_prefix.as_deref(),
+            )
+            .unwrap_or_else(|_| dest.clone());
+            failed.push(checkpoint::Entry {
+                src: source.to_path_buf(),
+                dst,
+                error: e.to_string(),
+                retry: 0,
+                flags: cli.copy_flags.clone(),
+            });
+        }
+    };
 
-    for source in &sources {
-        if let Err(e) = copy_source(source, &dest, dest_is_dir, opts) {
+    // Sources landing on the same target as an earlier one are left out
+    let (skipped, collisions) = claim_targets(sources, &dest, dest_is_dir, &opts);
+    // Not recorded for --error-checkpoint: copying again fails the same way
+    for e in collisions {
+        eprintln!("cp: {}", e);
+        exit_code = 1;
+    }
+    let kept: Vec<PathBuf>;
+    let sources = if skipped.is_empty() {
+        sources
+    } else {
+        kept = sources
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !skipped.contains(i))
+            .map(|(_, s)| s.clone())
+            .collect();
+        &kept
+    };
+
+    if sources_independent(sources, dest_is_dir, &opts) {
+        // Reported once all are done, in command-line order
+        for (source, e) in copy_sources_parallel(sources, &dest, &opts) {
             eprintln!("cp: {}", e);
+            record(source, &e);
+            exit_code = 1;
+        }
+    } else {
+        for source in sources {
+            if let Err(e) = copy_source(source, &dest, dest_is_dir, &opts) {
+                eprintln!("cp: {}", e);
+                record(source, &e);
+                exit_code = 1;
+                // --max-total-size: the rest of the sources would only fail too
+                if matches!(e, CpError::MaxSizeExceeded { .. }) {
+                    break;
+                }
+            }
+        }
+    }
+
+    if let Some(ref report) = opts.shared_report {
+        eprintln!("cp: {}", report.summary());
+    }
+
+    for sink in [&opts.stat, &opts.copy_stat].into_iter().flatten() {
+        if let Err(e) = sink.flush() {
+            eprintln!("cp: error writing statistics: {}", e);
             exit_code = 1;
         }
     }
 
+    // --fail-on-skip: errors keep their own status
+    if exit_code == 0
+        && let Some(code) = cli.fail_on_skip
+        && opts
+            .skipped_any
+            .as_ref()
+            .is_some_and(|s| s.load(Ordering::Relaxed))
+    {
+        exit_code = code.into();
+    }
+
+    if let Some(ref path) = cli.error_checkpoint {
+        match checkpoint::write(path, &failed) {
+            Ok(()) if !failed.is_empty() => exit_code = 2,
+            Ok(()) => {}
+            Err(e) => {
+                eprintln!("cp: cannot write '{}': {}", path.display(), e);
+                exit_code = 1;
+            }
+        }
+    }
+
     exit_code
 }
 
+/// --estimate: total files and bytes of `sources` on stdout. Nothing is
+/// written, not even the destination directory.
+fn print_estimate(sources: &[PathBuf], opts: &CopyOptions) -> i32 {
+    let mut total = dir::Estimate::default();
+    let mut exit_code = 0;
+    for source in sources {
+        match dir::estimate(source, opts) {
+            Ok(est) => {
+                total.files += est.files;
+                total.bytes += est.bytes;
+            }
+            Err(e) => {
+                eprintln!("cp: {}", e);
+                exit_code = 1;
+            }
+        }
+    }
+    println!(
+        "{} files, {} bytes ({})",
+        total.files,
+        total.bytes,
+        util::human_size(total.bytes)
+    );
+    exit_code
+}
+
+/// --compare: print each difference between `src` and `dst` on stdout.
+/// Exits as diff(1): 0 when they match, 1 when they differ, 2 when
+/// something could not be read.
+fn compare(src: &Path, dst: &Path, by: cli::CompareBy, opts: &CopyOptions) -> i32 {
+    let diffs = compare::compare_trees(src, dst, by, opts);
+    let mut exit_code = 0;
+    for d in &diffs {
+        if let compare::Difference::Error(..) = d {
+            eprintln!("cp: {}", d);
+            exit_code = 2;
+        } else {
+            println!("{}", d);
+            exit_code = exit_code.max(1);
+        }
+    }
+    exit_code
+}
+
+/// --retry-checkpoint: copy each failed operation of the checkpoint at
+/// `path` again, up to `attempts` times, and rewrite it with those still
+/// failing. Each is copied with the options recorded with it; `opts` only
+/// serves entries from checkpoints that have none. Exits like a run with
+/// --error-checkpoint: 2 when any remain.
+fn retry_checkpoint(path: &Path, attempts: u32, opts: &CopyOptions) -> i32 {
+    let mut entries = match checkpoint::read(path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("cp: cannot read '{}': {}", path.display(), e);
+            return 1;
+        }
+    };
+
+    entries.retain_mut(|entry| {
+        let recorded;
+        let opts = if entry.flags.is_empty() {
+            opts
+        } else {
+            let mut args: Vec<OsString> = vec!["cp".into()];
+            args.extend(entry.flags.iter().map(OsString::from));
+            args.extend([entry.src.clone().into(), entry.dst.clone().into()]);
+            recorded = CopyOptions::from_cli(&Cli::parse_ordered_from(args));
+            &recorded
+        };
+        for _ in 0..attempts.max(1) {
+            // The entry names the destination itself, not a directory to copy into
+            match copy_source(&entry.src, &entry.dst, false, opts) {
+                Ok(()) => return false,
+                Err(e) => {
+                    entry.error = e.to_string();
+                    entry.retry += 1;
+                }
+            }
+        }
+        eprintln!("cp: {}", entry.error);
+        true
+    });
+
+    if let Err(e) = checkpoint::write(path, &entries) {
+        eprintln!("cp: cannot write '{}': {}", path.display(), e);
+        return 1;
+    }
+    if entries.is_empty() { 0 } else { 2 }
+}
+
+/// Find the sources that would land where an earlier one did in directory
+/// `dest` (`cp x/f y/f dir`), by index. A source given twice is left out
+/// with a warning; two directories are merged, as GNU does; anything else
+/// would clobber a file just copied, an error. Returns the indexes to leave
+/// out and the errors among them.
+fn claim_targets(
+    sources: &[PathBuf],
+    dest: &Path,
+    dest_is_dir: bool,
+    opts: &CopyOptions,
+) -> (HashSet<usize>, Vec<CpError>) {
+    let mut skipped = HashSet::new();
+    let mut errors = Vec::new();
+    if sources.len() < 2 || !dest_is_dir || opts.from_format.is_some() {
+        return (skipped, errors);
+    }
+
+    let is_dir = |s: &Path| {
+        let follow = util::should_follow_symlink(s, opts.dereference, true);
+        util::get_metadata(s, follow).is_ok_and(|m| m.is_dir())
+    };
+    let mut claimed: HashMap<PathBuf, usize> = HashMap::with_capacity(sources.len());
+    for (i, source) in sources.iter().enumerate() {
+        let Ok(target) = util::build_dest_path(
+            source,
+            dest,
+            dest_is_dir,
+            opts.parents,
+            opts.strip_prefix.as_deref(),
+        ) else {
+            continue;
+        };
+        let Some(&first) = claimed.get(&target) else {
+            claimed.insert(target, i);
+            continue;
+        };
+        let first = &sources[first];
+        if util::is_same_file(first, source) {
+            let kind = if is_dir(source) { "directory" } else { "file" };
+            util::warn(
+                opts,
+                format!(
+                    "source {} '{}' specified more than once",
+                    kind,
+                    source.display()
+                ),
+            );
+            skipped.insert(i);
+        } else if !(is_dir(first) && is_dir(source)) {
+            skipped.insert(i);
+            errors.push(CpError::WillNotOverwrite {
+                src: source.clone(),
+                dst: target,
+            });
+        }
+    }
+    (skipped, errors)
+}
+
+/// Top-level sources from which copying them concurrently pays off.
+const PARALLEL_SOURCES: usize = 64;
+
+/// Whether the sources can be copied concurrently: many of them, into a
+/// directory under distinct names, with nothing that needs them one after
+/// the other (prompts, backups, verbose lines, progress bars, --parents,
+/// --max-total-size).
+fn sources_independent(sources: &[PathBuf], dest_is_dir: bool, opts: &CopyOptions) -> bool {
+    if sources.len() < PARALLEL_SOURCES
+        || !dest_is_dir
+        || !opts.can_parallelize()
+        || opts.verbose
+        || opts.progress.is_some()
+        || opts.parents
+        || opts.strip_prefix.is_some()
+        || opts.list
+        || opts.from_format.is_some()
+        || opts.max_total_size.is_some()
+    {
+        return false;
+    }
+    let mut names = HashSet::with_capacity(sources.len());
+    sources
+        .iter()
+        .all(|s| s.file_name().is_some_and(|name| names.insert(name)))
+}
+
+/// Copy every source into directory `dest` on a Rayon pool (sized by --jobs
+/// when given). Returns the failed sources with their errors, in source order.
+fn copy_sources_parallel<'a>(
+    sources: &'a [PathBuf],
+    dest: &Path,
+    opts: &CopyOptions,
+) -> Vec<(&'a PathBuf, CpError)> {
+    use rayon::prelude::*;
+
+    let copy_all = || {
+        sources
+            .par_iter()
+            .filter_map(|source| {
+                copy_source(source, dest, true, opts)
+                    .err()
+                    .map(|e| (source, e))
+            })
+            .collect()
+    };
+    match opts
+        .jobs
+        .and_then(|n| rayon::ThreadPoolBuilder::new().num_threads(n).build().ok())
+    {
+        Some(pool) => pool.install(copy_all),
+        None => copy_all(),
+    }
+}
+
 fn copy_source(
     source: &Path,
     dest: &Path,
     dest_is_dir: bool,
     opts: &CopyOptions,
 ) -> Result<(), CpError> {
+    // --from-format=tar: the source is an archive expanded into the destination
+    if opts.from_format == Some(InputFormat::Tar) {
+        return tar_src::extract(source, dest, opts);
+    }
+
     // Check source exists
     let follow = util::should_follow_symlink(source, opts.dereference, true);
     let src_meta = util::get_metadata(source, follow).map_err(|e| CpError::Stat {
@@ -92,7 +522,28 @@ fn copy_source(
         });
     }
 
-    let target = util::build_dest_path(source, dest, dest_is_dir, opts.parents);
+    let target = util::build_dest_path(
+        source,
+        dest,
+        dest_is_dir,
+        opts.parents,
+        opts.strip_prefix.as_deref(),
+    )?;
+
+    if opts.list {
+        return list::list(source, &target, opts);
+    }
+
+    // --strip-prefix keeps intermediate components, which may not exist
+    // yet; -D asks for the target's to be created
+    if (opts.make_parents || (opts.strip_prefix.is_some() && dest_is_dir))
+        && let Some(parent) = target.parent()
+    {
+        std::fs::create_dir_all(parent).map_err(|e| CpError::CreateDir {
+            path: parent.to_path_buf(),
+            source: e,
+        })?;
+    }
 
     if is_dir {
         // Check we're not copying into self
@@ -114,12 +565,50 @@ fn copy_source(
             });
         }
 
-        dir::copy_directory(source, &target, opts)?;
+        // In tree order a directory's line comes before its contents
+        if opts.verbose_tree() {
+            println!("'{}' -> '{}'", source.display(), target.display());
+        }
+
+        if opts.atomic_dir && target.is_dir() {
+            dir::copy_directory_atomic(source, &target, opts)?;
+        } else {
+            dir::copy_directory(source, &target, opts)?;
+        }
+
+        if opts.hardlink_ref_count_check && opts.preserve_links {
+            let mismatches = verify::check_hardlink_counts(source, &target, opts);
+            for m in &mismatches {
+                util::warn(
+                    opts,
+                    format!(
+                        "hard link group at inode {} has {} links in source but {} in destination",
+                        m.ino, m.src_links, m.dst_links
+                    ),
+                );
+            }
+            if !mismatches.is_empty() {
+                return Err(CpError::HardlinkCountMismatch {
+                    src: source.to_path_buf(),
+                    dst: target,
+                    groups: mismatches.len(),
+                });
+            }
+        }
 
-        if opts.verbose {
+        if opts.verbose && !opts.verbose_tree() {
             println!("'{}' -> '{}'", source.display(), target.display());
         }
     } else {
+        // `cp --parents a/f .` lands on a/f itself: report it before
+        // creating any parent (copy_single would, but only afterwards)
+        if opts.parents && opts.backup == BackupMode::None && util::is_same_file(source, &target) {
+            return Err(CpError::SameFile {
+                src: source.to_path_buf(),
+                dst: target,
+            });
+        }
+
         // Ensure parent directory exists for --parents
         if opts.parents
             && let Some(parent) = target.parent()
@@ -134,15 +623,23 @@ fn copy_source(
             src_meta.len(),
             &source.display().to_string(),
             opts.progress,
+            &opts.progress_target,
         );
         copy::copy_single(source, &target, opts, true, &pb)?;
-        pb.finish_and_clear();
+        if opts.progress == Some(ProgressMode::Plain) {
+            // Leave the final 100% line in the log
+            pb.finish();
+        } else {
+            pb.finish_and_clear();
+        }
 
         // Preserve metadata of each intermediate source directory (after file copy,
         // so directory mtime isn't overwritten by file creation)
         if opts.parents {
-            let need_meta =
-                opts.preserve_mode || opts.preserve_ownership || opts.preserve_timestamps;
+            let need_meta = opts.preserve_mode
+                || opts.preserve_ownership
+                || opts.preserve_timestamps
+                || opts.chown.is_some();
             if need_meta {
                 if let Some(src_parent) = source.parent() {
                     let src_stripped = src_parent.strip_prefix("/").unwrap_or(src_parent);
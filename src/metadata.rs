@@ -1,12 +1,39 @@
+use std::ffi::{CStr, CString};
 use std::fs;
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::cli::Owner;
 use crate::error::{CpError, CpResult};
 use crate::options::CopyOptions;
+use crate::util;
 
-const ENOTSUP: i32 = 95; // linux ENOTSUP
+/// FS_IOC_GETFLAGS / FS_IOC_SETFLAGS (linux/fs.h; the kernel moves an int).
+const FS_IOC_GETFLAGS: nix::libc::c_ulong = 0x80086601;
+const FS_IOC_SETFLAGS: nix::libc::c_ulong = 0x40086602;
+
+/// The inode flags chattr(1) sets, which --preserve=flags copies: secure
+/// deletion, undelete, compression, sync, immutable, append-only, nodump,
+/// noatime, data journaling, no tail-merging, dirsync, top dir, no CoW and
+/// project inheritance. The others describe the on-disk layout.
+const CHATTR_FLAGS: nix::libc::c_int = 0x1
+    | 0x2
+    | 0x4
+    | 0x8
+    | 0x10
+    | 0x20
+    | 0x40
+    | 0x80
+    | 0x4000
+    | 0x8000
+    | 0x10000
+    | 0x20000
+    | 0x80_0000
+    | 0x2000_0000;
 
 /// Cached: does the filesystem support xattr? (reset on ENOTSUP)
 static XATTR_SUPPORTED: AtomicBool = AtomicBool::new(true);
@@ -14,8 +41,59 @@ static XATTR_SUPPORTED: AtomicBool = AtomicBool::new(true);
 /// Cached: does the filesystem support ACLs?
 static ACL_SUPPORTED: AtomicBool = AtomicBool::new(true);
 
+/// Same, for symlinks: some filesystems reject xattrs/ACLs on links only,
+/// which must not disable them for regular files
+static XATTR_ON_SYMLINK_SUPPORTED: AtomicBool = AtomicBool::new(true);
+static ACL_ON_SYMLINK_SUPPORTED: AtomicBool = AtomicBool::new(true);
+
+fn xattr_flag(is_symlink: bool) -> &'static AtomicBool {
+    if is_symlink {
+        &XATTR_ON_SYMLINK_SUPPORTED
+    } else {
+        &XATTR_SUPPORTED
+    }
+}
+
+fn acl_flag(is_symlink: bool) -> &'static AtomicBool {
+    if is_symlink {
+        &ACL_ON_SYMLINK_SUPPORTED
+    } else {
+        &ACL_SUPPORTED
+    }
+}
+
+/// Whether xattrs are still attempted for this kind of file.
+pub fn xattr_supported(is_symlink: bool) -> bool {
+    xattr_flag(is_symlink).load(Ordering::SeqCst)
+}
+
+/// Whether ACLs are still attempted for this kind of file.
+pub fn acl_supported(is_symlink: bool) -> bool {
+    acl_flag(is_symlink).load(Ordering::SeqCst)
+}
+
+/// Record an xattr failure: ENOTSUP disables xattrs for this kind of file
+/// from now on. Returns whether the error was such an "unsupported" one.
+pub fn note_xattr_error(err: &std::io::Error, is_symlink: bool) -> bool {
+    let unsupported = err.raw_os_error() == Some(nix::libc::ENOTSUP);
+    if unsupported {
+        xattr_flag(is_symlink).store(false, Ordering::SeqCst);
+    }
+    unsupported
+}
+
+/// Record an ACL failure (by message, as posix_acl reports them): "not
+/// supported" disables ACLs for this kind of file from now on.
+pub fn note_acl_error(msg: &str, is_symlink: bool) -> bool {
+    let unsupported = msg.contains("not supported") || msg.contains("No data available");
+    if unsupported {
+        acl_flag(is_symlink).store(false, Ordering::SeqCst);
+    }
+    unsupported
+}
+
 /// Preserve metadata from source to destination.
-/// Order matters: xattr -> chown -> chmod -> utimensat -> ACL
+/// Order matters: chown -> chmod -> xattr -> context -> ACL -> utimensat
 pub fn preserve_metadata(
     src: &Path,
     dst: &Path,
@@ -23,29 +101,43 @@ pub fn preserve_metadata(
     opts: &CopyOptions,
     is_symlink: bool,
 ) -> CpResult<()> {
-    // 1. Extended attributes (before chown which may strip them)
-    if opts.preserve_xattr && XATTR_SUPPORTED.load(Ordering::Relaxed) {
-        preserve_xattr(src, dst)?;
-    }
+    preserve_metadata_at(src, dst, None, src_meta, opts, is_symlink)
+}
 
-    // 2. Ownership (before chmod, since chown can clear setuid/setgid)
+/// `preserve_metadata` for a destination also known as `dst_at`, its
+/// directory fd and name: ownership is then changed relative to that fd.
+pub fn preserve_metadata_at(
+    src: &Path,
+    dst: &Path,
+    dst_at: Option<(RawFd, &CStr)>,
+    src_meta: &fs::Metadata,
+    opts: &CopyOptions,
+    is_symlink: bool,
+) -> CpResult<()> {
+    // 1. Ownership (before chmod, since chown can clear setuid/setgid)
     // Try chown even as non-root — preserve_ownership tolerates EPERM
-    if opts.preserve_ownership {
-        preserve_ownership(dst, src_meta, is_symlink)?;
+    if let Some(owner) = opts.chown {
+        set_owner(dst, dst_at, owner, is_symlink)?;
+    } else if opts.preserve_ownership {
+        preserve_ownership(dst, dst_at, src_meta.uid(), src_meta.gid(), is_symlink)?;
     }
 
-    // 3. Permissions
+    // 2. Permissions
     if opts.preserve_mode && !is_symlink {
-        preserve_mode(dst, src_meta)?;
+        preserve_mode(dst, src_meta.mode())?;
     }
 
-    // 4. Timestamps
-    if opts.preserve_timestamps {
-        preserve_timestamps(dst, src_meta, is_symlink)?;
+    // 3. Extended attributes (after chown, which may strip security.capability)
+    if opts.preserve_xattr && xattr_supported(is_symlink) {
+        preserve_xattr(src, dst, is_symlink, opts)?;
     }
 
-    // 5. ACL (includes POSIX permission bits — may override mode)
-    if opts.preserve_acl && ACL_SUPPORTED.load(Ordering::Relaxed) {
+    // SELinux context (explicit -Z/--context is applied later and wins)
+    #[cfg(feature = "selinux")]
+    crate::selinux::preserve_context(src, dst, opts)?;
+
+    // 4. ACL (includes POSIX permission bits — may override mode)
+    if opts.preserve_acl && acl_supported(is_symlink) {
         // ACL entries include the POSIX permission bits (owner/group/other).
         // If mode is NOT being preserved, save the current mode and restore after ACL.
         let saved_mode = if !opts.preserve_mode && !is_symlink {
@@ -54,105 +146,299 @@ pub fn preserve_metadata(
             None
         };
 
-        preserve_acl(src, dst)?;
+        preserve_acl(src, dst, is_symlink)?;
 
         if let Some(mode) = saved_mode {
             fs::set_permissions(dst, fs::Permissions::from_mode(mode)).ok();
         }
     }
 
+    // 5. Timestamps, so none of the steps above can disturb them
+    if opts.preserve_timestamps {
+        let atime = filetime::FileTime::from_last_access_time(src_meta);
+        let mtime = filetime::FileTime::from_last_modification_time(src_meta);
+        preserve_timestamps(dst, atime, mtime, is_symlink)?;
+    }
+
+    // 6. Inode flags last of all: immutable would refuse everything above.
+    // Only files and directories: opening a device to ask has side effects
+    if opts.preserve_flags && (src_meta.is_file() || src_meta.is_dir()) {
+        preserve_flags(src, dst, opts);
+    }
+
+    Ok(())
+}
+
+/// --preserve=flags for paths; failures are warnings (see `preserve_flags_fd`).
+pub fn preserve_flags(src: &Path, dst: &Path, opts: &CopyOptions) {
+    let open = |p: &Path| {
+        fs::File::options()
+            .read(true)
+            .custom_flags(nix::libc::O_NONBLOCK | nix::libc::O_NOFOLLOW)
+            .open(p)
+    };
+    let result = open(src)
+        .and_then(|s| open(dst).map(|d| (s, d)))
+        .and_then(|(s, d)| preserve_flags_fd(s.as_raw_fd(), d.as_raw_fd()));
+    if let Err(e) = result {
+        warn_flags(dst, &e, opts);
+    }
+}
+
+/// Copy the chattr flags of `src_fd` onto `dst_fd`. A filesystem without
+/// inode flags on either side has nothing to copy. Setting immutable or
+/// append-only takes CAP_LINUX_IMMUTABLE.
+pub fn preserve_flags_fd(src_fd: RawFd, dst_fd: RawFd) -> io::Result<()> {
+    let get = |fd: RawFd| {
+        let mut flags: nix::libc::c_int = 0;
+        if unsafe { nix::libc::ioctl(fd, FS_IOC_GETFLAGS, &mut flags) } == 0 {
+            Ok(flags)
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    };
+    let unsupported = |e: &io::Error| {
+        matches!(
+            e.raw_os_error(),
+            Some(nix::libc::ENOTTY | nix::libc::EOPNOTSUPP | nix::libc::EINVAL)
+        )
+    };
+
+    let wanted = match get(src_fd) {
+        Ok(flags) => flags & CHATTR_FLAGS,
+        Err(e) if unsupported(&e) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let current = match get(dst_fd) {
+        Ok(flags) => flags,
+        Err(e) if unsupported(&e) && wanted == 0 => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let flags = (current & !CHATTR_FLAGS) | wanted;
+    if flags != current && unsafe { nix::libc::ioctl(dst_fd, FS_IOC_SETFLAGS, &flags) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Inode flags are copied on a best-effort basis: a failure is a warning.
+pub fn warn_flags(dst: &Path, err: &io::Error, opts: &CopyOptions) {
+    util::warn(
+        opts,
+        format_args!("cannot preserve flags of '{}': {}", dst.display(), err),
+    );
+}
+
+/// Ownership, permission bits and timestamps for a destination whose source
+/// is not a file on disk (e.g. a tar entry).
+pub struct Attrs {
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+    pub atime: filetime::FileTime,
+    pub mtime: filetime::FileTime,
+}
+
+/// Apply `attrs` to `dst` as far as the preserve options ask, in the same
+/// order as `preserve_metadata`: chown -> chmod -> utimensat.
+pub fn apply_attrs(
+    dst: &Path,
+    attrs: &Attrs,
+    opts: &CopyOptions,
+    is_symlink: bool,
+) -> CpResult<()> {
+    if let Some(owner) = opts.chown {
+        set_owner(dst, None, owner, is_symlink)?;
+    } else if opts.preserve_ownership {
+        preserve_ownership(dst, None, attrs.uid, attrs.gid, is_symlink)?;
+    }
+    if opts.preserve_mode && !is_symlink {
+        preserve_mode(dst, attrs.mode)?;
+    }
+    if opts.preserve_timestamps {
+        preserve_timestamps(dst, attrs.atime, attrs.mtime, is_symlink)?;
+    }
+    Ok(())
+}
+
+/// `apply_attrs` through `fd`, an open descriptor for the directory or file
+/// `dst` (used in messages): nothing is looked up by path again, so a
+/// symlink swapped in for `dst` cannot redirect the changes.
+pub fn apply_attrs_fd(dst: &Path, fd: RawFd, attrs: &Attrs, opts: &CopyOptions) -> CpResult<()> {
+    if let Some(owner) = opts.chown {
+        set_owner(dst, Some((fd, c"")), owner, false)?;
+    } else if opts.preserve_ownership {
+        preserve_ownership(dst, Some((fd, c"")), attrs.uid, attrs.gid, false)?;
+    }
+    if opts.preserve_mode && unsafe { nix::libc::fchmod(fd, attrs.mode as _) } != 0 {
+        return Err(CpError::Chmod {
+            path: dst.to_path_buf(),
+            source: io::Error::last_os_error(),
+        });
+    }
+    if opts.preserve_timestamps {
+        let ts = |t: filetime::FileTime| nix::libc::timespec {
+            tv_sec: t.unix_seconds(),
+            tv_nsec: t.nanoseconds() as _,
+        };
+        set_times_fd(fd, ts(attrs.atime), ts(attrs.mtime)).map_err(|e| CpError::Timestamps {
+            path: dst.to_path_buf(),
+            source: e,
+        })?;
+    }
     Ok(())
 }
 
 /// Public wrapper for xattr preservation (used by dir.rs fast path).
-pub fn preserve_xattr_pub(src: &Path, dst: &Path) -> CpResult<()> {
-    if !XATTR_SUPPORTED.load(Ordering::Relaxed) {
+pub fn preserve_xattr_pub(
+    src: &Path,
+    dst: &Path,
+    is_symlink: bool,
+    opts: &CopyOptions,
+) -> CpResult<()> {
+    if !xattr_supported(is_symlink) {
         return Ok(());
     }
-    preserve_xattr(src, dst)
-}
-
-fn preserve_xattr(src: &Path, dst: &Path) -> CpResult<()> {
-    match xattr::list(src) {
-        Ok(attrs) => {
-            for attr in attrs {
-                match xattr::get(src, &attr) {
-                    Ok(Some(value)) => {
-                        if let Err(e) = xattr::set(dst, &attr, &value) {
-                            if e.raw_os_error() == Some(ENOTSUP) {
-                                XATTR_SUPPORTED.store(false, Ordering::Relaxed);
-                                return Ok(());
-                            }
-                            // Non-fatal for permission denied
-                            if e.kind() != std::io::ErrorKind::PermissionDenied {
-                                return Err(CpError::Xattr {
-                                    path: dst.to_path_buf(),
-                                    source: e,
-                                });
-                            }
-                        }
-                    }
-                    Ok(None) => {}
-                    Err(e) => {
-                        if e.kind() != std::io::ErrorKind::PermissionDenied {
-                            return Err(CpError::Xattr {
-                                path: src.to_path_buf(),
-                                source: e,
-                            });
-                        }
-                    }
-                }
-            }
-        }
+    preserve_xattr(src, dst, is_symlink, opts)
+}
+
+/// Errors that only cost one attribute: a `security.ima` a non-root user
+/// may not write, a namespace the destination lacks, a value too large.
+/// Such attributes are skipped (and counted) unless --strict-xattr.
+pub fn xattr_skippable(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(nix::libc::EPERM | nix::libc::EACCES | nix::libc::ENOTSUP | nix::libc::ERANGE)
+    )
+}
+
+/// The one warning for all the xattrs of `dst` that could not be copied.
+pub fn warn_xattrs_skipped(dst: &Path, skipped: usize, opts: &CopyOptions) {
+    if skipped > 0 {
+        util::warn(
+            opts,
+            format_args!(
+                "could not preserve {} xattr{} on '{}'",
+                skipped,
+                if skipped == 1 { "" } else { "s" },
+                dst.display()
+            ),
+        );
+    }
+}
+
+fn preserve_xattr(src: &Path, dst: &Path, is_symlink: bool, opts: &CopyOptions) -> CpResult<()> {
+    let strict = opts.strict_xattr;
+    let attrs = match xattr::list(src) {
+        Ok(attrs) => attrs,
         Err(e) => {
-            if e.raw_os_error() == Some(ENOTSUP) {
-                XATTR_SUPPORTED.store(false, Ordering::Relaxed);
+            if note_xattr_error(&e, is_symlink) || (!strict && xattr_skippable(&e)) {
                 return Ok(());
             }
-            if e.kind() != std::io::ErrorKind::PermissionDenied {
-                return Err(CpError::Xattr {
-                    path: src.to_path_buf(),
-                    source: e,
-                });
-            }
+            return Err(CpError::Xattr {
+                path: src.to_path_buf(),
+                source: e,
+            });
+        }
+    };
+
+    let mut skipped = 0;
+    for attr in attrs {
+        let (path, result) = match xattr::get(src, &attr) {
+            Ok(Some(value)) => (dst, xattr::set(dst, &attr, &value)),
+            Ok(None) => continue,
+            Err(e) => (src, Err(e)),
+        };
+        let Err(e) = result else {
+            continue;
+        };
+        if note_xattr_error(&e, is_symlink) {
+            warn_xattrs_skipped(dst, skipped, opts);
+            return Ok(());
+        }
+        if strict || !xattr_skippable(&e) {
+            warn_xattrs_skipped(dst, skipped, opts);
+            return Err(CpError::Xattr {
+                path: path.to_path_buf(),
+                source: e,
+            });
         }
+        skipped += 1;
     }
+    warn_xattrs_skipped(dst, skipped, opts);
     Ok(())
 }
 
-fn preserve_ownership(dst: &Path, meta: &fs::Metadata, is_symlink: bool) -> CpResult<()> {
-    use std::ffi::CString;
-    use std::os::unix::ffi::OsStrExt;
-
-    let uid = meta.uid();
-    let gid = meta.gid();
+/// Give `dst` the owner of its source. Without the privilege to (EPERM)
+/// the copy keeps the caller's ids, as GNU does.
+fn preserve_ownership(
+    dst: &Path,
+    dst_at: Option<(RawFd, &CStr)>,
+    uid: u32,
+    gid: u32,
+    is_symlink: bool,
+) -> CpResult<()> {
+    match chown_dst(dst, dst_at, uid, gid, is_symlink) {
+        Err(err) if err != nix::Error::EPERM => Err(CpError::Chown {
+            path: dst.to_path_buf(),
+            source: err,
+        }),
+        _ => Ok(()),
+    }
+}
 
-    let c_path = CString::new(dst.as_os_str().as_bytes()).map_err(|_| CpError::Chown {
+/// --chown: the owner was asked for explicitly, so failing to set it,
+/// EPERM included, fails the copy.
+fn set_owner(
+    dst: &Path,
+    dst_at: Option<(RawFd, &CStr)>,
+    owner: Owner,
+    is_symlink: bool,
+) -> CpResult<()> {
+    // -1 leaves that id unchanged
+    let uid = owner.uid.unwrap_or(u32::MAX);
+    let gid = owner.gid.unwrap_or(u32::MAX);
+    chown_dst(dst, dst_at, uid, gid, is_symlink).map_err(|source| CpError::SetOwner {
         path: dst.to_path_buf(),
-        source: nix::Error::EINVAL,
-    })?;
+        source,
+    })
+}
 
-    let ret = if is_symlink {
-        unsafe { nix::libc::lchown(c_path.as_ptr(), uid, gid) }
+/// fchownat `dst`, not following it when it is a symlink. With `dst_at`
+/// (directory fd and name) only the last component is looked up again;
+/// otherwise the whole path is, and a directory swapped in since `dst` was
+/// created would redirect the chown: callers holding the directory fd
+/// should pass it. An empty name means the fd itself (fchown).
+fn chown_dst(
+    dst: &Path,
+    dst_at: Option<(RawFd, &CStr)>,
+    uid: u32,
+    gid: u32,
+    is_symlink: bool,
+) -> nix::Result<()> {
+    let flags = if is_symlink {
+        nix::libc::AT_SYMLINK_NOFOLLOW
     } else {
-        unsafe { nix::libc::chown(c_path.as_ptr(), uid, gid) }
+        0
     };
-
-    if ret != 0 {
-        let err = nix::Error::last();
-        if err != nix::Error::EPERM {
-            return Err(CpError::Chown {
-                path: dst.to_path_buf(),
-                source: err,
-            });
+    let ret = match dst_at {
+        Some((fd, name)) if name.is_empty() => unsafe { nix::libc::fchown(fd, uid, gid) },
+        Some((dir_fd, name)) => unsafe {
+            nix::libc::fchownat(dir_fd, name.as_ptr(), uid, gid, flags)
+        },
+        None => {
+            let c_path =
+                CString::new(dst.as_os_str().as_bytes()).map_err(|_| nix::Error::EINVAL)?;
+            unsafe { nix::libc::fchownat(nix::libc::AT_FDCWD, c_path.as_ptr(), uid, gid, flags) }
         }
+    };
+    if ret != 0 {
+        return Err(nix::Error::last());
     }
-
     Ok(())
 }
 
-fn preserve_mode(dst: &Path, meta: &fs::Metadata) -> CpResult<()> {
-    let mode = meta.mode();
+fn preserve_mode(dst: &Path, mode: u32) -> CpResult<()> {
     fs::set_permissions(dst, fs::Permissions::from_mode(mode)).map_err(|e| CpError::Chmod {
         path: dst.to_path_buf(),
         source: e,
@@ -160,39 +446,117 @@ fn preserve_mode(dst: &Path, meta: &fs::Metadata) -> CpResult<()> {
     Ok(())
 }
 
-fn preserve_timestamps(dst: &Path, meta: &fs::Metadata, is_symlink: bool) -> CpResult<()> {
-    let atime = filetime::FileTime::from_last_access_time(meta);
-    let mtime = filetime::FileTime::from_last_modification_time(meta);
-
-    let result = if is_symlink {
-        filetime::set_symlink_file_times(dst, atime, mtime)
-    } else {
-        filetime::set_file_times(dst, atime, mtime)
+fn preserve_timestamps(
+    dst: &Path,
+    atime: filetime::FileTime,
+    mtime: filetime::FileTime,
+    is_symlink: bool,
+) -> CpResult<()> {
+    let ts = |t: filetime::FileTime| nix::libc::timespec {
+        tv_sec: t.unix_seconds(),
+        tv_nsec: t.nanoseconds() as _,
     };
-
-    result.map_err(|e| CpError::Timestamps {
+    set_times_path(dst, ts(atime), ts(mtime), is_symlink).map_err(|e| CpError::Timestamps {
         path: dst.to_path_buf(),
         source: e,
-    })?;
+    })
+}
 
-    Ok(())
+/// Set atime/mtime of `path` with utimensat (nanoseconds), falling back to
+/// utimes/lutimes (microseconds) on kernels without it (ENOSYS).
+pub fn set_times_path(
+    path: &Path,
+    atime: nix::libc::timespec,
+    mtime: nix::libc::timespec,
+    is_symlink: bool,
+) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from_raw_os_error(nix::libc::EINVAL))?;
+    set_times_at(nix::libc::AT_FDCWD, &c_path, atime, mtime, is_symlink)
+}
+
+/// `set_times_path` for `name` relative to directory fd `dir_fd`. The
+/// fallback has no *at form: away from the working directory, ENOSYS is
+/// returned as is.
+pub fn set_times_at(
+    dir_fd: RawFd,
+    name: &CStr,
+    atime: nix::libc::timespec,
+    mtime: nix::libc::timespec,
+    is_symlink: bool,
+) -> io::Result<()> {
+    let flags = if is_symlink {
+        nix::libc::AT_SYMLINK_NOFOLLOW
+    } else {
+        0
+    };
+    let times = [atime, mtime];
+    if unsafe { nix::libc::utimensat(dir_fd, name.as_ptr(), times.as_ptr(), flags) } == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(nix::libc::ENOSYS) || dir_fd != nix::libc::AT_FDCWD {
+        return Err(err);
+    }
+
+    let tv = [to_timeval(atime), to_timeval(mtime)];
+    let ret = if is_symlink {
+        unsafe { nix::libc::lutimes(name.as_ptr(), tv.as_ptr()) }
+    } else {
+        unsafe { nix::libc::utimes(name.as_ptr(), tv.as_ptr()) }
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
 }
 
-/// Public wrapper for ACL preservation (used by dir.rs fast path).
-pub fn preserve_acl_pub(src: &Path, dst: &Path) -> CpResult<()> {
-    if !ACL_SUPPORTED.load(Ordering::Relaxed) {
+/// Set atime/mtime through an open fd with futimens (nanoseconds), falling
+/// back to `futimes_fallback` on kernels without it (ENOSYS).
+pub fn set_times_fd(
+    fd: RawFd,
+    atime: nix::libc::timespec,
+    mtime: nix::libc::timespec,
+) -> io::Result<()> {
+    let times = [atime, mtime];
+    if unsafe { nix::libc::futimens(fd, times.as_ptr()) } == 0 {
         return Ok(());
     }
-    preserve_acl(src, dst)
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() != Some(nix::libc::ENOSYS) {
+        return Err(err);
+    }
+    futimes_fallback(fd, atime, mtime)
+}
+
+/// futimes: microsecond timestamps, for kernels predating futimens.
+pub fn futimes_fallback(
+    fd: RawFd,
+    atime: nix::libc::timespec,
+    mtime: nix::libc::timespec,
+) -> io::Result<()> {
+    let tv = [to_timeval(atime), to_timeval(mtime)];
+    if unsafe { nix::libc::futimes(fd, tv.as_ptr()) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+fn to_timeval(ts: nix::libc::timespec) -> nix::libc::timeval {
+    nix::libc::timeval {
+        tv_sec: ts.tv_sec,
+        tv_usec: (ts.tv_nsec / 1000) as _,
+    }
 }
 
-fn preserve_acl(src: &Path, dst: &Path) -> CpResult<()> {
+fn preserve_acl(src: &Path, dst: &Path, is_symlink: bool) -> CpResult<()> {
     match posix_acl::PosixACL::read_acl(src) {
         Ok(mut acl) => {
             if let Err(e) = acl.write_acl(dst) {
                 let msg = e.to_string();
-                if msg.contains("not supported") || msg.contains("Operation not supported") {
-                    ACL_SUPPORTED.store(false, Ordering::Relaxed);
+                if note_acl_error(&msg, is_symlink) {
                     return Ok(());
                 }
                 return Err(CpError::Acl {
@@ -203,8 +567,7 @@ fn preserve_acl(src: &Path, dst: &Path) -> CpResult<()> {
         }
         Err(e) => {
             let msg = e.to_string();
-            if msg.contains("not supported") || msg.contains("No data available") {
-                ACL_SUPPORTED.store(false, Ordering::Relaxed);
+            if note_acl_error(&msg, is_symlink) {
                 return Ok(());
             }
             return Err(CpError::Acl {
@@ -1,6 +1,17 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use crate::cli::{Cli, ReflinkMode, SparseMode, UpdateMode};
+use crate::cli::{
+    Cli, InputFormat, Owner, ProgressMode, ProgressOutput, ReflinkFallback, ReflinkMode, SortOrder,
+    SparseMode, UpdateMode, VerboseOrder,
+};
+use crate::copy::{FileCountLimit, TotalSizeLimit};
+use crate::dedup::DedupIndex;
+use crate::engine::SharedReport;
+use crate::progress::ProgressTarget;
+use crate::stat::StatSink;
 
 /// Resolved copy options from CLI flags.
 #[derive(Debug, Clone)]
@@ -9,18 +20,56 @@ pub struct CopyOptions {
     pub force: bool,
     pub interactive: bool,
     pub no_clobber: bool,
+    /// --fail-on-skip: set once -n leaves an existing destination alone
+    pub skipped_any: Option<Arc<AtomicBool>>,
     pub verbose: bool,
+    /// --verbose-size: annotate verbose lines with file sizes
+    pub verbose_size: bool,
+    /// --verbose-order
+    pub verbose_order: VerboseOrder,
+    /// -q: no warnings, errors only
+    pub quiet: bool,
     pub debug: bool,
-    pub progress: bool,
+    pub progress: Option<ProgressMode>,
+    // --progress-output; a file target is opened by the caller
+    pub progress_target: ProgressTarget,
     pub hard_link: bool,
     pub symbolic_link: bool,
+    pub link_threshold: Option<u64>,
+    /// --hardlink-across-sources: earlier copies new files may be linked to
+    pub dedup: Option<DedupIndex>,
+    /// --report-shared: totals of copied and extent-shared bytes
+    pub shared_report: Option<SharedReport>,
     pub attributes_only: bool,
+    /// --copy-contents: read FIFOs and devices like regular files under -R
+    pub copy_contents: bool,
     pub remove_destination: bool,
     pub strip_trailing_slashes: bool,
     pub one_file_system: bool,
     pub parents: bool,
+    /// -D: create the target's missing parent directories
+    pub make_parents: bool,
+    pub strip_prefix: Option<PathBuf>,
+    pub atomic_dir: bool,
+    pub no_empty_dirs: bool,
+    // --dir-mode, None when --preserve=mode gives directories their source mode
+    pub dir_mode: Option<u32>,
+    // --chown, applied to every destination instead of the source owner
+    pub chown: Option<Owner>,
+    pub list: bool,
+    /// --max-files: files the whole run may copy into directories
+    pub max_files: Option<FileCountLimit>,
+    pub max_depth: Option<usize>,
+    /// --max-total-size: bytes of file data the whole run may copy
+    pub max_total_size: Option<TotalSizeLimit>,
+    pub pre_check_size: bool,
+    pub hardlink_ref_count_check: bool,
+    pub direct: bool,
+    pub fadvise: bool,
+    pub cow_verify: bool,
     pub no_target_directory: bool,
     pub target_directory: Option<PathBuf>,
+    pub from_format: Option<InputFormat>,
 
     // Dereference behavior
     pub dereference: Dereference,
@@ -31,20 +80,69 @@ pub struct CopyOptions {
     pub preserve_timestamps: bool,
     pub preserve_links: bool,
     pub preserve_xattr: bool,
+    /// --preserve=xattr named explicitly, not just implied by -a: xattrs the
+    /// destination can't hold are warned about
+    pub require_xattr: bool,
     pub preserve_acl: bool,
+    pub preserve_context: bool,
+    /// --preserve=context named explicitly, not just implied by -a: a
+    /// context that can't be preserved fails the copy
+    #[cfg_attr(not(feature = "selinux"), allow(dead_code))]
+    pub require_context: bool,
+    /// --preserve=flags: chattr inode flags (not part of `all`)
+    pub preserve_flags: bool,
+    /// --strict-xattr: an xattr that can't be copied is an error, not a warning
+    pub strict_xattr: bool,
 
     // Reflink
     pub reflink: ReflinkMode,
+    pub reflink_fallback: ReflinkFallback,
 
     // Sparse
     pub sparse: SparseMode,
+    /// Share of the file --sparse=auto needs to find in holes (0.0: any hole)
+    pub sparse_ratio_threshold: f64,
+    pub no_sparse_on_copy_file_range: bool,
+
+    // Directory entry order
+    pub sort: SortOrder,
+
+    // Worker threads for parallel copies (None = automatic)
+    pub jobs: Option<usize>,
+
+    // Per-step I/O timeout (--io-timeout)
+    pub io_timeout: Option<Duration>,
+
+    // Bytes per copy_file_range/sendfile call (--chunk-size)
+    pub chunk_size: Option<usize>,
 
     // Update
     pub update: Option<UpdateMode>,
+    pub timestamp_resolution_secs: u64,
+    pub size_only: bool,
+
+    // Incremental backup: previous tree to hard-link unchanged files from.
+    // `link_dest_root` is the destination path that LINKDIR mirrors; it is
+    // filled in once the target is resolved.
+    pub link_dest: Option<PathBuf>,
+    pub link_dest_root: Option<PathBuf>,
 
     // Backup
     pub backup: BackupMode,
     pub backup_suffix: String,
+
+    // SELinux: None = untouched, Some("") = default type (-Z), Some(ctx) = explicit
+    pub selinux_context: Option<String>,
+    #[cfg(feature = "selinux")]
+    pub selinux_backend: crate::selinux::SharedBackend,
+
+    // Source paths whose per-file errors are only warned about
+    pub ignore_errors_from: Vec<glob::Pattern>,
+
+    // --stat output, opened by the caller once arguments are validated
+    pub stat: Option<StatSink>,
+    // --copy-stat output, opened likewise
+    pub copy_stat: Option<StatSink>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -68,16 +166,17 @@ pub enum BackupMode {
 impl CopyOptions {
     pub fn from_cli(cli: &Cli) -> Self {
         let debug = cli.debug;
-        let verbose = cli.verbose || debug;
+        let verbose = cli.verbose || cli.verbose_size || debug;
 
         // Resolve dereference: last specified wins, default depends on -R
+        // (-a implies -d, so it never follows command-line symlinks either)
         let dereference = if cli.dereference {
             Dereference::Always
         } else if cli.no_dereference || cli.no_deref_preserve_links {
             Dereference::Never
         } else if cli.dereference_args {
             Dereference::CommandLine
-        } else if cli.recursive {
+        } else if cli.recursive || cli.archive {
             Dereference::Never
         } else {
             Dereference::CommandLine
@@ -90,57 +189,63 @@ impl CopyOptions {
         let mut preserve_timestamps = archive || cli.preserve_default;
         let mut preserve_links = archive || cli.no_deref_preserve_links;
         let mut preserve_xattr = archive;
+        let mut require_xattr = false;
         let mut preserve_acl = false;
-        let mut _preserve_context = archive;
+        let mut preserve_context = archive;
+        let mut require_context = false;
+        let mut preserve_flags = false;
         let mut _preserve_all = archive;
 
-        if let Some(ref attrs) = cli.preserve {
-            for attr in attrs {
-                match attr.as_str() {
-                    "mode" => preserve_mode = true,
-                    "ownership" => preserve_ownership = true,
-                    "timestamps" => preserve_timestamps = true,
-                    "links" => preserve_links = true,
-                    "xattr" => preserve_xattr = true,
-                    "acl" => preserve_acl = true,
-                    "context" => _preserve_context = true,
-                    "all" => {
-                        preserve_mode = true;
-                        preserve_ownership = true;
-                        preserve_timestamps = true;
-                        preserve_links = true;
-                        preserve_xattr = true;
-                        preserve_acl = true;
-                        _preserve_context = true;
-                        _preserve_all = true;
-                    }
-                    _ => {}
+        // The last --preserve or --no-preserve naming an attribute wins. A Cli
+        // from plain `parse_from` has no recorded order: its --preserve list
+        // applies first, then --no-preserve
+        let attrs: Vec<(bool, &str)> = if cli.preserve_order.is_empty() {
+            let on = cli.preserve.iter().flatten().map(|a| (true, a.as_str()));
+            let off = cli
+                .no_preserve
+                .iter()
+                .flatten()
+                .map(|a| (false, a.as_str()));
+            on.chain(off).collect()
+        } else {
+            cli.preserve_order
+                .iter()
+                .map(|(on, a)| (*on, a.as_str()))
+                .collect()
+        };
+        for (on, attr) in attrs {
+            match attr {
+                "mode" => preserve_mode = on,
+                "ownership" => preserve_ownership = on,
+                "timestamps" => preserve_timestamps = on,
+                // ctime can't be set: main warns about it once
+                "timestamps=ctime" if on => preserve_timestamps = true,
+                "links" => preserve_links = on,
+                "xattr" => {
+                    preserve_xattr = on;
+                    require_xattr = on;
                 }
-            }
-        }
-
-        if let Some(ref attrs) = cli.no_preserve {
-            for attr in attrs {
-                match attr.as_str() {
-                    "mode" => preserve_mode = false,
-                    "ownership" => preserve_ownership = false,
-                    "timestamps" => preserve_timestamps = false,
-                    "links" => preserve_links = false,
-                    "xattr" => preserve_xattr = false,
-                    "acl" => preserve_acl = false,
-                    "context" => _preserve_context = false,
-                    "all" => {
-                        preserve_mode = false;
-                        preserve_ownership = false;
-                        preserve_timestamps = false;
-                        preserve_links = false;
-                        preserve_xattr = false;
-                        preserve_acl = false;
-                        _preserve_context = false;
-                        _preserve_all = false;
+                "acl" => preserve_acl = on,
+                "context" => {
+                    preserve_context = on;
+                    require_context = on;
+                }
+                "flags" => preserve_flags = on,
+                "all" => {
+                    preserve_mode = on;
+                    preserve_ownership = on;
+                    preserve_timestamps = on;
+                    preserve_links = on;
+                    preserve_xattr = on;
+                    preserve_acl = on;
+                    preserve_context = on;
+                    // --preserve=all leaves flags alone; --no-preserve=all clears them
+                    if !on {
+                        preserve_flags = false;
                     }
-                    _ => {}
+                    _preserve_all = on;
                 }
+                _ => {}
             }
         }
 
@@ -158,37 +263,118 @@ impl CopyOptions {
             .or_else(|| std::env::var("SIMPLE_BACKUP_SUFFIX").ok())
             .unwrap_or_else(|| "~".to_string());
 
+        // Resolve SELinux context: explicit --context=CTX wins over -Z
+        let selinux_context = match cli.context {
+            Some(ref ctx) => Some(ctx.clone()),
+            None if cli.selinux_default => Some(String::new()),
+            None => None,
+        };
+
         Self {
             recursive: cli.recursive || archive,
             force: cli.force,
             interactive: cli.interactive,
             no_clobber: cli.no_clobber && !cli.interactive,
+            skipped_any: cli.fail_on_skip.map(|_| Arc::default()),
             verbose,
+            verbose_size: cli.verbose_size,
+            verbose_order: cli.verbose_order.unwrap_or(VerboseOrder::Grouped),
+            quiet: cli.quiet,
             debug,
             progress: cli.progress,
+            progress_target: match cli.progress_output {
+                Some(ProgressOutput::Stdout) => ProgressTarget::Stdout,
+                _ => ProgressTarget::Stderr,
+            },
             hard_link: cli.hard_link,
             symbolic_link: cli.symbolic_link,
+            link_threshold: cli.link_threshold,
+            dedup: cli.hardlink_across_sources.map(DedupIndex::new),
+            shared_report: cli.report_shared.then(SharedReport::default),
             attributes_only: cli.attributes_only,
+            copy_contents: cli.copy_contents,
             remove_destination: cli.remove_destination,
             strip_trailing_slashes: cli.strip_trailing_slashes,
             one_file_system: cli.one_file_system,
             parents: cli.parents,
+            make_parents: cli.make_parents,
+            strip_prefix: cli.strip_prefix.clone(),
+            atomic_dir: cli.atomic_dir,
+            no_empty_dirs: cli.no_empty_dirs,
+            dir_mode: cli.dir_mode.filter(|_| !preserve_mode),
+            chown: cli.chown,
+            list: cli.list,
+            max_files: cli.max_files.map(FileCountLimit::new),
+            max_depth: cli.max_depth,
+            max_total_size: cli.max_total_size.map(TotalSizeLimit::new),
+            pre_check_size: cli.pre_check_size,
+            hardlink_ref_count_check: cli.hardlink_ref_count_check,
+            direct: cli.direct,
+            fadvise: cli.fadvise,
+            cow_verify: cli.cow_verify,
             no_target_directory: cli.no_target_directory,
             target_directory: cli.target_directory.clone(),
+            from_format: cli.from_format,
             dereference,
             preserve_mode,
             preserve_ownership,
             preserve_timestamps,
             preserve_links,
             preserve_xattr,
+            require_xattr,
             preserve_acl,
+            preserve_flags,
+            preserve_context,
+            require_context,
+            strict_xattr: cli.strict_xattr,
             reflink,
+            reflink_fallback: cli.reflink_fallback.unwrap_or(ReflinkFallback::Auto),
             sparse,
+            sparse_ratio_threshold: cli.sparse_detect_ratio.unwrap_or(0.0),
+            no_sparse_on_copy_file_range: cli.no_sparse_on_copy_file_range,
+            sort: cli.sort.unwrap_or(SortOrder::None),
+            jobs: cli.jobs.filter(|&n| n > 0),
+            io_timeout: cli.io_timeout.map(Duration::from_secs),
+            chunk_size: cli.chunk_size,
             update: cli.update,
+            timestamp_resolution_secs: cli.timestamp_resolution,
+            size_only: cli.size_only,
+            link_dest: cli.link_dest.clone(),
+            link_dest_root: None,
             backup,
             backup_suffix,
+            selinux_context,
+            #[cfg(feature = "selinux")]
+            selinux_backend: Default::default(),
+            ignore_errors_from: cli.ignore_errors_from.clone(),
+            stat: None,
+            copy_stat: None,
         }
     }
+
+    /// Whether per-file copies in the walkdir path may run concurrently.
+    /// Prompts read stdin and backup naming probes siblings, so both stay
+    /// sequential, as do verbose lines that must come out in tree order.
+    pub fn can_parallelize(&self) -> bool {
+        !self.interactive && self.backup == BackupMode::None && !self.verbose_tree()
+    }
+
+    /// -v with --verbose-order=tree: each line printed as its entry is copied.
+    pub fn verbose_tree(&self) -> bool {
+        self.verbose && self.verbose_order == VerboseOrder::Tree
+    }
+
+    /// Note for --fail-on-skip that -n left an existing destination alone.
+    pub fn record_skip(&self) {
+        if let Some(ref skipped) = self.skipped_any {
+            skipped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether a copy error on source `path` is tolerated (--ignore-errors-from).
+    pub fn ignores_errors_for(&self, path: &Path) -> bool {
+        self.ignore_errors_from.iter().any(|p| p.matches_path(path))
+    }
 }
 
 fn resolve_backup(cli: &Cli) -> BackupMode {
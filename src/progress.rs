@@ -1,68 +1,328 @@
-use std::io::IsTerminal;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle, TermLike};
+
+use crate::cli::ProgressMode;
+
+/// Where progress bars are drawn (--progress-output).
+#[derive(Debug, Clone, Default)]
+pub enum ProgressTarget {
+    #[default]
+    Stderr,
+    Stdout,
+    /// file:PATH — drawn whether or not anyone watches, escapes included
+    File(ProgressFile),
+}
+
+impl ProgressTarget {
+    /// The draw target, or None when a bar has no TTY to go to.
+    fn draw_target(&self, mode: ProgressMode) -> Option<ProgressDrawTarget> {
+        if mode == ProgressMode::Plain {
+            let out: Arc<Mutex<dyn Write + Send>> = match self {
+                Self::Stderr => Arc::new(Mutex::new(io::stderr())),
+                Self::Stdout => Arc::new(Mutex::new(io::stdout())),
+                Self::File(file) => file.0.clone(),
+            };
+            return Some(ProgressDrawTarget::term_like_with_hz(
+                Box::new(PlainTerm::new(out)),
+                PLAIN_HZ,
+            ));
+        }
+        match self {
+            Self::Stderr => io::stderr().is_terminal().then(ProgressDrawTarget::stderr),
+            Self::Stdout => io::stdout().is_terminal().then(ProgressDrawTarget::stdout),
+            Self::File(file) => Some(ProgressDrawTarget::term_like(Box::new(file.clone()))),
+        }
+    }
+}
+
+/// --progress=plain redraws at most once a second: one log line each.
+const PLAIN_HZ: u8 = 1;
+
+/// --progress=plain: each redraw becomes one plain line, with no cursor
+/// movement or colors, so progress reads fine in a log or a CI console.
+struct PlainTerm {
+    out: Arc<Mutex<dyn Write + Send>>,
+    /// The line being drawn, written out on flush
+    line: Mutex<String>,
+    /// The last line written: an unchanged redraw is not repeated
+    last: Mutex<String>,
+}
+
+impl PlainTerm {
+    fn new(out: Arc<Mutex<dyn Write + Send>>) -> Self {
+        PlainTerm {
+            out,
+            line: Mutex::new(String::new()),
+            last: Mutex::new(String::new()),
+        }
+    }
+
+    fn emit(&self, line: &str) -> io::Result<()> {
+        let line = line.trim_end();
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        if line.is_empty() || *last == line {
+            return Ok(());
+        }
+        line.clone_into(&mut last);
+        drop(last);
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(out, "{}", line)?;
+        out.flush()
+    }
+}
+
+impl std::fmt::Debug for PlainTerm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PlainTerm")
+    }
+}
+
+impl TermLike for PlainTerm {
+    fn width(&self) -> u16 {
+        // Wide enough that indicatif never wraps or truncates a line
+        u16::MAX
+    }
+
+    fn move_cursor_up(&self, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_down(&self, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_right(&self, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_left(&self, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        let mut line = self.line.lock().unwrap_or_else(|e| e.into_inner());
+        line.push_str(s);
+        let done = std::mem::take(&mut *line);
+        drop(line);
+        self.emit(&done)
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        let s = s.trim_start_matches('\r');
+        self.line
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push_str(s);
+        Ok(())
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let done = std::mem::take(&mut *self.line.lock().unwrap_or_else(|e| e.into_inner()));
+        self.emit(&done)
+    }
+}
+
+/// A progress log file, shared by every bar of the run. Renders the bars
+/// with the ANSI cursor movements a terminal would get.
+#[derive(Debug, Clone)]
+pub struct ProgressFile(Arc<Mutex<File>>);
+
+impl ProgressFile {
+    /// Create (truncate) the file at `path`.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(ProgressFile(Arc::new(Mutex::new(File::create(path)?))))
+    }
+
+    fn write(&self, s: &str) -> io::Result<()> {
+        let mut file = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(s.as_bytes())
+    }
+}
+
+impl TermLike for ProgressFile {
+    fn width(&self) -> u16 {
+        80
+    }
+
+    fn move_cursor_up(&self, n: usize) -> io::Result<()> {
+        if n > 0 {
+            self.write(&format!("\x1b[{}A", n))?;
+        }
+        Ok(())
+    }
+
+    fn move_cursor_down(&self, n: usize) -> io::Result<()> {
+        if n > 0 {
+            self.write(&format!("\x1b[{}B", n))?;
+        }
+        Ok(())
+    }
+
+    fn move_cursor_right(&self, n: usize) -> io::Result<()> {
+        if n > 0 {
+            self.write(&format!("\x1b[{}C", n))?;
+        }
+        Ok(())
+    }
+
+    fn move_cursor_left(&self, n: usize) -> io::Result<()> {
+        if n > 0 {
+            self.write(&format!("\x1b[{}D", n))?;
+        }
+        Ok(())
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        self.write(&format!("{}\n", s))
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        self.write(s)
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        self.write("\r\x1b[2K")
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).flush()
+    }
+}
 
 /// Create a progress bar for a single file copy.
-/// Only displays if `enabled` is true AND stderr is a TTY.
-pub fn make_file_progress(total: u64, name: &str, enabled: bool) -> ProgressBar {
-    if !enabled || !std::io::stderr().is_terminal() || total == 0 {
+/// Only displays with --progress AND if `target` can show it (always, for
+/// --progress=plain).
+pub fn make_file_progress(
+    total: u64,
+    name: &str,
+    mode: Option<ProgressMode>,
+    target: &ProgressTarget,
+) -> ProgressBar {
+    let Some(mode) = mode.filter(|_| total > 0) else {
         return ProgressBar::hidden();
-    }
+    };
+    let Some(draw_target) = target.draw_target(mode) else {
+        return ProgressBar::hidden();
+    };
 
-    let pb = ProgressBar::new(total);
-    pb.set_style(
-        ProgressStyle::default_bar()
+    let pb = ProgressBar::with_draw_target(Some(total), draw_target);
+    let style = match mode {
+        ProgressMode::Bar => ProgressStyle::default_bar()
             .template(
                 "{spinner:.green} {msg}\n  [{elapsed_precise}] [{wide_bar:.cyan/dark_gray}] \
                  {bytes}/{total_bytes} ({bytes_per_sec}, eta {eta})",
             )
             .unwrap()
             .progress_chars("━╸─"),
-    );
+        ProgressMode::Plain => ProgressStyle::with_template(
+            "[{elapsed_precise}] {msg}: {bytes}/{total_bytes} ({percent}%, eta {eta})",
+        )
+        .unwrap(),
+    };
+    pb.set_style(style);
     pb.set_message(name.to_string());
     pb
 }
 
 /// Create a spinner-style progress bar for recursive directory copies.
 /// Shows file count as it progresses.
-pub fn make_dir_progress(src_name: &str, enabled: bool) -> ProgressBar {
-    if !enabled || !std::io::stderr().is_terminal() {
+pub fn make_dir_progress(
+    src_name: &str,
+    mode: Option<ProgressMode>,
+    target: &ProgressTarget,
+) -> ProgressBar {
+    let Some(mode) = mode else {
         return ProgressBar::hidden();
-    }
+    };
+    let Some(draw_target) = target.draw_target(mode) else {
+        return ProgressBar::hidden();
+    };
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} [{elapsed_precise}] {msg}")
-            .unwrap(),
-    );
+    let pb = ProgressBar::with_draw_target(None, draw_target);
+    let template = match mode {
+        ProgressMode::Bar => "{spinner:.green} [{elapsed_precise}] {msg}",
+        ProgressMode::Plain => "[{elapsed_precise}] {msg}",
+    };
+    pb.set_style(ProgressStyle::default_spinner().template(template).unwrap());
     pb.set_message(format!("Copying {} ...", src_name));
-    pb.enable_steady_tick(std::time::Duration::from_millis(100));
+    pb.enable_steady_tick(REFRESH_INTERVAL);
     pb
 }
 
+/// Interval between spinner message refreshes.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Thread-safe file counter for directory progress.
+/// Copy threads only bump an atomic; a ticker thread renders the count every
+/// `REFRESH_INTERVAL`, so the hot loop never contends on the bar's lock.
 pub struct DirProgressCounter {
     pb: ProgressBar,
-    count: AtomicU64,
+    count: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    ticker: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl DirProgressCounter {
     pub fn new(pb: ProgressBar) -> Self {
+        let count = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Nothing to render for a hidden bar (no --progress, or not a TTY)
+        let ticker = (!pb.is_hidden()).then(|| {
+            let (pb, count, stop) = (pb.clone(), Arc::clone(&count), Arc::clone(&stop));
+            std::thread::spawn(move || {
+                let mut shown = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(REFRESH_INTERVAL);
+                    let n = count.load(Ordering::Relaxed);
+                    if n != shown {
+                        pb.set_message(format!("{} files copied", n));
+                        shown = n;
+                    }
+                }
+            })
+        });
+
         Self {
             pb,
-            count: AtomicU64::new(0),
+            count,
+            stop,
+            ticker: Mutex::new(ticker),
         }
     }
 
     pub fn inc(&self) {
-        let n = self.count.fetch_add(1, Ordering::Relaxed) + 1;
-        self.pb.set_message(format!("{} files copied", n));
+        self.count.fetch_add(1, Ordering::Relaxed);
     }
 
     pub fn finish(&self) {
+        self.stop_ticker();
         let n = self.count.load(Ordering::Relaxed);
         self.pb.finish_with_message(format!("{} files copied", n));
     }
+
+    fn stop_ticker(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.ticker.lock().ok().and_then(|mut t| t.take()) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for DirProgressCounter {
+    fn drop(&mut self) {
+        // Copy aborted before finish(): don't leave the ticker running
+        self.stop_ticker();
+    }
 }
@@ -0,0 +1,174 @@
+use std::fs;
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use indicatif::ProgressBar;
+
+use crate::cli::Cli;
+use crate::copy;
+use crate::dir;
+use crate::options::CopyOptions;
+
+type Check = fn(&Path) -> Result<(), String>;
+
+/// Smoke check of the main copy paths (--selftest), run in a scratch
+/// directory under $TMPDIR so the filesystem being validated can be chosen.
+/// Prints one line per check; returns whether all of them passed.
+pub fn run() -> bool {
+    let root = std::env::temp_dir().join(format!("cp-selftest-{}", std::process::id()));
+    if let Err(e) = fs::create_dir(&root) {
+        eprintln!("cp: selftest: cannot create '{}': {}", root.display(), e);
+        return false;
+    }
+
+    let checks: [(&str, Check); 5] = [
+        ("single file", check_single_file),
+        ("recursive tree", check_tree),
+        ("sparse file", check_sparse),
+        ("symlink", check_symlink),
+        ("directory fds", check_dir_fds),
+    ];
+
+    let mut ok = true;
+    for (name, check) in checks {
+        let dir = root.join(name.replace(' ', "-"));
+        let result = fs::create_dir(&dir)
+            .map_err(|e| e.to_string())
+            .and_then(|()| check(&dir));
+        match result {
+            Ok(()) => println!("selftest: {:<16} ok", name),
+            Err(msg) => {
+                println!("selftest: {:<16} FAILED: {}", name, msg);
+                ok = false;
+            }
+        }
+    }
+
+    let _ = fs::remove_dir_all(&root);
+    ok
+}
+
+/// Options as if parsed from the command line (placeholder paths).
+fn options(args: &[&str]) -> CopyOptions {
+    let mut argv = vec!["cp"];
+    argv.extend_from_slice(args);
+    argv.extend_from_slice(&["src", "dst"]);
+    CopyOptions::from_cli(&Cli::parse_ordered_from(argv))
+}
+
+fn pattern(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+fn same_content(a: &Path, b: &Path) -> Result<(), String> {
+    let da = fs::read(a).map_err(|e| format!("{}: {}", a.display(), e))?;
+    let db = fs::read(b).map_err(|e| format!("{}: {}", b.display(), e))?;
+    if da != db {
+        return Err(format!("'{}' differs from '{}'", b.display(), a.display()));
+    }
+    Ok(())
+}
+
+fn check_single_file(dir: &Path) -> Result<(), String> {
+    let src = dir.join("src");
+    let dst = dir.join("dst");
+    fs::write(&src, pattern(300_000)).map_err(|e| e.to_string())?;
+
+    copy::copy_single(&src, &dst, &options(&[]), true, &ProgressBar::hidden())
+        .map_err(|e| e.to_string())?;
+    same_content(&src, &dst)
+}
+
+fn check_tree(dir: &Path) -> Result<(), String> {
+    let src = dir.join("src");
+    let dst = dir.join("dst");
+    let files: Vec<PathBuf> = ["a", "sub/b", "sub/deeper/c"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    for (i, f) in files.iter().enumerate() {
+        let path = src.join(f);
+        fs::create_dir_all(path.parent().unwrap_or(&src)).map_err(|e| e.to_string())?;
+        fs::write(&path, pattern(1000 * (i + 1))).map_err(|e| e.to_string())?;
+    }
+
+    dir::copy_directory(&src, &dst, &options(&["-R"])).map_err(|e| e.to_string())?;
+    for f in &files {
+        same_content(&src.join(f), &dst.join(f))?;
+    }
+    Ok(())
+}
+
+fn check_sparse(dir: &Path) -> Result<(), String> {
+    let src = dir.join("src");
+    let dst = dir.join("dst");
+    let mut f = fs::File::create(&src).map_err(|e| e.to_string())?;
+    f.seek(SeekFrom::Start(4 * 1024 * 1024))
+        .and_then(|_| f.write_all(b"tail"))
+        .map_err(|e| e.to_string())?;
+    drop(f);
+
+    copy::copy_single(
+        &src,
+        &dst,
+        &options(&["--sparse=always"]),
+        true,
+        &ProgressBar::hidden(),
+    )
+    .map_err(|e| e.to_string())?;
+    same_content(&src, &dst)?;
+
+    // Only meaningful where the filesystem kept the source sparse
+    let blocks = |p: &Path| fs::metadata(p).map(|m| m.blocks()).unwrap_or(0);
+    let src_meta = fs::metadata(&src).map_err(|e| e.to_string())?;
+    if src_meta.blocks() * 512 < src_meta.len() && blocks(&dst) > blocks(&src) {
+        return Err(format!(
+            "holes not preserved ({} blocks, source {})",
+            blocks(&dst),
+            blocks(&src)
+        ));
+    }
+    Ok(())
+}
+
+fn check_symlink(dir: &Path) -> Result<(), String> {
+    let src = dir.join("src");
+    let dst = dir.join("dst");
+    fs::create_dir(&src).map_err(|e| e.to_string())?;
+    fs::write(src.join("target"), b"pointed-to").map_err(|e| e.to_string())?;
+    std::os::unix::fs::symlink("target", src.join("link")).map_err(|e| e.to_string())?;
+
+    dir::copy_directory(&src, &dst, &options(&["-R"])).map_err(|e| e.to_string())?;
+    let link = fs::read_link(dst.join("link")).map_err(|e| e.to_string())?;
+    if link != Path::new("target") {
+        return Err(format!("link points to '{}'", link.display()));
+    }
+    same_content(&src.join("target"), &dst.join("link"))
+}
+
+/// The library's fd-relative entry point: names resolve against the
+/// directory fd, whatever the working directory.
+fn check_dir_fds(dir: &Path) -> Result<(), String> {
+    let src = dir.join("src");
+    fs::create_dir_all(src.join("sub")).map_err(|e| e.to_string())?;
+    fs::write(src.join("sub/f"), pattern(5000)).map_err(|e| e.to_string())?;
+    std::os::unix::fs::symlink("sub/f", src.join("link")).map_err(|e| e.to_string())?;
+
+    let fd = fs::File::open(dir).map_err(|e| e.to_string())?;
+    let fd = fd.as_raw_fd();
+    let opts = options(&["-R"]);
+    dir::copy_at(fd, Path::new("src"), fd, Path::new("dst"), &opts).map_err(|e| e.to_string())?;
+    dir::copy_at(fd, Path::new("src/link"), fd, Path::new("renamed"), &opts)
+        .map_err(|e| e.to_string())?;
+
+    same_content(&src.join("sub/f"), &dir.join("dst/sub/f"))?;
+    for link in ["dst/link", "renamed"] {
+        let target = fs::read_link(dir.join(link)).map_err(|e| e.to_string())?;
+        if target != Path::new("sub/f") {
+            return Err(format!("'{}' points to '{}'", link, target.display()));
+        }
+    }
+    Ok(())
+}
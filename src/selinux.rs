@@ -0,0 +1,196 @@
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::{CpError, CpResult};
+use crate::options::CopyOptions;
+use crate::util;
+
+/// Abstraction over the SELinux library so context assignment can be tested
+/// without a labeled system.
+pub trait ContextBackend {
+    /// Whether SELinux is enabled on this host.
+    fn is_enabled(&self) -> bool;
+    /// Default context for `path` according to the loaded file-context policy.
+    fn default_context(&self, path: &Path, mode: u32) -> io::Result<String>;
+    /// Current context of `path` (without following symlinks).
+    fn get_context(&self, path: &Path) -> io::Result<String>;
+    /// Set the security context of `path` (without following symlinks).
+    fn set_context(&self, path: &Path, context: &str) -> io::Result<()>;
+}
+
+/// Backend calling into libselinux.
+pub struct LibSelinux;
+
+/// The backend a copy labels its destinations through: libselinux, unless a
+/// library caller puts another one in `CopyOptions::selinux_backend`.
+#[derive(Clone)]
+pub struct SharedBackend(pub Arc<dyn ContextBackend + Send + Sync>);
+
+impl Default for SharedBackend {
+    fn default() -> Self {
+        Self(Arc::new(LibSelinux))
+    }
+}
+
+impl fmt::Debug for SharedBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedBackend")
+    }
+}
+
+#[link(name = "selinux")]
+unsafe extern "C" {
+    fn is_selinux_enabled() -> i32;
+    fn matchpathcon(
+        path: *const nix::libc::c_char,
+        mode: u32,
+        con: *mut *mut nix::libc::c_char,
+    ) -> i32;
+    fn freecon(con: *mut nix::libc::c_char);
+    fn lgetfilecon(path: *const nix::libc::c_char, con: *mut *mut nix::libc::c_char) -> i32;
+    fn lsetfilecon(path: *const nix::libc::c_char, con: *const nix::libc::c_char) -> i32;
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::from_raw_os_error(nix::libc::EINVAL))
+}
+
+/// Take ownership of a context string allocated by libselinux.
+fn take_con(con: *mut nix::libc::c_char) -> String {
+    let ctx = unsafe { CStr::from_ptr(con) }
+        .to_string_lossy()
+        .into_owned();
+    unsafe { freecon(con) };
+    ctx
+}
+
+impl ContextBackend for LibSelinux {
+    fn is_enabled(&self) -> bool {
+        unsafe { is_selinux_enabled() > 0 }
+    }
+
+    fn default_context(&self, path: &Path, mode: u32) -> io::Result<String> {
+        let c = c_path(path)?;
+        let mut con: *mut nix::libc::c_char = std::ptr::null_mut();
+        if unsafe { matchpathcon(c.as_ptr(), mode, &mut con) } != 0 || con.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(take_con(con))
+    }
+
+    fn get_context(&self, path: &Path) -> io::Result<String> {
+        let c = c_path(path)?;
+        let mut con: *mut nix::libc::c_char = std::ptr::null_mut();
+        if unsafe { lgetfilecon(c.as_ptr(), &mut con) } < 0 || con.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(take_con(con))
+    }
+
+    fn set_context(&self, path: &Path, context: &str) -> io::Result<()> {
+        let c = c_path(path)?;
+        let con =
+            CString::new(context).map_err(|_| io::Error::from_raw_os_error(nix::libc::EINVAL))?;
+        if unsafe { lsetfilecon(c.as_ptr(), con.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+/// Apply the requested context to `dst`. An empty `requested` label selects
+/// the policy default for the destination's location (-Z).
+/// Does nothing when SELinux is disabled.
+pub fn apply_context<B: ContextBackend + ?Sized>(
+    backend: &B,
+    dst: &Path,
+    requested: &str,
+) -> CpResult<()> {
+    if !backend.is_enabled() {
+        return Ok(());
+    }
+
+    let selinux_err = |e: io::Error| CpError::Selinux {
+        path: dst.to_path_buf(),
+        msg: e.to_string(),
+    };
+
+    let context = if requested.is_empty() {
+        let mode = std::fs::symlink_metadata(dst)
+            .map(|m| m.mode())
+            .unwrap_or(0);
+        backend.default_context(dst, mode).map_err(selinux_err)?
+    } else {
+        requested.to_string()
+    };
+
+    backend.set_context(dst, &context).map_err(selinux_err)
+}
+
+/// Copy the security context of `src` onto `dst` (--preserve=context).
+/// Sources without a label, or destinations on filesystems that can't store
+/// one, are skipped — like xattrs on a filesystem without support.
+pub fn copy_context<B: ContextBackend + ?Sized>(
+    backend: &B,
+    src: &Path,
+    dst: &Path,
+) -> CpResult<()> {
+    if !backend.is_enabled() {
+        return Ok(());
+    }
+
+    let context = match backend.get_context(src) {
+        Ok(c) => c,
+        Err(_) => return Ok(()),
+    };
+
+    match backend.set_context(dst, &context) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(nix::libc::ENOTSUP) => Ok(()),
+        Err(e) => Err(CpError::Selinux {
+            path: dst.to_path_buf(),
+            msg: e.to_string(),
+        }),
+    }
+}
+
+/// Set the destination context according to -Z / --context, if requested.
+pub fn set_dest_context(dst: &Path, opts: &CopyOptions) -> CpResult<()> {
+    match opts.selinux_context {
+        Some(ref label) => apply_context(&*opts.selinux_backend.0, dst, label),
+        None => Ok(()),
+    }
+}
+
+/// Preserve the source context (--preserve=context, -a) unless an explicit
+/// -Z / --context was given, which takes precedence.
+pub fn preserve_context(src: &Path, dst: &Path, opts: &CopyOptions) -> CpResult<()> {
+    preserve_context_with(&*opts.selinux_backend.0, src, dst, opts)
+}
+
+/// `preserve_context` through `backend`. As in GNU cp, failing to set the
+/// context is only an error for an explicit --preserve=context; implied by
+/// -a, it is a warning.
+pub fn preserve_context_with<B: ContextBackend + ?Sized>(
+    backend: &B,
+    src: &Path,
+    dst: &Path,
+    opts: &CopyOptions,
+) -> CpResult<()> {
+    if !opts.preserve_context || opts.selinux_context.is_some() {
+        return Ok(());
+    }
+    match copy_context(backend, src, dst) {
+        Err(e) if !opts.require_context => {
+            util::warn(opts, e);
+            Ok(())
+        }
+        result => result,
+    }
+}
@@ -1,12 +1,17 @@
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::AsRawFd;
 use std::path::Path;
+use std::sync::Mutex;
 
 use indicatif::ProgressBar;
 
 use crate::cli::SparseMode;
+use crate::engine;
 use crate::error::{CpError, CpResult};
+use crate::util;
 
 /// SEEK_HOLE and SEEK_DATA constants (Linux).
 const SEEK_DATA: i32 = 3;
@@ -15,15 +20,27 @@ const SEEK_HOLE: i32 = 4;
 /// Buffer size for sparse read/write.
 const BUF_SIZE: usize = 256 * 1024;
 
+/// --sparse=heuristic: bytes sampled from the start of the source, and the
+/// share of them that must be zero to copy by zero detection.
+const HEURISTIC_SAMPLE: usize = 64 * 1024;
+const HEURISTIC_ZERO_RATIO: f64 = 0.5;
+
+/// Whether each destination device can keep holes, probed once per run.
+static HOLE_SUPPORT: Mutex<BTreeMap<u64, bool>> = Mutex::new(BTreeMap::new());
+
 /// Copy a file preserving sparse holes using SEEK_HOLE/SEEK_DATA.
 /// Returns true if sparse copy was performed, false if fallback needed.
+/// With Auto, files whose holes make up less than `min_hole_ratio` of
+/// `size` are left to the normal copy too.
+#[allow(clippy::too_many_arguments)]
 pub fn copy_sparse(
-    src: &mut File,
-    dst: &mut File,
+    mut src: &File,
+    mut dst: &File,
     size: u64,
     src_path: &Path,
     dst_path: &Path,
     mode: SparseMode,
+    min_hole_ratio: f64,
     pb: &ProgressBar,
 ) -> CpResult<bool> {
     match mode {
@@ -33,6 +50,15 @@ pub fn copy_sparse(
             copy_sparse_by_zero_detection(src, dst, src_path, dst_path, size, pb)?;
             Ok(true)
         }
+        SparseMode::Heuristic => {
+            // Heuristic: no SEEK_HOLE/FIEMAP, decide from a sample of the data
+            let sample = std::cmp::min(HEURISTIC_SAMPLE as u64, size) as usize;
+            if sample_zero_ratio(src, sample) <= HEURISTIC_ZERO_RATIO {
+                return Ok(false);
+            }
+            copy_sparse_by_zero_detection(src, dst, src_path, dst_path, size, pb)?;
+            Ok(true)
+        }
         SparseMode::Auto => {
             // Auto: use SEEK_HOLE/SEEK_DATA to preserve existing holes
             let scan = scan_sparse_regions(src, size);
@@ -45,13 +71,25 @@ pub fn copy_sparse(
                         return Ok(false);
                     }
 
+                    // Too little of the file is holes to be worth seeking around
+                    let hole_ratio = (size - data_bytes) as f64 / size as f64;
+                    if hole_ratio < min_hole_ratio {
+                        return Ok(false);
+                    }
+
+                    // The destination would allocate the holes anyway
+                    if !dest_supports_holes(dst) {
+                        return Ok(false);
+                    }
+
                     // Set the file size to create trailing holes
-                    dst.set_len(size).map_err(|e| CpError::Write {
-                        path: dst_path.to_path_buf(),
-                        source: e,
-                    })?;
+                    dst.set_len(size)
+                        .map_err(|e| engine::write_error(dst_path, e))?;
 
                     let mut buf = vec![0u8; BUF_SIZE];
+                    // Cleared after the first region copy_file_range refuses,
+                    // so a filesystem without it costs one failed call
+                    let mut use_cfr = true;
 
                     for region in &regions {
                         src.seek(SeekFrom::Start(region.offset))
@@ -65,20 +103,28 @@ pub fn copy_sparse(
                                 source: e,
                             })?;
 
+                        // Data regions go through the kernel; read/write
+                        // only covers what copy_file_range left
                         let mut remaining = region.length;
+                        if use_cfr {
+                            let copied = engine::copy_range(src, dst, region.length, dst_path, pb)?;
+                            use_cfr = copied > 0;
+                            remaining -= copied;
+                        }
                         while remaining > 0 {
                             let to_read = std::cmp::min(remaining as usize, BUF_SIZE);
-                            let n = src.read(&mut buf[..to_read]).map_err(|e| CpError::Read {
-                                path: src_path.to_path_buf(),
-                                source: e,
-                            })?;
+                            let n =
+                                util::read_retry(&mut src, &mut buf[..to_read]).map_err(|e| {
+                                    CpError::Read {
+                                        path: src_path.to_path_buf(),
+                                        source: e,
+                                    }
+                                })?;
                             if n == 0 {
                                 break;
                             }
-                            dst.write_all(&buf[..n]).map_err(|e| CpError::Write {
-                                path: dst_path.to_path_buf(),
-                                source: e,
-                            })?;
+                            dst.write_all(&buf[..n])
+                                .map_err(|e| engine::write_error(dst_path, e))?;
                             remaining -= n as u64;
                             pb.inc(n as u64);
                         }
@@ -97,6 +143,45 @@ pub fn copy_sparse(
     }
 }
 
+/// Whether a filesystem of statfs type `fs_type` can represent holes. FAT,
+/// exFAT and HFS allocate every byte up to the file size, so seeking past
+/// a hole there only costs time. Anything else (FUSE included, which can't
+/// tell what it sits on) is assumed to.
+#[cfg(not(target_os = "macos"))]
+pub fn fs_type_supports_holes(fs_type: nix::sys::statfs::FsType) -> bool {
+    const EXFAT_SUPER_MAGIC: nix::libc::c_long = 0x2011_BAB0;
+    const HFS_SUPER_MAGIC: nix::libc::c_long = 0x4244;
+
+    let magic = fs_type.0 as nix::libc::c_long;
+    fs_type != nix::sys::statfs::MSDOS_SUPER_MAGIC
+        && magic != EXFAT_SUPER_MAGIC
+        && magic != HFS_SUPER_MAGIC
+}
+
+/// `fs_type_supports_holes` for macOS, where statfs names the filesystem
+/// instead of giving a magic number.
+#[cfg(target_os = "macos")]
+pub fn fs_name_supports_holes(name: &str) -> bool {
+    !matches!(name, "msdos" | "exfat" | "hfs")
+}
+
+/// Whether the filesystem holding `dst` can represent holes, cached per
+/// device. True when it can't be told.
+pub fn dest_supports_holes(dst: &File) -> bool {
+    let Ok(dev) = dst.metadata().map(|m| m.dev()) else {
+        return true;
+    };
+    let mut cache = HOLE_SUPPORT.lock().unwrap_or_else(|e| e.into_inner());
+    *cache.entry(dev).or_insert_with(|| {
+        #[cfg(not(target_os = "macos"))]
+        let supports = |st: nix::sys::statfs::Statfs| fs_type_supports_holes(st.filesystem_type());
+        #[cfg(target_os = "macos")]
+        let supports =
+            |st: nix::sys::statfs::Statfs| fs_name_supports_holes(st.filesystem_type_name());
+        nix::sys::statfs::fstatfs(dst).map_or(true, supports)
+    })
+}
+
 /// A data region in a file (non-hole).
 struct DataRegion {
     offset: u64,
@@ -144,25 +229,42 @@ fn scan_sparse_regions(file: &File, size: u64) -> Option<Vec<DataRegion>> {
     Some(regions)
 }
 
-/// For --sparse=always: detect zero blocks and punch holes.
+/// Share of zero bytes in the first `sample_size` bytes of `src`, which is
+/// rewound afterwards. A read error counts as no zeros (normal copy).
+fn sample_zero_ratio(mut src: &File, sample_size: usize) -> f64 {
+    let mut buf = vec![0u8; sample_size];
+    let mut filled = 0;
+    while filled < sample_size {
+        match util::read_retry(&mut src, &mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(_) => return 0.0,
+        }
+    }
+    if src.rewind().is_err() || filled == 0 {
+        return 0.0;
+    }
+    let zeros = buf[..filled].iter().filter(|&&b| b == 0).count();
+    zeros as f64 / filled as f64
+}
+
+/// For --sparse=always and --sparse=heuristic: detect zero blocks and punch holes.
 fn copy_sparse_by_zero_detection(
-    src: &mut File,
-    dst: &mut File,
+    mut src: &File,
+    mut dst: &File,
     src_path: &Path,
     dst_path: &Path,
     size: u64,
     pb: &ProgressBar,
 ) -> CpResult<()> {
-    dst.set_len(size).map_err(|e| CpError::Write {
-        path: dst_path.to_path_buf(),
-        source: e,
-    })?;
+    dst.set_len(size)
+        .map_err(|e| engine::write_error(dst_path, e))?;
 
     let mut buf = vec![0u8; BUF_SIZE];
     let mut offset: u64 = 0;
 
     loop {
-        let n = src.read(&mut buf).map_err(|e| CpError::Read {
+        let n = util::read_retry(&mut src, &mut buf).map_err(|e| CpError::Read {
             path: src_path.to_path_buf(),
             source: e,
         })?;
@@ -177,10 +279,8 @@ fn copy_sparse_by_zero_detection(
                     path: dst_path.to_path_buf(),
                     source: e,
                 })?;
-            dst.write_all(&buf[..n]).map_err(|e| CpError::Write {
-                path: dst_path.to_path_buf(),
-                source: e,
-            })?;
+            dst.write_all(&buf[..n])
+                .map_err(|e| engine::write_error(dst_path, e))?;
         }
         // If all zeros, don't write -- leave as hole
 
@@ -0,0 +1,105 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Per-file statistics sink for --stat and --copy-stat, one tab-separated line
+/// per copied file. Shared between worker threads, hence the lock.
+#[derive(Clone)]
+pub struct StatSink(Arc<Mutex<BufWriter<Box<dyn Write + Send>>>>);
+
+impl fmt::Debug for StatSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StatSink")
+    }
+}
+
+impl StatSink {
+    /// Open the sink: `-` is stdout, anything else a file (truncated).
+    pub fn open(spec: &Path) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = if spec == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(spec)?)
+        };
+        Ok(StatSink(Arc::new(Mutex::new(BufWriter::new(out)))))
+    }
+
+    /// Append the --stat line for one copy:
+    /// `src  dst  src_size  dst_size  src_mtime  dst_mtime  method  elapsed_ms`.
+    pub fn record(
+        &self,
+        src: &Path,
+        dst: &Path,
+        src_meta: &fs::Metadata,
+        method: &str,
+        elapsed: Duration,
+    ) {
+        let (dst_size, dst_mtime) = fs::metadata(dst)
+            .map(|m| (m.len(), m.mtime()))
+            .unwrap_or((0, 0));
+        let line = format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            src.display(),
+            dst.display(),
+            src_meta.len(),
+            dst_size,
+            src_meta.mtime(),
+            dst_mtime,
+            method,
+            elapsed.as_millis()
+        );
+        if let Ok(mut w) = self.0.lock() {
+            let _ = w.write_all(line.as_bytes());
+        }
+    }
+
+    /// Append the --copy-stat line for one copy, pairing each stat(2) field of
+    /// the source with the destination's: `src_ino dst_ino src_mode dst_mode
+    /// src_uid dst_uid src_gid dst_gid src_size dst_size src_mtime dst_mtime
+    /// src dst`. Modes are octal, mtimes `seconds.nanoseconds`; the fields of
+    /// a destination that cannot be stat'ed are left empty.
+    pub fn record_attrs(&self, src: &Path, dst: &Path, src_meta: &fs::Metadata) {
+        let src_fields = [
+            src_meta.ino().to_string(),
+            format!("{:o}", src_meta.mode()),
+            src_meta.uid().to_string(),
+            src_meta.gid().to_string(),
+            src_meta.size().to_string(),
+            format!("{}.{:09}", src_meta.mtime(), src_meta.mtime_nsec()),
+        ];
+        let dst_fields = match nix::sys::stat::lstat(dst) {
+            Ok(st) => [
+                st.st_ino.to_string(),
+                format!("{:o}", st.st_mode),
+                st.st_uid.to_string(),
+                st.st_gid.to_string(),
+                st.st_size.to_string(),
+                format!("{}.{:09}", st.st_mtime, st.st_mtime_nsec),
+            ],
+            Err(_) => Default::default(),
+        };
+
+        let mut line = String::new();
+        for (s, d) in src_fields.iter().zip(&dst_fields) {
+            line.push_str(s);
+            line.push('\t');
+            line.push_str(d);
+            line.push('\t');
+        }
+        line.push_str(&format!("{}\t{}\n", src.display(), dst.display()));
+        if let Ok(mut w) = self.0.lock() {
+            let _ = w.write_all(line.as_bytes());
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        match self.0.lock() {
+            Ok(mut w) => w.flush(),
+            Err(_) => Ok(()),
+        }
+    }
+}
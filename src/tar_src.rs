@@ -0,0 +1,245 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::{CpError, CpResult};
+use crate::metadata::{self, Attrs};
+use crate::options::CopyOptions;
+use crate::util;
+
+/// Expand the tar archive `archive` into the directory `dest` (--from-format=tar),
+/// with the same preservation rules as a copy: modes, ownership and timestamps
+/// follow -p/--preserve, hard links are kept with --preserve=links.
+pub fn extract(archive: &Path, dest: &Path, opts: &CopyOptions) -> CpResult<()> {
+    let file = File::open(archive).map_err(|e| CpError::OpenRead {
+        path: archive.to_path_buf(),
+        source: e,
+    })?;
+    fs::create_dir_all(dest).map_err(|e| CpError::CreateDir {
+        path: dest.to_path_buf(),
+        source: e,
+    })?;
+
+    let archive_err = |e: io::Error| CpError::Archive {
+        path: archive.to_path_buf(),
+        msg: e.to_string(),
+    };
+
+    let mut tar = tar::Archive::new(BufReader::new(file));
+    // Directory attributes are applied last, deepest first, so creating their
+    // contents doesn't bump mtimes or trip over read-only modes
+    let mut dirs: Vec<(PathBuf, Attrs)> = Vec::new();
+
+    for entry in tar.entries().map_err(archive_err)? {
+        let mut entry = entry.map_err(archive_err)?;
+        let rel = entry.path().map_err(archive_err)?.into_owned();
+        let dst = safe_join(dest, &rel).ok_or_else(|| CpError::Archive {
+            path: archive.to_path_buf(),
+            msg: format!("refusing unsafe entry path '{}'", rel.display()),
+        })?;
+        if dst == dest {
+            continue;
+        }
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent).map_err(|e| CpError::CreateDir {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+
+        let header = entry.header();
+        let mtime = filetime::FileTime::from_unix_time(header.mtime().unwrap_or(0) as i64, 0);
+        let attrs = Attrs {
+            uid: header.uid().unwrap_or(0) as u32,
+            gid: header.gid().unwrap_or(0) as u32,
+            mode: header.mode().unwrap_or(0o644) & 0o7777,
+            atime: mtime,
+            mtime,
+        };
+        let kind = header.entry_type();
+        let size = header.size().unwrap_or(0);
+
+        if kind.is_dir() {
+            // A symlink an earlier entry left here is replaced, not
+            // followed: the deferred attributes would land on its target
+            if is_symlink(&dst) {
+                let _ = fs::remove_file(&dst);
+            }
+            fs::create_dir_all(&dst).map_err(|e| CpError::CreateDir {
+                path: dst.clone(),
+                source: e,
+            })?;
+            dirs.push((dst.clone(), attrs));
+        } else if kind.is_file() {
+            if opts.no_clobber && dst.symlink_metadata().is_ok() {
+                opts.record_skip();
+                continue;
+            }
+            remove_non_dir(&dst);
+            let mut out = fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(attrs.mode)
+                .open(&dst)
+                .map_err(|e| CpError::CreateFile {
+                    path: dst.clone(),
+                    source: e,
+                })?;
+            io::copy(&mut entry, &mut out).map_err(|e| CpError::Write {
+                path: dst.clone(),
+                source: e,
+            })?;
+            drop(out);
+            metadata::apply_attrs(&dst, &attrs, opts, false)?;
+        } else if kind.is_symlink() {
+            let target = link_name(&entry, archive)?;
+            remove_non_dir(&dst);
+            std::os::unix::fs::symlink(&target, &dst).map_err(|e| CpError::Symlink {
+                dst: dst.clone(),
+                source: e,
+            })?;
+            metadata::apply_attrs(&dst, &attrs, opts, true)?;
+        } else if kind.is_hard_link() {
+            let target = link_name(&entry, archive)?;
+            let first = safe_join(dest, &target).ok_or_else(|| CpError::Archive {
+                path: archive.to_path_buf(),
+                msg: format!("refusing unsafe link target '{}'", target.display()),
+            })?;
+            // Only a file extracted from this archive: through a symlink
+            // one of its entries planted, it could be any file outside `dest`
+            if is_symlink(&first) {
+                return Err(CpError::Archive {
+                    path: archive.to_path_buf(),
+                    msg: format!("refusing hard link to symlink '{}'", target.display()),
+                });
+            }
+            remove_non_dir(&dst);
+            if opts.preserve_links {
+                fs::hard_link(&first, &dst).map_err(|e| CpError::HardLink {
+                    src: first.clone(),
+                    dst: dst.clone(),
+                    source: e,
+                })?;
+            } else {
+                // Like copying a tree without --preserve=links: a separate file
+                copy_nofollow(&first, &dst, attrs.mode).map_err(|e| CpError::Copy {
+                    src: first.clone(),
+                    dst: dst.clone(),
+                    reason: e.to_string(),
+                })?;
+                metadata::apply_attrs(&dst, &attrs, opts, false)?;
+            }
+        } else {
+            util::warn(
+                opts,
+                format_args!(
+                    "skipping '{}' in '{}': unsupported entry type",
+                    rel.display(),
+                    archive.display()
+                ),
+            );
+            continue;
+        }
+
+        if opts.verbose {
+            let size_note = if opts.verbose_size && kind.is_file() {
+                util::size_note(size)
+            } else {
+                String::new()
+            };
+            println!(
+                "'{}:{}'{} -> '{}'",
+                archive.display(),
+                rel.display(),
+                size_note,
+                dst.display()
+            );
+        }
+    }
+
+    for (dir, attrs) in dirs.iter().rev() {
+        // Through an fd that refuses a symlink in place of the directory
+        let fd = fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(nix::libc::O_DIRECTORY | nix::libc::O_NOFOLLOW)
+            .open(dir)
+            .map_err(|e| CpError::OpenRead {
+                path: dir.clone(),
+                source: e,
+            })?;
+        metadata::apply_attrs_fd(dir, fd.as_raw_fd(), attrs, opts)?;
+        // Without --preserve=mode a directory keeps its umask-based mode,
+        // but must stay writable/searchable for us if it is re-used
+        if !opts.preserve_mode && attrs.mode & 0o700 != 0o700 {
+            let _ = fd.set_permissions(fs::Permissions::from_mode(0o755));
+        }
+    }
+
+    Ok(())
+}
+
+/// `dest.join(rel)` for an archive path, or None if `rel` could escape
+/// `dest`: absolute paths, `..` components, or a symlink already extracted
+/// on the way (an earlier entry `link -> /etc` followed by `link/passwd`).
+fn safe_join(dest: &Path, rel: &Path) -> Option<PathBuf> {
+    let mut out = dest.to_path_buf();
+    for component in rel.components() {
+        match component {
+            Component::Normal(part) => {
+                if out != dest && is_symlink(&out) {
+                    return None;
+                }
+                out.push(part);
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+fn link_name<R: io::Read>(entry: &tar::Entry<'_, R>, archive: &Path) -> CpResult<PathBuf> {
+    match entry.link_name() {
+        Ok(Some(name)) => Ok(name.into_owned()),
+        Ok(None) => Err(CpError::Archive {
+            path: archive.to_path_buf(),
+            msg: "link entry without a target".into(),
+        }),
+        Err(e) => Err(CpError::Archive {
+            path: archive.to_path_buf(),
+            msg: e.to_string(),
+        }),
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    path.symlink_metadata()
+        .is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Copy the extracted file `src` to `dst` (created with `mode`), refusing
+/// to follow a symlink at `src`.
+fn copy_nofollow(src: &Path, dst: &Path, mode: u32) -> io::Result<u64> {
+    let mut input = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::libc::O_NOFOLLOW)
+        .open(src)?;
+    let mut out = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(dst)?;
+    io::copy(&mut input, &mut out)
+}
+
+/// Make way for a new entry: an existing file or symlink is replaced, like
+/// an archive extraction over a previous one.
+fn remove_non_dir(path: &Path) {
+    if path.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+        let _ = fs::remove_file(path);
+    }
+}
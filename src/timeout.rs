@@ -0,0 +1,54 @@
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+#[error("operation timed out after {0:?}")]
+pub struct TimeoutError(pub Duration);
+
+/// How often `with_idle_timeout` asks whether the worker made progress.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Run `f` on a worker thread and wait for it to finish, at most `duration`
+/// past the last time `progressed` said it got further: a long copy only
+/// times out once it stops moving. On timeout the worker is left detached:
+/// a syscall stalled on a dead NFS server cannot be cancelled, but the
+/// caller is free to report and exit.
+pub fn with_idle_timeout<T, F>(
+    duration: Duration,
+    mut progressed: impl FnMut() -> bool,
+    f: F,
+) -> Result<T, TimeoutError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let handle = std::thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+
+    let mut deadline = Instant::now() + duration;
+    loop {
+        let wait = deadline.saturating_duration_since(Instant::now());
+        match rx.recv_timeout(wait.min(POLL_INTERVAL)) {
+            Ok(value) => return Ok(value),
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                if progressed() {
+                    deadline = now + duration;
+                } else if now >= deadline {
+                    return Err(TimeoutError(duration));
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                // Worker exited without sending: it panicked — propagate
+                match handle.join() {
+                    Err(payload) => std::panic::resume_unwind(payload),
+                    Ok(()) => unreachable!("worker exited without a result"),
+                }
+            }
+        }
+    }
+}
@@ -1,14 +1,19 @@
 use std::fs;
-use std::io;
+use std::io::{self, BufRead, IsTerminal};
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::error::{CpError, CpResult};
-use crate::options::Dereference;
+use crate::options::{CopyOptions, Dereference};
 
 /// Check if two paths refer to the same file (same device + inode).
+/// Uses stat rather than opening the files, which would block on a FIFO.
 pub fn is_same_file(src: &Path, dst: &Path) -> bool {
-    same_file::is_same_file(src, dst).unwrap_or(false)
+    match (fs::metadata(src), fs::metadata(dst)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
 }
 
 /// Strip trailing slashes from a path.
@@ -24,40 +29,64 @@ pub fn strip_trailing_slashes(path: &Path) -> PathBuf {
 
 /// Determine the target path for a copy operation.
 /// Returns (sources, target_dir_or_file).
-pub fn resolve_target(
-    paths: &[PathBuf],
+pub fn resolve_target<'a>(
+    paths: &'a [PathBuf],
     target_dir: &Option<PathBuf>,
     no_target_dir: bool,
-) -> CpResult<(Vec<PathBuf>, PathBuf)> {
+) -> CpResult<(&'a [PathBuf], PathBuf)> {
     if let Some(dir) = target_dir {
-        // -t DIR: all paths are sources
-        if !dir.is_dir() {
-            return Err(CpError::NotADirectory { path: dir.clone() });
-        }
-        return Ok((paths.to_vec(), dir.clone()));
+        // -t DIR: all paths are sources. A missing DIR is reported as such,
+        // not as "not a directory"
+        return match fs::metadata(dir) {
+            Ok(m) if m.is_dir() => Ok((paths, dir.clone())),
+            Ok(_) => Err(CpError::TargetDirectory {
+                path: dir.clone(),
+                source: io::Error::from_raw_os_error(nix::libc::ENOTDIR),
+            }),
+            Err(e) => Err(CpError::TargetDirectory {
+                path: dir.clone(),
+                source: e,
+            }),
+        };
     }
 
-    match paths.len() {
-        0 => Err(CpError::MissingOperand),
-        1 => Err(CpError::MissingDestination {
-            src: paths[0].to_string_lossy().into_owned(),
+    match paths {
+        [] => Err(CpError::MissingOperand),
+        [only] => Err(CpError::MissingDestination {
+            src: only.to_string_lossy().into_owned(),
         }),
-        _ => {
-            let sources = paths[..paths.len() - 1].to_vec();
-            let dest = paths[paths.len() - 1].clone();
-
+        [sources @ .., dest] => {
             if sources.len() > 1 && !dest.is_dir() && !no_target_dir {
-                return Err(CpError::NotADirectory { path: dest });
+                return Err(CpError::NotADirectory { path: dest.clone() });
             }
 
-            Ok((sources, dest))
+            Ok((sources, dest.clone()))
         }
     }
 }
 
 /// Get the final destination path for a source file being copied.
-pub fn build_dest_path(source: &Path, dest: &Path, dest_is_dir: bool, parents: bool) -> PathBuf {
-    if dest_is_dir {
+/// `strip_prefix` (--strip-prefix) keeps the part of `source` below the
+/// prefix: `/a/b/c/f` with prefix `/a/b` lands in `dest/c/f`.
+pub fn build_dest_path(
+    source: &Path,
+    dest: &Path,
+    dest_is_dir: bool,
+    parents: bool,
+    strip_prefix: Option<&Path>,
+) -> CpResult<PathBuf> {
+    if dest_is_dir && let Some(prefix) = strip_prefix {
+        return match source.strip_prefix(prefix) {
+            Ok(rel) if !rel.as_os_str().is_empty() => Ok(dest.join(rel)),
+            Ok(_) => Ok(dest.join(source.file_name().unwrap_or(source.as_ref()))),
+            Err(_) => Err(CpError::StripPrefix {
+                path: source.to_path_buf(),
+                prefix: prefix.to_path_buf(),
+            }),
+        };
+    }
+
+    Ok(if dest_is_dir {
         if parents {
             // --parents: replicate full source path under dest
             // e.g., cp --parents a/b/c dest → dest/a/b/c
@@ -67,9 +96,56 @@ pub fn build_dest_path(source: &Path, dest: &Path, dest_is_dir: bool, parents: b
         }
     } else {
         dest.to_path_buf()
+    })
+}
+
+/// Path in the --link-dest tree corresponding to `path`, a destination path
+/// under `relative_to`: `build_link_dest_path("new/a/b", "prev", "new")` is
+/// `prev/a/b`.
+pub fn build_link_dest_path(path: &Path, link_dest: &Path, relative_to: &Path) -> PathBuf {
+    match path.strip_prefix(relative_to) {
+        Ok(rel) if !rel.as_os_str().is_empty() => link_dest.join(rel),
+        _ => link_dest.join(path.file_name().unwrap_or(path.as_os_str())),
     }
 }
 
+/// The ` (4.2 MiB)` annotation --verbose-size puts after a source name.
+pub fn size_note(bytes: u64) -> String {
+    format!(" ({})", human_size(bytes))
+}
+
+/// Format a byte count with binary units and one decimal: `512 B`, `4.2 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// --cow-verify: whether `dst`, just cloned from `src`, shares its data with
+/// it. st_blocks can't tell (a clone counts as many blocks as its source),
+/// so this asks FIEMAP: at least three quarters of each file must be in
+/// shared extents. True when the filesystem can't say.
+pub fn verify_cow(src: &Path, dst: &Path) -> bool {
+    [src, dst].iter().all(|p| {
+        let Ok(file) = fs::File::open(p) else {
+            return true;
+        };
+        let size = file.metadata().map_or(0, |m| m.len());
+        match crate::engine::shared_bytes(&file) {
+            Ok(shared) => shared.saturating_mul(4) >= size.saturating_mul(3),
+            Err(_) => true,
+        }
+    })
+}
+
 /// Get file metadata, optionally following symlinks.
 pub fn get_metadata(path: &Path, follow: bool) -> io::Result<fs::Metadata> {
     if follow {
@@ -93,12 +169,103 @@ pub fn get_device(path: &Path) -> io::Result<u64> {
     fs::metadata(path).map(|m| m.dev())
 }
 
+/// Timestamp granularity in seconds of the filesystem holding `path`:
+/// 2 for FAT/exFAT, 0 (nanosecond, nothing to round) for everything else.
+#[cfg(not(target_os = "macos"))]
+pub fn filesystem_timestamp_resolution(path: &Path) -> u64 {
+    const EXFAT_SUPER_MAGIC: nix::libc::c_long = 0x2011_BAB0;
+
+    match nix::sys::statfs::statfs(path) {
+        Ok(st) => {
+            let fs_type = st.filesystem_type();
+            if fs_type == nix::sys::statfs::MSDOS_SUPER_MAGIC
+                || fs_type.0 as nix::libc::c_long == EXFAT_SUPER_MAGIC
+            {
+                2
+            } else {
+                0
+            }
+        }
+        Err(_) => 0,
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn filesystem_timestamp_resolution(path: &Path) -> u64 {
+    match nix::sys::statfs::statfs(path) {
+        Ok(st) if matches!(st.filesystem_type_name(), "msdos" | "exfat") => 2,
+        _ => 0,
+    }
+}
+
+/// Round `t` down to a multiple of `resolution_secs` (0 leaves it untouched).
+pub fn truncate_time(t: SystemTime, resolution_secs: u64) -> SystemTime {
+    if resolution_secs == 0 {
+        return t;
+    }
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => UNIX_EPOCH + Duration::from_secs(d.as_secs() - d.as_secs() % resolution_secs),
+        Err(_) => t,
+    }
+}
+
+/// Run a raw syscall until it completes or fails with anything but EINTR.
+/// `f` follows the libc convention: a negative return with errno set on error.
+/// Only the Linux copy loops need it.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+pub fn retry_eintr(mut f: impl FnMut() -> isize) -> isize {
+    loop {
+        let ret = f();
+        if ret >= 0 || io::Error::last_os_error().raw_os_error() != Some(nix::libc::EINTR) {
+            return ret;
+        }
+    }
+}
+
+/// `Read::read`, retried when interrupted by a signal (`write_all` already does this).
+pub fn read_retry(reader: &mut impl io::Read, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match reader.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+/// Byte-for-byte comparison of two files of the same size.
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    const BUF_SIZE: usize = 64 * 1024;
+    let (mut fa, mut fb) = (fs::File::open(a)?, fs::File::open(b)?);
+    let (mut ba, mut bb) = (vec![0u8; BUF_SIZE], vec![0u8; BUF_SIZE]);
+    loop {
+        let n = read_retry(&mut fa, &mut ba)?;
+        if n == 0 {
+            return Ok(read_retry(&mut fb, &mut bb[..1])? == 0);
+        }
+        io::Read::read_exact(&mut fb, &mut bb[..n])?;
+        if ba[..n] != bb[..n] {
+            return Ok(false);
+        }
+    }
+}
+
 /// Prompt user on stderr and read y/n.
+/// When stdin is not a terminal (`find ... | cp -i ...`) the answer is read
+/// from the controlling terminal instead; without one, stdin is read as before.
 /// Accepts common affirmatives across locales: y/yes/o/oui/j/ja/s/si/d/da.
 pub fn prompt_yes(msg: &str) -> bool {
     eprint!("{}", msg);
     let mut buf = String::new();
-    if io::stdin().read_line(&mut buf).is_ok() {
+    let tty = if io::stdin().is_terminal() {
+        None
+    } else {
+        fs::File::open("/dev/tty").ok()
+    };
+    let read = match tty {
+        Some(tty) => io::BufReader::new(tty).read_line(&mut buf),
+        None => io::stdin().read_line(&mut buf),
+    };
+    if read.is_ok() {
         let answer = buf.trim().to_lowercase();
         matches!(
             answer.as_str(),
@@ -108,3 +275,10 @@ pub fn prompt_yes(msg: &str) -> bool {
         false
     }
 }
+
+/// Print a non-fatal `cp: warning: ...`, unless -q.
+pub fn warn(opts: &CopyOptions, msg: impl std::fmt::Display) {
+    if !opts.quiet {
+        eprintln!("cp: warning: {}", msg);
+    }
+}
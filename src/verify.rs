@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::options::{CopyOptions, Dereference};
+
+/// A hard link group of the source tree that the copy did not reproduce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HardlinkMismatch {
+    /// Source inode of the group.
+    pub ino: u64,
+    /// Names of the inode in the source tree.
+    pub src_links: usize,
+    /// How many of those names share one inode in the destination.
+    pub dst_links: usize,
+}
+
+/// --hardlink-ref-count-check: after copying `src` to `dst` with
+/// --preserve=links, every group of source names sharing an inode must map
+/// to destination names sharing one inode too. Links to files outside `src`
+/// don't count, so a group is the names found inside the tree.
+pub fn check_hardlink_counts(src: &Path, dst: &Path, opts: &CopyOptions) -> Vec<HardlinkMismatch> {
+    let follow = opts.dereference == Dereference::Always;
+
+    // (dev, ino) -> paths relative to `src`
+    let mut groups: HashMap<(u64, u64), Vec<PathBuf>> = HashMap::new();
+    for entry in WalkDir::new(src).follow_links(follow).into_iter().flatten() {
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if meta.nlink() > 1
+            && let Ok(rel) = entry.path().strip_prefix(src)
+        {
+            groups
+                .entry((meta.dev(), meta.ino()))
+                .or_default()
+                .push(rel.to_path_buf());
+        }
+    }
+
+    let mut mismatches: Vec<HardlinkMismatch> = groups
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .filter_map(|((_, ino), names)| {
+            // Count the destination names on the most common inode: one stray
+            // copy shows up as a group one link short
+            let mut dst_inodes: HashMap<(u64, u64), usize> = HashMap::new();
+            for rel in &names {
+                if let Ok(m) = fs::symlink_metadata(dst.join(rel)) {
+                    *dst_inodes.entry((m.dev(), m.ino())).or_default() += 1;
+                }
+            }
+            let dst_links = dst_inodes.into_values().max().unwrap_or(0);
+            (dst_links != names.len()).then_some(HardlinkMismatch {
+                ino,
+                src_links: names.len(),
+                dst_links,
+            })
+        })
+        .collect();
+    mismatches.sort_unstable_by_key(|m| m.ino);
+    mismatches
+}
@@ -123,6 +123,93 @@ pub fn cp() -> Command {
     Command::cargo_bin("cp").unwrap()
 }
 
+/// `cp()` in a new session, without a controlling terminal: -i prompts can't
+/// fall back to /dev/tty and read the answers piped to stdin.
+pub fn cp_no_tty() -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_cp"));
+    unsafe {
+        cmd.pre_exec(|| {
+            nix::libc::setsid();
+            Ok(())
+        });
+    }
+    Command::from_std(cmd)
+}
+
+/// `cp()` that permission checks apply to: when the tests run as root it
+/// runs as nobody, from a copy of the binary put in `e` (whose root is made
+/// world-readable). Whatever the copy touches must be accessible to nobody.
+pub fn cp_unprivileged(e: &Env) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    if !nix::unistd::geteuid().is_root() {
+        return cp();
+    }
+    let bin = e.p("cp-bin");
+    if !bin.exists() {
+        fs::copy(env!("CARGO_BIN_EXE_cp"), &bin).unwrap();
+    }
+    fs::set_permissions(e.path(), fs::Permissions::from_mode(0o755)).unwrap();
+    let mut cmd = std::process::Command::new(&bin);
+    unsafe {
+        cmd.pre_exec(|| {
+            nix::unistd::setgid(nix::unistd::Gid::from_raw(65534))?;
+            nix::unistd::setuid(nix::unistd::Uid::from_raw(65534))?;
+            Ok(())
+        });
+    }
+    Command::from_std(cmd)
+}
+
+/// Build library options the same way the CLI does.
+pub fn lib_opts(args: &[&str]) -> cp::options::CopyOptions {
+    use clap::Parser;
+
+    let mut argv = vec!["cp"];
+    argv.extend_from_slice(args);
+    argv.extend_from_slice(&["unused-src", "unused-dst"]);
+    cp::options::CopyOptions::from_cli(&cp::cli::Cli::parse_from(argv))
+}
+
+/// The umask `cp()` inherits from the test process. Read from /proc where
+/// there is one: setting it to read it back races the other test threads.
+pub fn umask() -> u32 {
+    use nix::sys::stat::{Mode, umask};
+
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|s| {
+            s.lines()
+                .find_map(|l| l.strip_prefix("Umask:"))
+                .and_then(|v| u32::from_str_radix(v.trim(), 8).ok())
+        })
+        .unwrap_or_else(|| {
+            let old = umask(Mode::from_bits_truncate(0o022));
+            umask(old);
+            old.bits() as u32
+        })
+}
+
+/// What --debug calls the in-kernel copy: macOS makes it with fcopyfile(3).
+#[cfg(not(target_os = "macos"))]
+pub const KERNEL_COPY: &str = "copy_file_range";
+#[cfg(target_os = "macos")]
+pub const KERNEL_COPY: &str = "fcopyfile";
+
+/// Whether `dir` is on btrfs, the filesystem the FICLONE tests can clone on.
+#[cfg(not(target_os = "macos"))]
+pub fn on_btrfs(dir: &Path) -> bool {
+    nix::sys::statfs::statfs(dir)
+        .is_ok_and(|fs| fs.filesystem_type() == nix::sys::statfs::BTRFS_SUPER_MAGIC)
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_btrfs(_dir: &Path) -> bool {
+    false
+}
+
 // ─── Zero-cost reader helpers ────────────────────────────────────────────────
 
 #[inline]
@@ -3,6 +3,7 @@
 mod common;
 use common::*;
 
+use predicates::prelude::*;
 use std::process::Command as StdCommand;
 
 fn has_gnu_cp() -> bool {
@@ -158,6 +159,219 @@ fn integ_verbose_format() {
     assert!(String::from_utf8_lossy(&out.stdout).contains("->"));
 }
 
+#[test]
+fn integ_verbose_reports_size_method_duration() {
+    let e = Env::new();
+    e.file("small", vec![7u8; 100]);
+    e.file("big", vec![1u8; 3 * 1024 * 1024]);
+
+    for (name, size) in [("small", "100 B"), ("big", "3.0 MiB")] {
+        let out = cp()
+            .arg("-v")
+            .arg(e.p(name))
+            .arg(e.p(&format!("{name}.copy")))
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&out.stdout);
+
+        // '<src>' -> '<dst>' [<size>, <method>, <secs>s]
+        let detail = stdout
+            .trim_end()
+            .rsplit_once(" [")
+            .and_then(|(_, d)| d.strip_suffix(']'))
+            .unwrap_or_else(|| panic!("no detail in {stdout:?}"));
+        let fields: Vec<&str> = detail.split(", ").collect();
+        assert_eq!(fields.len(), 3, "{detail}");
+        assert_eq!(fields[0], size);
+        assert!(!fields[1].is_empty());
+        assert!(fields[2].ends_with('s'));
+        assert!(fields[2].trim_end_matches('s').parse::<f64>().is_ok());
+    }
+}
+
+#[test]
+fn integ_copy_contents_verbose_notes_type_change() {
+    use std::io::Write;
+    let e = Env::new();
+    e.file("src/plain", "regular");
+    let fifo = e.p("src/pipe");
+    let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { nix::libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+
+    // Feeds the FIFO once cp opens it for reading
+    let writer = std::thread::spawn(move || {
+        let mut f = std::fs::OpenOptions::new().write(true).open(fifo).unwrap();
+        f.write_all(b"piped data").unwrap();
+    });
+
+    let out = cp()
+        .arg("-R")
+        .arg("--copy-contents")
+        .arg("-v")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .timeout(std::time::Duration::from_secs(30))
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{out:?}");
+    writer.join().unwrap();
+
+    assert!(
+        std::fs::symlink_metadata(e.p("dst/pipe"))
+            .unwrap()
+            .is_file()
+    );
+    assert_eq!(content(&e.p("dst/pipe")), "piped data");
+    assert_eq!(content(&e.p("dst/plain")), "regular");
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let expected = format!(
+        "'{}' (fifo) -> '{}' (regular file, contents copied)",
+        e.p("src/pipe").display(),
+        e.p("dst/pipe").display()
+    );
+    assert!(stdout.contains(&expected), "{stdout}");
+}
+
+#[test]
+fn integ_verbose_size_annotates_source() {
+    let e = Env::new();
+    e.file("f", vec![1u8; 1536]);
+    e.file("tree/a", vec![2u8; 100]);
+
+    let out = cp()
+        .arg("--verbose-size")
+        .arg(e.p("f"))
+        .arg(e.p("g"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let expected = format!(
+        "'{}' (1.5 KiB) -> '{}'",
+        e.p("f").display(),
+        e.p("g").display()
+    );
+    assert!(stdout.starts_with(&expected), "{stdout}");
+
+    // Directory copies (raw fast path) annotate each file
+    let out = cp()
+        .arg("-R")
+        .arg("--verbose-size")
+        .arg(e.p("tree"))
+        .arg(e.p("tree2"))
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let expected = format!(
+        "'{}' (100 B) -> '{}'",
+        e.p("tree/a").display(),
+        e.p("tree2/a").display()
+    );
+    assert!(stdout.contains(&expected), "{stdout}");
+}
+
+#[test]
+fn integ_stat_file_tab_separated() {
+    let e = Env::new();
+    e.dir("src/sub");
+    e.file("src/a", vec![1u8; 5000]);
+    e.file("src/sub/b", "hello");
+    e.file("src/empty", "");
+
+    cp().arg("-R")
+        .arg(format!("--stat={}", e.p("stats.tsv").display()))
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    let stats = content(&e.p("stats.tsv"));
+    let lines: Vec<&str> = stats.lines().collect();
+    assert_eq!(lines.len(), 3, "{stats}");
+
+    for line in lines {
+        // src  dst  src_size  dst_size  src_mtime  dst_mtime  method  elapsed_ms
+        let f: Vec<&str> = line.split('\t').collect();
+        assert_eq!(f.len(), 8, "{line}");
+        assert!(f[0].contains("/src/"));
+        assert!(f[1].contains("/dst/"));
+        let src_size: u64 = f[2].parse().unwrap();
+        let dst_size: u64 = f[3].parse().unwrap();
+        assert_eq!(src_size, dst_size);
+        f[4].parse::<i64>().unwrap();
+        f[5].parse::<i64>().unwrap();
+        assert!(!f[6].is_empty());
+        f[7].parse::<u64>().unwrap();
+    }
+}
+
+#[test]
+fn integ_stat_to_stdout() {
+    let e = Env::new();
+    e.file("f", "data");
+
+    cp().arg("--stat=-")
+        .arg(e.p("f"))
+        .arg(e.p("g"))
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("\t4\t4\t"));
+}
+
+/// Fields of the single line of a --copy-stat file.
+fn copy_stat_fields(path: &std::path::Path) -> Vec<String> {
+    let stats = content(path);
+    let lines: Vec<&str> = stats.lines().collect();
+    assert_eq!(lines.len(), 1, "{stats}");
+    let f: Vec<String> = lines[0].split('\t').map(String::from).collect();
+    // src/dst pairs: ino, mode, uid, gid, size, mtime; then both paths
+    assert_eq!(f.len(), 14, "{stats}");
+    f
+}
+
+#[test]
+fn integ_copy_stat_preserved_attributes_match() {
+    let e = Env::new();
+    e.file_mode("f", "data", 0o751);
+    e.set_mtime("f", 1_500_000_000);
+
+    cp().arg("--preserve=mode,timestamps")
+        .arg(format!("--copy-stat={}", e.p("attrs.tsv").display()))
+        .arg(e.p("f"))
+        .arg(e.p("g"))
+        .assert()
+        .success();
+
+    let f = copy_stat_fields(&e.p("attrs.tsv"));
+    assert_ne!(f[0], f[1], "inodes");
+    assert_eq!(f[2], "100751");
+    assert_eq!(f[2], f[3], "modes");
+    assert_eq!(f[8], f[9], "sizes");
+    assert_eq!(f[10], "1500000000.000000000");
+    assert_eq!(f[10], f[11], "mtimes");
+    assert!(f[12].ends_with("/f") && f[13].ends_with("/g"));
+}
+
+#[test]
+fn integ_copy_stat_without_preserve_differs() {
+    let e = Env::new();
+    e.file_mode("f", "data", 0o777);
+    e.set_mtime("f", 1_500_000_000);
+    let umask = umask();
+
+    cp().arg(format!("--copy-stat={}", e.p("attrs.tsv").display()))
+        .arg(e.p("f"))
+        .arg(e.p("g"))
+        .assert()
+        .success();
+
+    let f = copy_stat_fields(&e.p("attrs.tsv"));
+    // New files are created 0666 minus the umask (see sec_umask_no_leak)
+    let dst_mode = u32::from_str_radix(&f[3], 8).unwrap();
+    assert_eq!(dst_mode & 0o7777, 0o666 & !umask);
+    let dst_mtime: f64 = f[11].parse().unwrap();
+    assert!(dst_mtime > 1_500_000_000.0 + 1.0, "mtime should be current");
+}
+
 // ─── Exit codes ─────────────────────────────────────────────────────────────
 
 #[test]
@@ -205,6 +419,47 @@ fn integ_multiple_sources_to_dir() {
     }
 }
 
+#[test]
+fn integ_multiple_sources_same_basename() {
+    let e = Env::new();
+    e.dir("dest");
+    e.file("x/f", "from x");
+    e.file("y/f", "from y");
+    e.file("x/sub/a", "a");
+    e.file("y/sub/b", "b");
+
+    // The first copy stays; the second would clobber it
+    cp().arg(e.p("x/f"))
+        .arg(e.p("y/f"))
+        .arg(e.p("dest"))
+        .assert()
+        .code(1)
+        .stderr(format!(
+            "cp: will not overwrite just-created '{}' with '{}'\n",
+            e.p("dest/f").display(),
+            e.p("y/f").display()
+        ));
+    assert_eq!(content(&e.p("dest/f")), "from x");
+
+    // The same file twice is only worth a warning
+    cp().arg(e.p("x/f"))
+        .arg(e.p("x/f"))
+        .arg(e.p("dest"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("specified more than once"));
+
+    // Directories with the same name are merged
+    cp().arg("-R")
+        .arg(e.p("x/sub"))
+        .arg(e.p("y/sub"))
+        .arg(e.p("dest"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("dest/sub/a")), "a");
+    assert_eq!(content(&e.p("dest/sub/b")), "b");
+}
+
 // ─── --version and --help ───────────────────────────────────────────────────
 
 #[test]
@@ -222,3 +477,197 @@ fn integ_version_flag() {
         .success()
         .stdout(predicates::str::contains("cp"));
 }
+
+#[test]
+fn integ_version_file_json() {
+    let e = Env::new();
+    e.file("src", "data");
+    let vf = e.file("version.json", "stale contents that must be replaced");
+
+    cp().arg("--version-file")
+        .arg(&vf)
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    let info: serde_json::Value = serde_json::from_str(&content(&vf)).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert!(info["build"].as_str().unwrap().ends_with('Z'));
+    assert!(!info["target"].as_str().unwrap().is_empty());
+    assert!(info["git"].is_string());
+    assert!(info["features"].is_array());
+    assert_eq!(content(&e.p("dst")), "data");
+}
+
+#[test]
+fn integ_version_file_unwritable_warns_and_copies() {
+    let e = Env::new();
+    e.file("src", "data");
+
+    cp().arg("--version-file")
+        .arg(e.p("missing/version.json"))
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("cannot write version file"));
+
+    assert_eq!(content(&e.p("dst")), "data");
+}
+
+// ─── Error checkpoint ───────────────────────────────────────────────────────
+
+#[test]
+fn integ_error_checkpoint_then_retry() {
+    let e = Env::new();
+    e.file("good", "ok");
+    e.file("tree/f", "in tree");
+    e.dir("dst");
+    let cpf = e.p("failed.jsonl");
+
+    cp().arg("-R")
+        .arg("--error-checkpoint")
+        .arg(&cpf)
+        .arg(e.p("good"))
+        .arg(e.p("late"))
+        .arg(e.p("tree"))
+        .arg(e.p("dst"))
+        .assert()
+        .code(2);
+
+    let entries = cp::checkpoint::read(&cpf).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].src, e.p("late"));
+    assert_eq!(entries[0].dst, e.p("dst/late"));
+    assert_eq!(entries[0].retry, 0);
+    let line: serde_json::Value = serde_json::from_str(content(&cpf).trim()).unwrap();
+    assert!(line["error"].as_str().unwrap().contains("No such file"));
+
+    // Still missing: every attempt is counted and the entry stays
+    cp().arg("--retry-checkpoint")
+        .arg(&cpf)
+        .arg("--retry=3")
+        .assert()
+        .code(2);
+    assert_eq!(cp::checkpoint::read(&cpf).unwrap()[0].retry, 3);
+
+    // The transient failure is gone: the copy completes and the entry is dropped
+    e.file("late", "arrived");
+    cp().arg("--retry-checkpoint").arg(&cpf).assert().success();
+    assert_eq!(content(&e.p("dst/late")), "arrived");
+    assert!(cp::checkpoint::read(&cpf).unwrap().is_empty());
+    assert_eq!(content(&e.p("dst/good")), "ok");
+    assert_eq!(content(&e.p("dst/tree/f")), "in tree");
+}
+
+#[test]
+fn integ_retry_checkpoint_copies_with_recorded_options() {
+    let e = Env::new();
+    e.dir("dst");
+    let cpf = e.p("failed.jsonl");
+
+    cp().arg("-R")
+        .arg("--preserve=timestamps")
+        .arg("--error-checkpoint")
+        .arg(&cpf)
+        .arg(e.p("late"))
+        .arg(e.p("dst"))
+        .assert()
+        .code(2);
+    assert_eq!(
+        cp::checkpoint::read(&cpf).unwrap()[0].flags,
+        ["--recursive", "--preserve=timestamps"]
+    );
+
+    // Retried without options: still a recursive copy keeping mtimes
+    e.file("late/f", "arrived");
+    e.set_mtime("late/f", 1_000_000);
+    let version = e.p("version.json");
+    cp().arg("--retry-checkpoint")
+        .arg(&cpf)
+        .arg("--version-file")
+        .arg(&version)
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("dst/late/f")), "arrived");
+    assert_eq!(mtime(&e.p("dst/late/f")), 1_000_000);
+    assert!(content(&version).contains("\"version\""));
+}
+
+#[test]
+fn integ_retry_checkpoint_non_utf8_name() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let e = Env::new();
+    e.dir("dst");
+    let cpf = e.p("failed.jsonl");
+    // "café" in Latin-1
+    let name = std::ffi::OsStr::from_bytes(b"caf\xe9");
+    let src = e.path().join(name);
+
+    cp().arg("--error-checkpoint")
+        .arg(&cpf)
+        .arg(&src)
+        .arg(e.p("dst"))
+        .assert()
+        .code(2);
+    let entries = cp::checkpoint::read(&cpf).unwrap();
+    assert_eq!(entries[0].src, src);
+    assert_eq!(entries[0].dst, e.p("dst").join(name));
+
+    std::fs::write(&src, "arrived").unwrap();
+    cp().arg("--retry-checkpoint").arg(&cpf).assert().success();
+    assert_eq!(content(&e.p("dst").join(name)), "arrived");
+    assert!(cp::checkpoint::read(&cpf).unwrap().is_empty());
+}
+
+#[test]
+fn integ_error_checkpoint_empty_on_success() {
+    let e = Env::new();
+    e.file("src", "data");
+    let cpf = e.file("failed.jsonl", "stale\n");
+
+    cp().arg("--error-checkpoint")
+        .arg(&cpf)
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&cpf), "");
+}
+
+// ─── Option list ────────────────────────────────────────────────────────────
+
+#[test]
+fn integ_list_options_marks_value_options() {
+    let out = cp().arg("--list-options").output().unwrap();
+    assert!(out.status.success());
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert!(lines.contains(&"--reflink\t-\tvalue"));
+    assert!(lines.contains(&"--recursive\t-R\tflag"));
+    assert!(lines.contains(&"-\t-r\tflag"));
+    assert!(lines.contains(&"--target-directory\t-t\tvalue"));
+    assert!(lines.iter().all(|l| l.split('\t').count() == 3));
+    // Hidden options stay out of completions
+    assert!(!stdout.contains("--selftest"));
+    assert!(!stdout.contains("--list-options"));
+}
+
+// ─── Self test ──────────────────────────────────────────────────────────────
+
+#[test]
+fn integ_selftest_passes() {
+    let e = Env::new();
+    cp().arg("--selftest")
+        .env("TMPDIR", e.path())
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("sparse file"))
+        .stdout(predicates::str::contains("FAILED").not());
+    // Scratch directory is cleaned up
+    assert_eq!(std::fs::read_dir(e.path()).unwrap().count(), 0);
+}
@@ -546,6 +546,35 @@ fn sec_socket_copy_warning() {
     );
 }
 
+#[test]
+fn sec_socket_warning_quiet() {
+    use std::os::unix::net::UnixListener;
+
+    let e = Env::new();
+    e.file("src/f", "data");
+    let _listener = UnixListener::bind(e.p("src/my.sock")).unwrap();
+
+    // Raw fast path, then the walkdir path (-n)
+    for (flags, out) in [("-Rq", "raw"), ("-Rqn", "walk")] {
+        cp().arg(flags)
+            .arg(e.p("src"))
+            .arg(e.p(out))
+            .assert()
+            .success()
+            .stderr("");
+        assert_eq!(content(&e.p(&format!("{out}/f"))), "data");
+        assert!(!e.p(&format!("{out}/my.sock")).exists());
+    }
+
+    // Errors still get through
+    cp().arg("-q")
+        .arg(e.p("missing"))
+        .arg(e.p("dst"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("missing"));
+}
+
 #[test]
 fn sec_same_file_two_symlinks() {
     let e = Env::new();
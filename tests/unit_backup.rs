@@ -242,3 +242,85 @@ fn backup_control_aliases() {
     assert!(!e.p("dst~").exists());
     assert!(!e.p("dst.~1~").exists());
 }
+
+#[test]
+fn backup_with_remove_destination() {
+    let e = Env::new();
+    e.file("src", "new");
+    e.file_mode("dst", "old", 0o444);
+    // dst shares its inode: neither flag may write through the link
+    e.hardlink("dst", "other");
+
+    cp().arg("--backup=simple")
+        .arg("--remove-destination")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst")), "new");
+    assert_eq!(content(&e.p("dst~")), "old");
+    assert_eq!(content(&e.p("other")), "old");
+    assert_eq!(ino(&e.p("dst~")), ino(&e.p("other")));
+}
+
+#[test]
+fn backup_numbered_next_to_dest_in_other_directory() {
+    let e = Env::new();
+    e.file("src/file.txt", "v1");
+    e.file("out/sub/file.txt", "v0");
+    e.dir("work");
+
+    // Run from an unrelated directory with a relative destination
+    for v in ["v1", "v2"] {
+        e.file("src/file.txt", v);
+        cp().current_dir(e.p("work"))
+            .arg("--backup=numbered")
+            .arg("../src/file.txt")
+            .arg("../out/sub")
+            .assert()
+            .success();
+    }
+
+    assert_eq!(content(&e.p("out/sub/file.txt")), "v2");
+    assert_eq!(content(&e.p("out/sub/file.txt.~1~")), "v0");
+    assert_eq!(content(&e.p("out/sub/file.txt.~2~")), "v1");
+    assert_eq!(file_count(&e.p("work")), 0);
+}
+
+#[test]
+fn backup_existing_bare_relative_dest() {
+    let e = Env::new();
+    e.file("src.txt", "v2");
+    e.file("file.txt", "v1");
+    e.file("file.txt.~1~", "v0");
+    // Looks like a backup of file.txt, but belongs to file.txt2
+    e.file("file.txt2.~7~", "other");
+
+    cp().current_dir(e.path())
+        .arg("--backup=existing")
+        .arg("src.txt")
+        .arg("file.txt")
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("file.txt.~2~")), "v1");
+    assert!(!e.p("file.txt~").exists());
+}
+
+#[test]
+fn backup_numbered_skips_past_gaps() {
+    let e = Env::new();
+    e.file("src.txt", "new");
+    e.file("file.txt", "old");
+    e.file("file.txt.~3~", "older");
+
+    cp().arg("--backup=numbered")
+        .arg(e.p("src.txt"))
+        .arg(e.p("file.txt"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("file.txt.~4~")), "old");
+    assert!(!e.p("file.txt.~1~").exists());
+}
@@ -60,6 +60,127 @@ fn copy_update_older_overwrites_older_dest() {
     assert_eq!(content(&e.p("dst")), "new");
 }
 
+#[test]
+fn copy_update_older_compares_symlink_dest_by_target() {
+    let e = Env::new();
+    e.file("src", "new");
+    e.set_mtime("src", 2_000_000);
+    e.file("old_target", "old");
+    e.set_mtime("old_target", 1_000_000);
+    e.file("new_target", "kept");
+    e.set_mtime("new_target", 3_000_000);
+    // The links themselves say the opposite of their targets
+    e.symlink("old_target", "to_old");
+    e.set_symlink_mtime("to_old", 3_000_000);
+    e.symlink("new_target", "to_new");
+    e.set_symlink_mtime("to_new", 1_000_000);
+
+    for dst in ["to_old", "to_new"] {
+        cp().arg("-u")
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .assert()
+            .success();
+        assert!(is_symlink(&e.p(dst)));
+    }
+
+    assert_eq!(content(&e.p("old_target")), "new");
+    assert_eq!(content(&e.p("new_target")), "kept");
+}
+
+#[test]
+fn copy_update_older_timestamp_resolution() {
+    let e = Env::new();
+    // dst as stored on FAT: source mtime rounded down to 2 seconds
+    e.file("src", "same");
+    e.set_mtime_ns("src", 1_000_001, 500_000_000);
+    e.file("dst", "fat copy");
+    e.set_mtime("dst", 1_000_000);
+
+    cp().arg("-u")
+        .arg("--timestamp-resolution=2")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("dst")), "fat copy");
+
+    // Exact comparison sees the source as newer
+    cp().arg("-u")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("dst")), "same");
+}
+
+#[test]
+fn copy_update_older_resolution_still_copies_newer() {
+    let e = Env::new();
+    e.file("src", "newer");
+    e.set_mtime("src", 1_000_004);
+    e.file("dst", "old");
+    e.set_mtime("dst", 1_000_000);
+
+    cp().arg("-u")
+        .arg("--timestamp-resolution=2")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("dst")), "newer");
+}
+
+#[test]
+fn copy_size_only_skips_same_size() {
+    let e = Env::new();
+    e.file("dst", "old!");
+    e.set_mtime("dst", 1_000_000); // older than src: -u would copy
+    e.file("src", "new!");
+
+    cp().arg("--size-only")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst")), "old!");
+}
+
+#[test]
+fn copy_size_only_copies_different_size() {
+    let e = Env::new();
+    e.file("dst", "old");
+    e.file("src", "newer");
+
+    cp().arg("--size-only")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst")), "newer");
+}
+
+#[test]
+fn copy_size_only_recursive() {
+    let e = Env::new();
+    e.file("src/same", "AAAA");
+    e.file("src/diff", "longer content");
+    e.file("dst/src/same", "BBBB");
+    e.file("dst/src/diff", "short");
+
+    cp().arg("-R")
+        .arg("--size-only")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/src/same")), "BBBB");
+    assert_eq!(content(&e.p("dst/src/diff")), "longer content");
+}
+
 #[test]
 fn copy_force_removes_readonly() {
     let e = Env::new();
@@ -177,6 +298,24 @@ fn copy_dir_without_recursive() {
         .stderr(predicates::str::contains("omitting directory"));
 }
 
+#[test]
+fn copy_dir_without_recursive_others_still_copied() {
+    let e = Env::new();
+    e.dir("mydir");
+    e.file("file.txt", "payload");
+    e.dir("dest");
+
+    cp().arg(e.p("mydir"))
+        .arg(e.p("file.txt"))
+        .arg(e.p("dest"))
+        .assert()
+        .code(1)
+        .stderr(predicates::str::contains("omitting directory"));
+
+    assert_eq!(content(&e.p("dest/file.txt")), "payload");
+    assert!(!e.p("dest/mydir").exists());
+}
+
 #[test]
 fn copy_verbose() {
     let e = Env::new();
@@ -272,6 +411,43 @@ fn copy_update_all_always_copies() {
     assert_eq!(content(&e.p("dst")), "old_src");
 }
 
+#[test]
+fn copy_update_older_or_same_size() {
+    let e = Env::new();
+    e.file("src", "new content");
+    e.set_mtime("src", 2_000_000);
+    // Older, but the same size: skipped even though the content differs
+    e.file("same_size", "old content");
+    e.set_mtime("same_size", 1_000_000);
+    // Older and a different size: copied
+    e.file("resized", "old");
+    e.set_mtime("resized", 1_000_000);
+    // Newer: skipped whatever the size
+    e.file("newer", "newer, longer content");
+    e.set_mtime("newer", 3_000_000);
+
+    for dst in ["same_size", "resized", "newer", "missing"] {
+        cp().arg("--update=older-or-same-size")
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .assert()
+            .success();
+    }
+
+    assert_eq!(content(&e.p("same_size")), "old content");
+    assert_eq!(content(&e.p("resized")), "new content");
+    assert_eq!(content(&e.p("newer")), "newer, longer content");
+    assert_eq!(content(&e.p("missing")), "new content");
+
+    // Plain -u copies the same-size file
+    cp().arg("-u")
+        .arg(e.p("src"))
+        .arg(e.p("same_size"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("same_size")), "new content");
+}
+
 #[test]
 fn copy_attributes_only_creates_empty() {
     let e = Env::new();
@@ -309,6 +485,29 @@ fn copy_attributes_only_preserves_existing() {
     assert_eq!(mode(&e.p("dst")), 0o741);
 }
 
+#[test]
+fn copy_attributes_only_new_dest_takes_source_mode() {
+    let e = Env::new();
+    e.file_mode("src", "src content", 0o751);
+    e.file_mode("existing", "kept", 0o600);
+    let umask = umask();
+
+    for dst in ["new", "existing"] {
+        cp().arg("--attributes-only")
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .assert()
+            .success();
+    }
+
+    // As GNU: a created file gets the source mode less the umask, an
+    // existing one keeps its own without --preserve=mode
+    assert_eq!(file_size(&e.p("new")), 0);
+    assert_eq!(mode(&e.p("new")), 0o751 & !umask);
+    assert_eq!(mode(&e.p("existing")), 0o600);
+    assert_eq!(content(&e.p("existing")), "kept");
+}
+
 #[test]
 fn copy_symlink_to_dir_without_r() {
     let e = Env::new();
@@ -429,6 +628,54 @@ fn copy_dangling_symlink_dest_force_succeeds() {
     assert_eq!(content(&e.p("dst")), "content");
 }
 
+#[test]
+fn copy_dangling_symlink_dest_matrix() {
+    // -P/-L pick how *source* symlinks are read: a dangling destination is
+    // refused either way, with GNU's message
+    for flag in ["-P", "-L", "-H"] {
+        let e = Env::new();
+        e.file("src", "content");
+        e.symlink(e.p("target"), "dst");
+
+        cp().arg(flag)
+            .arg(e.p("src"))
+            .arg(e.p("dst"))
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(format!(
+                "not writing through dangling symlink '{}'",
+                e.p("dst").display()
+            )));
+        assert!(is_symlink(&e.p("dst")), "{flag}");
+        assert!(!e.p("target").exists(), "{flag}");
+    }
+
+    // -f writes through the link: the target is created, the link kept
+    let e = Env::new();
+    e.file("src", "content");
+    e.symlink(e.p("target"), "dst");
+    cp().arg("-Pf")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert!(is_symlink(&e.p("dst")));
+    assert_eq!(content(&e.p("target")), "content");
+
+    // --remove-destination replaces the link itself
+    let e = Env::new();
+    e.file("src", "content");
+    e.symlink(e.p("target"), "dst");
+    cp().arg("--remove-destination")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert!(!is_symlink(&e.p("dst")));
+    assert_eq!(content(&e.p("dst")), "content");
+    assert!(!e.p("target").exists());
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // Interactive mode tests
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -439,8 +686,9 @@ fn copy_interactive_no_input_skips() {
     e.file("src", "new");
     e.file("dst", "keep_me");
 
-    // -i with piped stdin (no TTY) → reads EOF → no overwrite
-    cp().arg("-i")
+    // -i with piped stdin and no controlling terminal → reads EOF → no overwrite
+    cp_no_tty()
+        .arg("-i")
         .arg(e.p("src"))
         .arg(e.p("dst"))
         .write_stdin("")
@@ -450,13 +698,48 @@ fn copy_interactive_no_input_skips() {
     assert_eq!(content(&e.p("dst")), "keep_me");
 }
 
+#[test]
+fn copy_interactive_reads_tty_when_stdin_is_redirected() {
+    let e = Env::new();
+    e.file("src", "new");
+    e.file("dst", "old");
+
+    // script(1) runs cp on a fresh pseudo-terminal and types our stdin into
+    // it; cp's own stdin is /dev/null, so only the tty can say "y"
+    let cmd = format!(
+        "'{}' -i '{}' '{}' < /dev/null",
+        env!("CARGO_BIN_EXE_cp"),
+        e.p("src").display(),
+        e.p("dst").display()
+    );
+    let Ok(out) = Command::new("script")
+        .args(["-qec", &cmd, "/dev/null"])
+        .write_stdin("y\n")
+        .timeout(std::time::Duration::from_secs(10))
+        .output()
+    else {
+        eprintln!("SKIP: script(1) not available");
+        return;
+    };
+    if !out.status.success() {
+        eprintln!(
+            "SKIP: no pseudo-terminal: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        return;
+    }
+
+    assert_eq!(content(&e.p("dst")), "new");
+}
+
 #[test]
 fn copy_interactive_yes_overwrites() {
     let e = Env::new();
     e.file("src", "new");
     e.file("dst", "old");
 
-    cp().arg("-i")
+    cp_no_tty()
+        .arg("-i")
         .arg(e.p("src"))
         .arg(e.p("dst"))
         .write_stdin("y\n")
@@ -472,7 +755,8 @@ fn copy_interactive_no_preserves() {
     e.file("src", "new");
     e.file("dst", "old");
 
-    cp().arg("-i")
+    cp_no_tty()
+        .arg("-i")
         .arg(e.p("src"))
         .arg(e.p("dst"))
         .write_stdin("n\n")
@@ -504,7 +788,8 @@ fn copy_interactive_locale_oui() {
     e.file("dst", "old");
 
     // "oui" should be accepted as affirmative
-    cp().arg("-i")
+    cp_no_tty()
+        .arg("-i")
         .arg(e.p("src"))
         .arg(e.p("dst"))
         .write_stdin("oui\n")
@@ -521,7 +806,8 @@ fn copy_interactive_locale_ja() {
     e.file("dst", "old");
 
     // "ja" should be accepted as affirmative
-    cp().arg("-i")
+    cp_no_tty()
+        .arg("-i")
         .arg(e.p("src"))
         .arg(e.p("dst"))
         .write_stdin("ja\n")
@@ -652,3 +938,336 @@ fn copy_backup_verbose() {
     assert_eq!(content(&e.p("dst")), "new");
     assert_eq!(content(&e.p("dst~")), "old");
 }
+
+#[test]
+fn copy_into_existing_fifo_writes_through() {
+    use std::io::Read;
+    use std::os::unix::fs::FileTypeExt;
+
+    let e = Env::new();
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    std::fs::write(e.p("src"), &data).unwrap();
+
+    let fifo_path = e.p("fifo");
+    let c_path = std::ffi::CString::new(fifo_path.to_str().unwrap()).unwrap();
+    let ret = unsafe { nix::libc::mkfifo(c_path.as_ptr(), 0o644) };
+    assert_eq!(ret, 0, "mkfifo failed");
+
+    // Drain the FIFO while cp writes into it
+    let reader_path = fifo_path.clone();
+    let reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        std::fs::File::open(reader_path)
+            .unwrap()
+            .read_to_end(&mut buf)
+            .unwrap();
+        buf
+    });
+
+    cp().arg(e.p("src")).arg(&fifo_path).assert().success();
+
+    assert_eq!(reader.join().unwrap(), data);
+    assert!(
+        std::fs::symlink_metadata(&fifo_path)
+            .unwrap()
+            .file_type()
+            .is_fifo()
+    );
+}
+
+// ─── --link-threshold ───────────────────────────────────────────────────────
+
+/// A file of `size` bytes, sparse so that it costs no disk space.
+fn sized_file(e: &Env, rel: &str, size: u64) {
+    let f = std::fs::File::create(e.p(rel)).unwrap();
+    f.set_len(size).unwrap();
+}
+
+#[test]
+fn link_threshold_links_large_files_only() {
+    let e = Env::new();
+    sized_file(&e, "big", 100 << 20);
+    sized_file(&e, "small", 10 << 20);
+    e.dir("out");
+
+    cp().arg("--link-threshold")
+        .arg("50M")
+        .arg(e.p("big"))
+        .arg(e.p("small"))
+        .arg(e.p("out"))
+        .assert()
+        .success();
+
+    assert_eq!(ino(&e.p("out/big")), ino(&e.p("big")));
+    assert_ne!(ino(&e.p("out/small")), ino(&e.p("small")));
+    assert_eq!(file_size(&e.p("out/small")), 10 << 20);
+}
+
+#[test]
+fn link_threshold_replaces_existing_destination() {
+    let e = Env::new();
+    sized_file(&e, "big", 2 << 20);
+    e.file("dst", "old");
+
+    cp().arg("--link-threshold=1MiB")
+        .arg(e.p("big"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(ino(&e.p("dst")), ino(&e.p("big")));
+}
+
+#[test]
+fn link_threshold_in_recursive_copy() {
+    let e = Env::new();
+    e.dir("src");
+    sized_file(&e, "src/big", 2 << 20);
+    e.file("src/small", "x");
+
+    cp().arg("-R")
+        .arg("--link-threshold=1M")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(ino(&e.p("dst/big")), ino(&e.p("src/big")));
+    assert_ne!(ino(&e.p("dst/small")), ino(&e.p("src/small")));
+}
+
+#[test]
+fn link_threshold_rejects_bad_size() {
+    cp().arg("--link-threshold=12Q")
+        .arg("a")
+        .arg("b")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid size"));
+}
+
+// ─── Files reporting size 0 are read to EOF (/proc, /sys) ───────────────────
+
+#[test]
+fn copy_proc_file_reads_to_eof() {
+    let src = std::path::Path::new("/proc/self/status");
+    if !src.exists() {
+        eprintln!("SKIP: no /proc");
+        return;
+    }
+    assert_eq!(file_size(src), 0);
+    let e = Env::new();
+
+    cp().arg(src).arg(e.p("status")).assert().success();
+    assert!(content(&e.p("status")).contains("Name:"));
+
+    // Raw directory fast path: copy_file_range copies nothing from procfs
+    cp().arg("-R")
+        .arg("/proc/self/net")
+        .arg(e.p("net"))
+        .assert()
+        .success();
+    assert!(content(&e.p("net/unix")).starts_with("Num"));
+}
+
+#[test]
+fn copy_read_error_removes_partial_dest() {
+    use std::os::unix::io::AsRawFd;
+
+    // Reading /proc/self/mem from offset 0 fails with EIO
+    let mem = std::path::Path::new("/proc/self/mem");
+    if !mem.exists() {
+        eprintln!("SKIP: no /proc");
+        return;
+    }
+    let e = Env::new();
+    e.file("existing", "old data");
+
+    cp().arg(mem)
+        .arg(e.p("new"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("Input/output error"));
+    assert!(!e.p("new").exists());
+
+    // An existing destination is truncated but not removed
+    cp().arg(mem).arg(e.p("existing")).assert().failure();
+    assert_eq!(content(&e.p("existing")), "");
+
+    // Raw fd path (copy_and_close), through the library
+    let proc_self = std::fs::File::open("/proc/self").unwrap();
+    let dst_dir = std::fs::File::open(e.path()).unwrap();
+    let opts = {
+        use clap::Parser;
+        cp::options::CopyOptions::from_cli(&cp::cli::Cli::parse_from(["cp", "s", "d"]))
+    };
+    assert!(
+        cp::copy_at(
+            proc_self.as_raw_fd(),
+            std::path::Path::new("mem"),
+            dst_dir.as_raw_fd(),
+            std::path::Path::new("raw"),
+            &opts,
+        )
+        .is_err()
+    );
+    assert!(!e.p("raw").exists());
+}
+
+#[test]
+fn copy_dir_across_filesystems() {
+    let shm = std::path::Path::new("/dev/shm");
+    let e = Env::new();
+    e.file("src/a", vec![3u8; 300_000]);
+    e.file("src/sub/b", "small");
+    let dev =
+        |p: &std::path::Path| std::os::unix::fs::MetadataExt::dev(&std::fs::metadata(p).unwrap());
+    if !shm.is_dir() || dev(shm) == dev(e.path()) {
+        eprintln!("SKIP: no second filesystem");
+        return;
+    }
+    let dst = tempfile::TempDir::new_in(shm).unwrap();
+
+    // copy_file_range fails with EXDEV there on recent kernels
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(dst.path().join("out"))
+        .assert()
+        .success();
+    assert_eq!(bytes(&dst.path().join("out/a")), vec![3u8; 300_000]);
+    assert_eq!(content(&dst.path().join("out/sub/b")), "small");
+}
+
+// ─── --hardlink-across-sources ──────────────────────────────────────────────
+
+#[test]
+fn copy_hardlink_across_sources() {
+    let e = Env::new();
+    e.file("a/x", "same data");
+    e.file("b/y", "same data");
+    e.file("b/other", "other content");
+    e.file("b/z", "SAME DATA");
+    for f in ["a/x", "b/y", "b/other", "b/z"] {
+        e.set_mtime(f, 1_600_000_000);
+    }
+    e.dir("dst");
+
+    cp().arg("-R")
+        .arg("--hardlink-across-sources")
+        .arg(e.p("a"))
+        .arg(e.p("b"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(ino(&e.p("dst/a/x")), ino(&e.p("dst/b/y")));
+    assert_ne!(ino(&e.p("dst/a/x")), ino(&e.p("dst/b/other")));
+    // Same size and mtime is not enough: the bytes are compared by default
+    assert_ne!(ino(&e.p("dst/a/x")), ino(&e.p("dst/b/z")));
+    assert_eq!(content(&e.p("dst/b/z")), "SAME DATA");
+    assert_eq!(content(&e.p("dst/b/y")), "same data");
+    // Linking among the copies, never to the sources
+    assert_ne!(ino(&e.p("dst/a/x")), ino(&e.p("a/x")));
+}
+
+#[test]
+fn copy_hardlink_across_sources_content() {
+    let e = Env::new();
+    e.file("a/x", "data one");
+    e.file("b/x", "data two");
+    e.file("c/x", "data one");
+    for f in ["a/x", "b/x", "c/x"] {
+        e.set_mtime(f, 1_600_000_000);
+    }
+    e.dir("dst");
+
+    // Same size and mtime throughout: only the bytes tell them apart
+    cp().arg("-R")
+        .arg("--hardlink-across-sources=content")
+        .arg(e.p("a"))
+        .arg(e.p("b"))
+        .arg(e.p("c"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_ne!(ino(&e.p("dst/a/x")), ino(&e.p("dst/b/x")));
+    assert_eq!(ino(&e.p("dst/a/x")), ino(&e.p("dst/c/x")));
+    assert_eq!(content(&e.p("dst/b/x")), "data two");
+}
+
+#[test]
+fn no_clobber_fail_on_skip() {
+    let e = Env::new();
+    e.file("src.txt", "new");
+    e.file("existing.txt", "old");
+
+    // Plain -n still succeeds
+    cp().arg("-n")
+        .arg(e.p("src.txt"))
+        .arg(e.p("existing.txt"))
+        .assert()
+        .success();
+
+    cp().args(["-n", "--fail-on-skip"])
+        .arg(e.p("src.txt"))
+        .arg(e.p("existing.txt"))
+        .assert()
+        .code(1);
+    assert_eq!(content(&e.p("existing.txt")), "old");
+
+    cp().args(["-n", "--fail-on-skip=3"])
+        .arg(e.p("src.txt"))
+        .arg(e.p("existing.txt"))
+        .assert()
+        .code(3);
+
+    cp().args(["-n", "--fail-on-skip"])
+        .arg(e.p("src.txt"))
+        .arg(e.p("fresh.txt"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("fresh.txt")), "new");
+
+    // A skip anywhere in a tree counts
+    e.file("tree/a", "a");
+    e.file("tree/sub/b", "b");
+    e.file("out/tree/sub/b", "kept");
+    cp().args(["-Rn", "--fail-on-skip=4"])
+        .arg(e.p("tree"))
+        .arg(e.p("out"))
+        .assert()
+        .code(4);
+    assert_eq!(content(&e.p("out/tree/a")), "a");
+    assert_eq!(content(&e.p("out/tree/sub/b")), "kept");
+}
+
+#[test]
+fn make_parents_creates_destination_directories() {
+    let e = Env::new();
+    e.file("src.txt", "data");
+    e.file("tree/f", "in tree");
+
+    cp().arg("-D")
+        .arg(e.p("src.txt"))
+        .arg(e.p("a/b/c/dst.txt"))
+        .assert()
+        .success();
+    assert!(e.p("a/b/c").is_dir());
+    assert_eq!(content(&e.p("a/b/c/dst.txt")), "data");
+
+    // A directory source lands at DEST itself, under new parents
+    cp().args(["-R", "--make-parents"])
+        .arg(e.p("tree"))
+        .arg(e.p("x/y/copy"))
+        .assert()
+        .success();
+    assert_eq!(content(&e.p("x/y/copy/f")), "in tree");
+
+    // Without -D a missing parent is still an error
+    cp().arg(e.p("src.txt"))
+        .arg(e.p("missing/dst.txt"))
+        .assert()
+        .failure();
+    assert!(!e.p("missing").exists());
+}
@@ -100,6 +100,76 @@ fn dir_parents_preserves_metadata() {
     assert_eq!(mtime(&sub_dir), 1_500_000_000);
 }
 
+#[test]
+fn dir_parents_onto_itself_is_same_file() {
+    let e = Env::new();
+    e.file("a/b/f", "content");
+    std::os::unix::fs::symlink(".", e.p("here")).unwrap();
+
+    // a/b/f under "." (or a link to it) is a/b/f itself
+    for dest in [".", "here"] {
+        cp().current_dir(e.path())
+            .arg("--parents")
+            .arg("a/b/f")
+            .arg(dest)
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("are the same file"));
+    }
+
+    assert_eq!(content(&e.p("a/b/f")), "content");
+    assert_eq!(file_count(&e.p("a")), 1);
+    assert_eq!(file_count(&e.p("a/b")), 1);
+    assert_eq!(file_count(e.path()), 2);
+}
+
+#[test]
+fn dir_relative_source_errors_name_absolute_paths() {
+    let e = Env::new();
+    e.file("src/ok", "fine");
+    e.file_mode("src/secret", "hidden", 0o000);
+    e.dir("out");
+    e.chmod("out", 0o777);
+
+    // Root reads mode-000 files
+    cp_unprivileged(&e)
+        .current_dir(e.path())
+        .args(["-R", "src", "out/"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(format!(
+            "'{}'",
+            e.p("src/secret").display()
+        )));
+
+    e.chmod("src/secret", 0o644); // cleanup
+}
+
+/// Only the message is absolute: patterns match, and -v names, the source
+/// as given.
+#[test]
+fn dir_relative_source_ignore_errors_from_matches_as_given() {
+    let e = Env::new();
+    e.file("src/ok", "fine");
+    e.file_mode("src/bad.dat", "hidden", 0o000);
+    e.dir("out");
+    e.chmod("out", 0o777);
+
+    cp_unprivileged(&e)
+        .current_dir(e.path())
+        .args(["-R", "-v", "--ignore-errors-from=src/*.dat", "src", "out/"])
+        .assert()
+        .success()
+        .stdout(predicates::str::contains("'src/ok' -> 'out/src/ok'"))
+        .stderr(predicates::str::contains(format!(
+            "'{}'",
+            e.p("src/bad.dat").display()
+        )));
+    assert_eq!(content(&e.p("out/src/ok")), "fine");
+
+    e.chmod("src/bad.dat", 0o644); // cleanup
+}
+
 #[test]
 fn dir_no_target_directory() {
     let e = Env::new();
@@ -130,6 +200,51 @@ fn dir_preserve_hard_links() {
     assert_eq!(ino(&e.p("dst/a")), ino(&e.p("dst/b")));
 }
 
+#[test]
+fn dir_verbose_reports_hard_links() {
+    let e = Env::new();
+    // small/ stays sequential, big/ (4 × 20 names) takes the parallel path
+    e.file("src/small/a", "shared");
+    e.hardlink("src/small/a", "src/small/b");
+    for i in 0..4 {
+        e.file(&format!("src/big/f{i}_00"), format!("data {i}"));
+        for j in 1..20 {
+            e.hardlink(&format!("src/big/f{i}_00"), &format!("src/big/f{i}_{j:02}"));
+        }
+    }
+
+    // Raw fast path, then walkdir (--backup with nothing to back up)
+    for (extra, dst) in [(None, "fast"), (Some("--backup=numbered"), "walk")] {
+        let out = cp()
+            .arg("-av")
+            .args(extra)
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let linked: Vec<_> = stdout
+            .lines()
+            .filter(|l| l.starts_with("hard linked '"))
+            .collect();
+        assert_eq!(linked.len(), 1 + 4 * 19, "{dst}:\n{stdout}");
+        assert!(
+            linked
+                .iter()
+                .all(|l| l.contains(&format!("' => '{}", e.p(dst).display()))),
+            "{dst}"
+        );
+        // Each inode is copied exactly once
+        let small = format!("' -> '{}/", e.p(&format!("{dst}/small")).display());
+        assert_eq!(stdout.matches(&small).count(), 1, "{dst}:\n{stdout}");
+        assert_eq!(
+            ino(&e.p(&format!("{dst}/small/a"))),
+            ino(&e.p(&format!("{dst}/small/b")))
+        );
+    }
+}
+
 #[test]
 fn dir_copy_into_self() {
     let e = Env::new();
@@ -282,6 +397,35 @@ fn dir_raw_path_with_fifo() {
     );
 }
 
+#[test]
+#[cfg(target_os = "macos")]
+fn dir_raw_path_skips_whiteout() {
+    use std::ffi::CString;
+
+    /// S_IFWHT (<sys/stat.h>; not in libc)
+    const S_IFWHT: nix::libc::mode_t = 0o160000;
+
+    let e = Env::new();
+    e.file("src/regular.txt", "hello");
+
+    // Only the upper layer of a union mount takes a whiteout
+    let c_path = CString::new(e.p("src/gone").to_str().unwrap()).unwrap();
+    if unsafe { nix::libc::mknod(c_path.as_ptr(), S_IFWHT, 0) } != 0 {
+        eprintln!("SKIP: whiteouts not supported here");
+        return;
+    }
+
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("skipping whiteout"));
+
+    assert_eq!(content(&e.p("dst/regular.txt")), "hello");
+    assert!(e.p("dst/gone").symlink_metadata().is_err());
+}
+
 #[test]
 fn dir_progress_recursive() {
     let e = Env::new();
@@ -300,6 +444,81 @@ fn dir_progress_recursive() {
     assert_eq!(file_count(&e.p("dst")), 10);
 }
 
+#[test]
+fn dir_progress_output_file() {
+    let e = Env::new();
+    for i in 0..10 {
+        e.file(&format!("src/f_{i}.txt"), format!("data_{i}"));
+    }
+
+    // A file target is drawn to without a TTY, escapes and all
+    cp().arg("-R")
+        .arg("--progress")
+        .arg(format!(
+            "--progress-output=file:{}",
+            e.p("progress.log").display()
+        ))
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr("");
+
+    let log = content(&e.p("progress.log"));
+    assert!(log.contains("10 files copied"), "{log:?}");
+    assert!(log.contains("\x1b["), "{log:?}");
+    assert_eq!(file_count(&e.p("dst")), 10);
+
+    cp().arg("--progress-output=tty")
+        .arg(e.p("src/f_0.txt"))
+        .arg(e.p("x"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid progress output"));
+}
+
+#[test]
+fn progress_plain_without_tty() {
+    let e = Env::new();
+    e.file("big", vec![7u8; 8 * 1024 * 1024]);
+    e.file("src/a", "a");
+
+    // stderr is a pipe here: the bar stays hidden, plain lines don't
+    let out = cp()
+        .arg("--progress=plain")
+        .arg(e.p("big"))
+        .arg(e.p("big.copy"))
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr
+            .lines()
+            .last()
+            .unwrap_or("")
+            .contains("8.00 MiB/8.00 MiB (100%"),
+        "{stderr:?}"
+    );
+    assert!(!stderr.contains('\x1b'), "{stderr:?}");
+
+    cp().arg("-R")
+        .arg("--progress=plain")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("1 files copied"));
+
+    let out = cp()
+        .arg("--progress")
+        .arg(e.p("big"))
+        .arg(e.p("big.bar"))
+        .output()
+        .unwrap();
+    assert!(out.stderr.is_empty());
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // -R combination tests
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -321,7 +540,8 @@ fn dir_recursive_with_interactive() {
     e.file("src/a.txt", "new_aaa");
 
     // -R -i with "y\n" for each file
-    cp().arg("-R")
+    cp_no_tty()
+        .arg("-R")
         .arg("-i")
         .arg(e.p("src"))
         .arg(e.p("dst"))
@@ -453,3 +673,1167 @@ fn dir_raw_preserves_xattr() {
     let val = xattr::get(&dst_path, "user.test").unwrap();
     assert_eq!(val, Some(b"value".to_vec()));
 }
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Library API: copy_at with raw directory fds
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn dir_copy_at_with_dir_fds() {
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    let e = Env::new();
+    e.file("root_a/tree/f1.txt", "one");
+    e.file("root_a/tree/sub/f2.txt", "two");
+    e.hardlink("root_a/tree/f1.txt", "root_a/tree/sub/f1_link.txt");
+    e.dir("root_b");
+
+    let src_dir = std::fs::File::open(e.p("root_a")).unwrap();
+    let dst_dir = std::fs::File::open(e.p("root_b")).unwrap();
+
+    cp::copy_at(
+        src_dir.as_raw_fd(),
+        Path::new("tree"),
+        dst_dir.as_raw_fd(),
+        Path::new("copy"),
+        &lib_opts(&["-a"]),
+    )
+    .unwrap();
+
+    assert_eq!(content(&e.p("root_b/copy/f1.txt")), "one");
+    assert_eq!(content(&e.p("root_b/copy/sub/f2.txt")), "two");
+    assert_eq!(
+        ino(&e.p("root_b/copy/f1.txt")),
+        ino(&e.p("root_b/copy/sub/f1_link.txt"))
+    );
+}
+
+#[test]
+fn dir_copy_at_regular_file_renamed() {
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    let e = Env::new();
+    e.file("a/src.txt", "payload");
+    e.dir("b");
+
+    let src_dir = std::fs::File::open(e.p("a")).unwrap();
+    let dst_dir = std::fs::File::open(e.p("b")).unwrap();
+
+    cp::copy_at(
+        src_dir.as_raw_fd(),
+        Path::new("src.txt"),
+        dst_dir.as_raw_fd(),
+        Path::new("dst.txt"),
+        &lib_opts(&[]),
+    )
+    .unwrap();
+
+    assert_eq!(content(&e.p("b/dst.txt")), "payload");
+    assert!(!e.p("b/src.txt").exists());
+}
+
+#[test]
+fn dir_copy_at_symlink_renamed() {
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    let e = Env::new();
+    e.file("a/target.txt", "payload");
+    e.symlink("target.txt", "a/link");
+    e.set_symlink_mtime("a/link", 1_000_000);
+    e.file("a/tree/f.txt", "x");
+    e.symlink("f.txt", "a/tree/inner");
+    e.set_symlink_mtime("a/tree/inner", 2_000_000);
+    e.set_mtime("a/tree", 3_000_000);
+    e.dir("b");
+
+    let src_dir = std::fs::File::open(e.p("a")).unwrap();
+    let dst_dir = std::fs::File::open(e.p("b")).unwrap();
+    let copy_at = |src: &str, dst: &str| {
+        cp::copy_at(
+            src_dir.as_raw_fd(),
+            Path::new(src),
+            dst_dir.as_raw_fd(),
+            Path::new(dst),
+            &lib_opts(&["-a"]),
+        )
+    };
+
+    // The link lands under the destination name, with its own mtime
+    copy_at("link", "renamed").unwrap();
+    assert_eq!(link_target(&e.p("b/renamed")), Path::new("target.txt"));
+    assert_eq!(symlink_mtime(&e.p("b/renamed")), 1_000_000);
+    assert!(e.p("b/link").symlink_metadata().is_err());
+
+    // Metadata applied once the tree is copied resolves against the fds too
+    copy_at("tree", "tree_copy").unwrap();
+    assert_eq!(link_target(&e.p("b/tree_copy/inner")), Path::new("f.txt"));
+    assert_eq!(symlink_mtime(&e.p("b/tree_copy/inner")), 2_000_000);
+    assert_eq!(mtime(&e.p("b/tree_copy")), 3_000_000);
+
+    // Messages name the entries as given, relative to the fds
+    let err = copy_at("missing", "x").unwrap_err();
+    assert!(err.to_string().contains("'missing'"), "{err}");
+}
+
+#[test]
+fn dir_copy_at_directory_requires_recursive() {
+    use std::os::unix::io::AsRawFd;
+    use std::path::Path;
+
+    let e = Env::new();
+    e.file("a/tree/f.txt", "x");
+    e.dir("b");
+
+    let src_dir = std::fs::File::open(e.p("a")).unwrap();
+    let dst_dir = std::fs::File::open(e.p("b")).unwrap();
+
+    let err = cp::copy_at(
+        src_dir.as_raw_fd(),
+        Path::new("tree"),
+        dst_dir.as_raw_fd(),
+        Path::new("tree"),
+        &lib_opts(&[]),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("omitting directory"));
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// -x: destination nested inside the source
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn dir_one_fs_skips_nested_destination() {
+    let e = Env::new();
+    e.file("src/f.txt", "data");
+    e.file("src/sub/g.txt", "more");
+    // Reach src through an alias so the path-based copy-into-self check can't see it
+    e.symlink("src", "alias");
+
+    cp().arg("-Rx")
+        .arg(e.p("src"))
+        .arg(e.p("alias/backup"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("src/backup/f.txt")), "data");
+    assert_eq!(content(&e.p("src/backup/sub/g.txt")), "more");
+    assert!(!e.p("src/backup/backup").exists());
+}
+
+#[test]
+fn dir_one_fs_skips_nested_destination_slow_path() {
+    let e = Env::new();
+    e.file("src/f.txt", "data");
+    e.file("src/sub/g.txt", "more");
+    e.symlink("src", "alias");
+
+    // -n forces the walkdir path
+    cp().arg("-Rxn")
+        .arg(e.p("src"))
+        .arg(e.p("alias/backup"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("src/backup/f.txt")), "data");
+    assert_eq!(content(&e.p("src/backup/sub/g.txt")), "more");
+    assert!(!e.p("src/backup/backup").exists());
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// Raw path: many directories (one directory stream per level)
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn dir_raw_many_directories() {
+    let e = Env::new();
+    for i in 0..40 {
+        for j in 0..10 {
+            e.file(&format!("src/d_{i:02}/s_{j}/f.txt"), format!("{i}-{j}"));
+        }
+    }
+
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(file_count(&e.p("dst")), 40);
+    for (i, j) in [(0, 0), (17, 3), (39, 9)] {
+        assert_eq!(
+            content(&e.p(&format!("dst/d_{i:02}/s_{j}/f.txt"))),
+            format!("{i}-{j}")
+        );
+    }
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// --atomic-dir
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn dir_atomic_replaces_existing_tree() {
+    let e = Env::new();
+    e.file("src/new.txt", "new");
+    e.file("src/sub/inner.txt", "inner");
+    e.file("dst/src/stale.txt", "stale");
+
+    cp().arg("-R")
+        .arg("--atomic-dir")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/src/new.txt")), "new");
+    assert_eq!(content(&e.p("dst/src/sub/inner.txt")), "inner");
+    assert!(!e.p("dst/src/stale.txt").exists());
+    // No leftover temporary directory next to the replaced tree
+    assert_eq!(file_count(&e.p("dst")), 1);
+}
+
+#[test]
+fn dir_atomic_keeps_old_tree_as_backup() {
+    let e = Env::new();
+    e.file("src/new.txt", "new");
+    e.file("dst/src/stale.txt", "stale");
+
+    cp().arg("-R")
+        .arg("--atomic-dir")
+        .arg("--backup=simple")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/src/new.txt")), "new");
+    assert!(!e.p("dst/src/stale.txt").exists());
+    assert_eq!(content(&e.p("dst/src~/stale.txt")), "stale");
+}
+
+#[test]
+fn dir_atomic_destination_never_partial() {
+    use std::os::unix::fs::MetadataExt;
+
+    let e = Env::new();
+    for i in 0..2000 {
+        e.file(&format!("src/f{i}.txt"), "new");
+    }
+    for i in 0..20 {
+        e.file(&format!("dst/src/old{i}.txt"), "old");
+    }
+    let target = e.p("dst/src");
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_cp"))
+        .arg("-R")
+        .arg("--atomic-dir")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .spawn()
+        .unwrap();
+
+    // Poll like a concurrent `ls`: the destination is always there, and
+    // always the whole old tree or the whole new one. A listing the swap
+    // happened during (the inode changed) may have read the old tree as it
+    // was being removed, so it doesn't count
+    let ino = |p: &std::path::Path| std::fs::metadata(p).expect("destination missing").ino();
+    let mut samples = 0;
+    let status = loop {
+        let before = ino(&target);
+        let entries = std::fs::read_dir(&target).unwrap().count();
+        if ino(&target) == before {
+            assert!(
+                entries == 20 || entries == 2000,
+                "partial tree: {entries} entries"
+            );
+            samples += 1;
+        }
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+    };
+
+    assert!(status.success());
+    assert!(samples > 0);
+    assert_eq!(file_count(&target), 2000);
+}
+
+#[test]
+fn dir_atomic_new_destination() {
+    let e = Env::new();
+    e.file("src/a.txt", "a");
+
+    cp().arg("-R")
+        .arg("--atomic-dir")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/a.txt")), "a");
+}
+
+#[test]
+fn dir_empty_directories_clean_eof() {
+    let e = Env::new();
+    e.dir("src/empty_a");
+    e.dir("src/nested/empty_b");
+
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr("");
+
+    assert!(e.p("dst/empty_a").is_dir());
+    assert_eq!(file_count(&e.p("dst/nested/empty_b")), 0);
+}
+
+#[test]
+fn link_dest_links_unchanged_and_copies_changed() {
+    let e = Env::new();
+    e.dir("src/sub");
+    e.file("src/same.txt", "unchanged");
+    e.file("src/sub/deep.txt", "also unchanged");
+    e.file("src/changed.txt", "v1");
+
+    cp().arg("-a")
+        .arg(e.p("src"))
+        .arg(e.p("b1"))
+        .assert()
+        .success();
+
+    // Different size so the change is detected regardless of mtime resolution
+    e.file("src/changed.txt", "version 2");
+
+    cp().arg("-a")
+        .arg("--link-dest")
+        .arg(e.p("b1"))
+        .arg(e.p("src"))
+        .arg(e.p("b2"))
+        .assert()
+        .success();
+
+    assert_eq!(ino(&e.p("b2/same.txt")), ino(&e.p("b1/same.txt")));
+    assert_eq!(ino(&e.p("b2/sub/deep.txt")), ino(&e.p("b1/sub/deep.txt")));
+    assert_ne!(ino(&e.p("b2/changed.txt")), ino(&e.p("b1/changed.txt")));
+    assert_eq!(content(&e.p("b2/changed.txt")), "version 2");
+    assert_eq!(content(&e.p("b1/changed.txt")), "v1");
+}
+
+#[test]
+fn link_dest_requires_same_mtime_mode_and_owner() {
+    let e = Env::new();
+    e.dir("src");
+    e.file("src/a.txt", "aaa");
+    e.file("src/b.txt", "bbb");
+    // Backdate the source so copy-time mtimes in b1 can't coincide with it
+    e.set_mtime("src/a.txt", 1_000_000);
+
+    // Plain -R: b1 mtimes are the copy time, newer than the source's
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("b1"))
+        .assert()
+        .success();
+
+    // Not linked even where --update=older would call the copy current
+    cp().arg("-R")
+        .arg("--update=older")
+        .arg("--link-dest")
+        .arg(e.p("b1"))
+        .arg(e.p("src"))
+        .arg(e.p("b2"))
+        .assert()
+        .success();
+    assert_ne!(ino(&e.p("b2/a.txt")), ino(&e.p("b1/a.txt")));
+
+    // Same data and mtime, another mode: a link would carry the wrong one
+    cp().arg("-a")
+        .arg(e.p("src"))
+        .arg(e.p("b3"))
+        .assert()
+        .success();
+    e.chmod("b3/b.txt", 0o600);
+
+    cp().arg("-a")
+        .arg("--link-dest")
+        .arg(e.p("b3"))
+        .arg(e.p("src"))
+        .arg(e.p("b4"))
+        .assert()
+        .success();
+    assert_eq!(ino(&e.p("b4/a.txt")), ino(&e.p("b3/a.txt")));
+    assert_ne!(ino(&e.p("b4/b.txt")), ino(&e.p("b3/b.txt")));
+    assert_eq!(mode(&e.p("b4/b.txt")), mode(&e.p("src/b.txt")));
+}
+
+#[test]
+fn ignore_errors_from_tolerates_matching_path() {
+    let e = Env::new();
+    e.dir("src");
+    e.file("src/good.txt", "good");
+    e.file("src/bad.dat", "bad");
+    e.chmod("src/bad.dat", 0o000);
+    e.dir("out");
+    e.chmod("out", 0o777);
+
+    cp_unprivileged(&e)
+        .arg("-R")
+        .arg("--ignore-errors-from=*.dat")
+        .arg(e.p("src"))
+        .arg(e.p("out/dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("warning"));
+    assert_eq!(content(&e.p("out/dst/good.txt")), "good");
+
+    e.chmod("src/bad.dat", 0o644);
+}
+
+#[test]
+fn ignore_errors_from_warning_silenced_by_quiet() {
+    let e = Env::new();
+    e.dir("src");
+    e.file("src/good.txt", "good");
+    e.file("src/bad.dat", "bad");
+    e.chmod("src/bad.dat", 0o000);
+    e.dir("out");
+    e.chmod("out", 0o777);
+
+    cp_unprivileged(&e)
+        .arg("-R")
+        .arg("-q")
+        .arg("--ignore-errors-from=*.dat")
+        .arg(e.p("src"))
+        .arg(e.p("out/dst"))
+        .assert()
+        .success()
+        .stderr("");
+    assert_eq!(content(&e.p("out/dst/good.txt")), "good");
+
+    e.chmod("src/bad.dat", 0o644);
+}
+
+#[test]
+fn ignore_errors_from_still_fails_other_paths() {
+    let e = Env::new();
+    e.dir("src");
+    e.file("src/good.txt", "good");
+    e.file("src/bad.dat", "bad");
+    e.chmod("src/bad.dat", 0o000);
+    e.dir("out");
+    e.chmod("out", 0o777);
+
+    cp_unprivileged(&e)
+        .arg("-R")
+        .arg("--ignore-errors-from=*.log")
+        .arg(e.p("src"))
+        .arg(e.p("out/dst"))
+        .assert()
+        .failure();
+
+    e.chmod("src/bad.dat", 0o644);
+}
+
+#[test]
+fn no_empty_dirs_skips_file_less_tree() {
+    let e = Env::new();
+    e.dir("src/a/b");
+    e.dir("src/c");
+
+    cp().arg("-R")
+        .arg("--no-empty-dirs")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert!(!e.p("dst").exists());
+}
+
+#[test]
+fn no_empty_dirs_creates_only_populated_paths() {
+    let e = Env::new();
+    e.dir("src/empty");
+    e.dir("src/a/b/c");
+    e.dir("src/a/hollow");
+    e.file("src/a/b/c/f.txt", "deep");
+
+    cp().arg("-a")
+        .arg("--no-empty-dirs")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/a/b/c/f.txt")), "deep");
+    assert!(!e.p("dst/empty").exists());
+    assert!(!e.p("dst/a/hollow").exists());
+}
+
+// ─── --max-depth ────────────────────────────────────────────────────────────
+
+#[test]
+fn max_depth_counts_levels_through_symlinked_dirs() {
+    let e = Env::new();
+    e.file("src/top", "0");
+    e.file("deep_dir/a/b/c/f1", "1");
+    e.file("deep_dir/a/b/c/d/f2", "2");
+    e.file("deep_dir/a/b/c/d/e/f3", "3");
+    e.symlink(e.p("deep_dir/a/b/c"), "src/link");
+
+    cp().args(["-R", "-L", "--max-depth", "2"])
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    // link is level 1, its entries level 2: nothing further down
+    assert_eq!(content(&e.p("dst/top")), "0");
+    assert_eq!(content(&e.p("dst/link/f1")), "1");
+    assert!(e.p("dst/link/d").is_dir());
+    assert!(!e.p("dst/link/d/f2").exists());
+    assert!(!e.p("dst/link/d/e").exists());
+}
+
+// ─── --max-files ────────────────────────────────────────────────────────────
+
+fn populate_flat(e: &Env, n: usize) {
+    e.dir("src");
+    for i in 0..n {
+        e.file(&format!("src/f_{i:04}"), "x");
+    }
+}
+
+#[test]
+fn max_files_stops_at_limit() {
+    // Sequential raw path, parallel raw path (>= 64 files) and walkdir (-n)
+    for (n, flags) in [(10, "-R"), (100, "-R"), (10, "-Rn")] {
+        let e = Env::new();
+        populate_flat(&e, n + 5);
+
+        cp().arg(flags)
+            .arg(format!("--max-files={n}"))
+            .arg(e.p("src"))
+            .arg(e.p("dst"))
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(format!(
+                "too many files (limit {n} exceeded"
+            )));
+
+        assert_eq!(file_count(&e.p("dst")), n, "{flags} with {n} files");
+    }
+}
+
+#[test]
+fn max_files_at_limit_succeeds() {
+    let e = Env::new();
+    populate_flat(&e, 10);
+    e.symlink("f_0000", "src/link");
+    e.file("src/sub/inner", "y");
+
+    cp().arg("-R")
+        .arg("--max-files=12")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/sub/inner")), "y");
+}
+
+#[test]
+fn max_files_counts_across_sources() {
+    for flags in ["-R", "-Rn"] {
+        let e = Env::new();
+        for i in 0..3 {
+            e.file(&format!("a/f{i}"), "x");
+            e.file(&format!("b/f{i}"), "x");
+        }
+        e.dir("dst");
+
+        cp().arg(flags)
+            .arg("--max-files=3")
+            .arg(e.p("a"))
+            .arg(e.p("b"))
+            .arg(e.p("dst"))
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("limit 3 exceeded"));
+
+        let copied = walkdir::WalkDir::new(e.p("dst"))
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .count();
+        assert_eq!(copied, 3, "{flags}");
+    }
+}
+
+#[test]
+fn max_files_remove_destination_cleans_up() {
+    let e = Env::new();
+    populate_flat(&e, 20);
+
+    cp().arg("-R")
+        .arg("--remove-destination")
+        .arg("--max-files=5")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("limit 5"));
+
+    assert!(!e.p("dst").exists());
+}
+
+// ─── --max-total-size ───────────────────────────────────────────────────────
+
+/// Total bytes of the regular files under `dir`.
+fn tree_bytes(dir: &std::path::Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.metadata().unwrap().len())
+        .sum()
+}
+
+#[test]
+fn max_total_size_stops_before_limit() {
+    // Sequential walkdir, and the parallel one (>= 64 files)
+    for n in [10, 100] {
+        let e = Env::new();
+        for i in 0..n {
+            e.file(&format!("src/f_{i:04}"), "x".repeat(100));
+        }
+
+        cp().arg("-R")
+            .arg("--max-total-size=350")
+            .arg(e.p("src"))
+            .arg(e.p("dst"))
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains(
+                "total size limit of 350 bytes exceeded at",
+            ));
+
+        assert_eq!(file_count(&e.p("dst")), 3, "{n} files");
+        assert_eq!(tree_bytes(&e.p("dst")), 300);
+    }
+}
+
+#[test]
+fn max_total_size_at_limit_succeeds() {
+    let e = Env::new();
+    e.file("src/a", "x".repeat(1000));
+    e.file("src/sub/b", "y".repeat(24));
+    e.symlink("a", "src/link");
+
+    cp().arg("-R")
+        .arg("--max-total-size=1K")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(tree_bytes(&e.p("dst")), 1024);
+}
+
+#[test]
+fn max_total_size_aborts_remaining_sources() {
+    let e = Env::new();
+    e.dir("dst");
+    for name in ["a", "b", "c", "d"] {
+        e.file(name, "z".repeat(100));
+    }
+
+    let out = cp()
+        .arg("--max-total-size=250")
+        .args(["a", "b", "c", "d"].map(|n| e.p(n)))
+        .arg(e.p("dst"))
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(stderr.matches("exceeded").count(), 1, "{stderr}");
+    assert!(stderr.contains(&format!("'{}'", e.p("c").display())));
+
+    assert!(e.p("dst/b").exists());
+    assert!(!e.p("dst/c").exists());
+    assert!(!e.p("dst/d").exists());
+}
+
+#[test]
+fn max_total_size_pre_check_copies_nothing() {
+    let e = Env::new();
+    e.file("small", "s".repeat(10));
+    e.file("src/a", "x".repeat(600));
+    e.file("src/b", "y".repeat(600));
+    e.dir("dst");
+
+    cp().arg("-R")
+        .arg("--max-total-size=1000")
+        .arg("--pre-check-size")
+        .arg(e.p("small"))
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("total size limit of 1000 bytes"));
+
+    assert_eq!(file_count(&e.p("dst")), 0);
+
+    // Without a limit to check against, the flag is refused
+    cp().arg("--pre-check-size")
+        .arg(e.p("small"))
+        .arg(e.p("dst"))
+        .assert()
+        .failure();
+}
+
+// ─── --sort ─────────────────────────────────────────────────────────────────
+
+#[test]
+fn sort_inode_copies_everything() {
+    let e = Env::new();
+    // Names in reverse creation order: name order differs from inode order
+    for i in (0..100).rev() {
+        e.file(&format!("src/f_{i:03}"), format!("data_{i}"));
+    }
+    e.file("src/sub/inner", "inner");
+    e.symlink("f_000", "src/link");
+
+    for flags in ["-R", "-Rn"] {
+        let out = format!("dst{flags}");
+        cp().arg(flags)
+            .arg("--sort=inode")
+            .arg(e.p("src"))
+            .arg(e.p(&out))
+            .assert()
+            .success();
+
+        assert_eq!(file_count(&e.p(&out)), 102);
+        assert_eq!(content(&e.p(&format!("{out}/f_042"))), "data_42");
+        assert_eq!(content(&e.p(&format!("{out}/sub/inner"))), "inner");
+        assert!(is_symlink(&e.p(&format!("{out}/link"))));
+    }
+}
+
+#[test]
+fn sort_name_orders_verbose_output() {
+    let e = Env::new();
+    for name in ["c", "a", "d", "b"] {
+        e.file(&format!("src/{name}"), name);
+    }
+
+    let out = cp()
+        .arg("-Rv")
+        .arg("--sort=name")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    let names: Vec<String> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .filter_map(|l| {
+            l.rsplit('/')
+                .next()
+                .map(|n| n.trim_end_matches('\'').to_string())
+        })
+        .filter(|n| n.len() == 1)
+        .collect();
+    assert_eq!(names, ["a", "b", "c", "d"]);
+}
+
+#[test]
+fn verbose_order_tree_prints_each_entry_in_traversal_order() {
+    let e = Env::new();
+    e.file("src/a", "a");
+    e.file("src/b/c", "c");
+    e.file("src/b/d/e", "e");
+    e.symlink("a", "src/b/l");
+    e.file("src/f", "f");
+
+    let out = cp()
+        .arg("-Rv")
+        .arg("--sort=name")
+        .arg("--verbose-order=tree")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+
+    // Pre-order, in name order: a directory's line before its contents
+    let root = e.p("src").display().to_string();
+    let sources: Vec<String> = String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .filter_map(|l| l.split(" -> ").next())
+        .map(|s| s.trim_matches('\'').replacen(&root, "src", 1))
+        .collect();
+    assert_eq!(
+        sources,
+        [
+            "src",
+            "src/a",
+            "src/b",
+            "src/b/c",
+            "src/b/d",
+            "src/b/d/e",
+            "src/b/l",
+            "src/f"
+        ]
+    );
+    assert_eq!(content(&e.p("dst/b/d/e")), "e");
+}
+
+// ─── --dir-mode ─────────────────────────────────────────────────────────────
+
+#[test]
+fn dir_mode_applies_to_every_created_directory() {
+    let e = Env::new();
+    e.file("src/a/b/c/f", "deep");
+    e.file("src/top", "top");
+    e.chmod("src/a", 0o755);
+    e.chmod("src/a/b", 0o775);
+
+    // Raw fast path, walkdir path, and walkdir with on-demand parents
+    for flags in ["-R", "-Rn", "-R --no-empty-dirs"] {
+        let out = format!("dst{}", flags.replace(' ', ""));
+        cp().args(flags.split(' '))
+            .arg("--dir-mode")
+            .arg("0700")
+            .arg(e.p("src"))
+            .arg(e.p(&out))
+            .assert()
+            .success();
+
+        for dir in ["", "/a", "/a/b", "/a/b/c"] {
+            assert_eq!(mode(&e.p(&format!("{out}{dir}"))), 0o700, "{flags}: {dir}");
+        }
+        assert_eq!(content(&e.p(&format!("{out}/a/b/c/f"))), "deep");
+    }
+}
+
+#[test]
+fn dir_mode_leaves_existing_dirs_and_yields_to_preserve_mode() {
+    let e = Env::new();
+    e.file("src/sub/f", "x");
+    e.chmod("src/sub", 0o751);
+    e.dir("dst");
+    e.chmod("dst", 0o755);
+
+    cp().arg("-R")
+        .arg("--dir-mode=700")
+        .arg(e.p("src/sub"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert_eq!(mode(&e.p("dst")), 0o755);
+    assert_eq!(mode(&e.p("dst/sub")), 0o700);
+
+    cp().arg("-a")
+        .arg("--dir-mode=700")
+        .arg(e.p("src"))
+        .arg(e.p("archived"))
+        .assert()
+        .success();
+    assert_eq!(mode(&e.p("archived/sub")), 0o751);
+}
+
+#[test]
+fn dir_mode_rejects_invalid_mode() {
+    cp().arg("--dir-mode=999")
+        .arg("a")
+        .arg("b")
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid mode"));
+}
+
+#[test]
+fn dir_link_recursive_fast_path() {
+    let e = Env::new();
+    for i in 0..80 {
+        e.file(&format!("src/f{i}"), format!("data {i}"));
+    }
+    e.file("src/sub/deep.txt", "deep");
+
+    for _ in 0..2 {
+        // Second run: linkat meets the existing links and replaces them
+        cp().arg("-lRT")
+            .arg(e.p("src"))
+            .arg(e.p("dst"))
+            .assert()
+            .success();
+    }
+    for i in 0..80 {
+        let name = format!("f{i}");
+        assert_eq!(
+            ino(&e.p(&format!("dst/{name}"))),
+            ino(&e.p(&format!("src/{name}")))
+        );
+    }
+    assert_eq!(ino(&e.p("dst/sub/deep.txt")), ino(&e.p("src/sub/deep.txt")));
+}
+
+#[test]
+fn dir_link_keeps_symlinks_and_copies_across_filesystems() {
+    let e = Env::new();
+    e.file("src/a.txt", "alpha");
+    e.file("src/sub/b.txt", "beta");
+    e.symlink("a.txt", "src/link");
+
+    cp().arg("-Rl")
+        .arg("-a")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert_eq!(ino(&e.p("dst/a.txt")), ino(&e.p("src/a.txt")));
+    assert_eq!(ino(&e.p("dst/sub/b.txt")), ino(&e.p("src/sub/b.txt")));
+    assert!(is_symlink(&e.p("dst/link")));
+
+    let shm = std::path::Path::new("/dev/shm");
+    let dev =
+        |p: &std::path::Path| std::os::unix::fs::MetadataExt::dev(&std::fs::metadata(p).unwrap());
+    if !shm.is_dir() || dev(shm) == dev(e.path()) {
+        eprintln!("SKIP: no second filesystem");
+        return;
+    }
+    let other = tempfile::TempDir::new_in(shm).unwrap();
+    cp().arg("-Rl")
+        .arg(e.p("src"))
+        .arg(other.path().join("out"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "across filesystems, copying instead",
+        ));
+    assert_eq!(content(&other.path().join("out/a.txt")), "alpha");
+    assert_eq!(content(&other.path().join("out/sub/b.txt")), "beta");
+}
+
+/// Unmounts the mount on drop, even when an assertion fails.
+struct Mounted(std::path::PathBuf);
+
+impl Drop for Mounted {
+    fn drop(&mut self) {
+        let _ = std::process::Command::new("umount").arg(&self.0).status();
+    }
+}
+
+#[test]
+fn dir_bind_mount_cycle_is_skipped_and_reported() {
+    let e = Env::new();
+    e.file("src/f.txt", "data");
+    e.file("src/other/g.txt", "more");
+    e.dir("src/loop");
+
+    // src/loop shows src again: a directory inside itself, without symlinks
+    let mounted = std::process::Command::new("mount")
+        .arg("--bind")
+        .arg(e.p("src"))
+        .arg(e.p("src/loop"))
+        .stderr(std::process::Stdio::null())
+        .status()
+        .is_ok_and(|s| s.success());
+    if !mounted {
+        eprintln!("SKIP: bind mounts not permitted");
+        return;
+    }
+    let _guard = Mounted(e.p("src/loop"));
+
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .timeout(std::time::Duration::from_secs(30))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(format!(
+            "cannot copy cyclic structure at '{}'",
+            e.p("src/loop").display()
+        )));
+    assert_eq!(content(&e.p("dst/f.txt")), "data");
+    assert_eq!(content(&e.p("dst/other/g.txt")), "more");
+    assert!(!e.p("dst/loop").exists());
+}
+
+#[test]
+fn dir_one_file_system_from_mount_point_root() {
+    let e = Env::new();
+    e.dir("root");
+    let mount_tmpfs = |at: &std::path::Path| {
+        std::process::Command::new("mount")
+            .args(["-t", "tmpfs", "none"])
+            .arg(at)
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success())
+    };
+
+    // The source root is itself a mount point, like / in `cp -ax / /backup`,
+    // with another filesystem mounted below it, like /proc
+    if !mount_tmpfs(&e.p("root")) {
+        eprintln!("SKIP: mounts not permitted");
+        return;
+    }
+    let _root = Mounted(e.p("root"));
+    e.file("root/top.txt", "top");
+    e.file("root/keep/f.txt", "kept");
+    e.dir("root/proc");
+    assert!(mount_tmpfs(&e.p("root/proc")));
+    let _proc = Mounted(e.p("root/proc"));
+    e.file("root/proc/inner.txt", "other filesystem");
+    e.file("root/proc/sub/deep.txt", "other filesystem");
+
+    // Raw fast path, then walkdir
+    for (out, extra) in [("out", None), ("out_walk", Some("--backup=numbered"))] {
+        cp().arg("-ax")
+            .args(extra)
+            .arg(e.p("root"))
+            .arg(e.p(out))
+            .assert()
+            .success();
+
+        assert_eq!(content(&e.p(&format!("{out}/top.txt"))), "top");
+        assert_eq!(content(&e.p(&format!("{out}/keep/f.txt"))), "kept");
+        // The mount point is kept, as an empty directory
+        assert!(e.p(&format!("{out}/proc")).is_dir(), "{out}");
+        assert_eq!(file_count(&e.p(&format!("{out}/proc"))), 0, "{out}");
+    }
+}
+
+// ─── --estimate ──────────────────────────────────────────────────────────────
+
+#[test]
+fn estimate_counts_files_and_bytes_without_copying() {
+    let e = Env::new();
+    e.file("src/a", "x".repeat(1000));
+    e.file("src/sub/b", "x".repeat(24));
+    e.file("src/sub/deeper/c", "x".repeat(4096));
+    e.file("src/sub/deeper/empty", "");
+    e.symlink("a", "src/link");
+    e.dir("src/empty_dir");
+
+    let estimate = |args: &[&str]| {
+        let out = cp()
+            .arg("--estimate")
+            .args(args)
+            .arg(e.p("src"))
+            .arg(e.p("dst"))
+            .assert()
+            .success()
+            .get_output()
+            .stdout
+            .clone();
+        String::from_utf8(out).unwrap()
+    };
+
+    // 4 regular files and a symlink (counted, with no bytes of its own)
+    assert_eq!(estimate(&["-R"]), "5 files, 5120 bytes (5.0 KiB)\n");
+    assert_eq!(
+        estimate(&["-R", "--max-depth=2"]),
+        "3 files, 1024 bytes (1.0 KiB)\n"
+    );
+    // -L counts what the symlink points to
+    assert_eq!(estimate(&["-RL"]), "5 files, 6120 bytes (6.0 KiB)\n");
+    // Without -R the directory would be omitted
+    assert_eq!(estimate(&[]), "0 files, 0 bytes (0 B)\n");
+
+    assert!(!e.p("dst").exists());
+}
+
+#[test]
+fn compare_identical_trees_exits_zero() {
+    let e = Env::new();
+    for root in ["src", "dst"] {
+        e.file(&format!("{}/a", root), "same");
+        e.file(&format!("{}/sub/b", root), "also same");
+        e.symlink("a", &format!("{}/link", root));
+    }
+
+    cp().arg("--compare")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .code(0)
+        .stdout("");
+}
+
+#[test]
+fn compare_reports_differences_without_copying() {
+    let e = Env::new();
+    e.file("src/same", "same");
+    e.file("src/modified", "before");
+    e.file("src/resized", "short");
+    e.file("src/missing", "only in src");
+    e.file("src/gone/f", "whole directory");
+    e.file("dst/same", "same");
+    e.file("dst/modified", "after!");
+    e.file("dst/resized", "much longer");
+    e.file("dst/extra", "only in dst");
+
+    let dst = e.p("dst");
+    let expected = [
+        format!("extra: '{}'", dst.join("extra").display()),
+        format!("missing: '{}'", dst.join("gone").display()),
+        format!("missing: '{}'", dst.join("missing").display()),
+        format!("differs (content): '{}'", dst.join("modified").display()),
+        format!("differs (size): '{}'", dst.join("resized").display()),
+    ];
+    cp().arg("--compare")
+        .arg(e.p("src"))
+        .arg(&dst)
+        .assert()
+        .code(1)
+        .stdout(expected.join("\n") + "\n");
+
+    // Nothing was copied
+    assert!(!dst.join("missing").exists());
+    assert_eq!(content(&dst.join("modified")), "after!");
+
+    // Same size, different bytes: only a content comparison sees it
+    cp().arg("--compare=size")
+        .arg(e.p("src/modified"))
+        .arg(dst.join("modified"))
+        .assert()
+        .code(0);
+}
+
+#[test]
+fn compare_by_mtime() {
+    let e = Env::new();
+    e.file("src/f", "data");
+    e.file("dst/f", "data");
+    e.set_mtime("src/f", 1_000_000);
+    e.set_mtime("dst/f", 2_000_000);
+
+    cp().arg("--compare")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .code(0);
+    cp().arg("--compare=mtime")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .code(1)
+        .stdout(format!("differs (mtime): '{}'\n", e.p("dst/f").display()));
+}
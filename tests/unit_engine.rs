@@ -14,7 +14,7 @@ fn engine_copy_file_range_small_file() {
         .arg(e.p("dst"))
         .assert()
         .success()
-        .stderr(predicates::str::contains("copy_file_range"));
+        .stderr(predicates::str::contains(KERNEL_COPY));
 
     assert_eq!(content(&e.p("dst")), "hello world");
 }
@@ -189,3 +189,393 @@ fn engine_ficlone_threshold_at() {
 
     assert_eq!(bytes(&e.p("dst")), data);
 }
+
+#[test]
+fn engine_reflink_try_always_small_files() {
+    let e = Env::new();
+    e.file("a", "tiny file");
+    e.file("b", vec![5u8; 4096]);
+    let on_btrfs = on_btrfs(e.path());
+
+    let assert = cp()
+        .arg("--reflink=try-always")
+        .arg("--debug")
+        .arg(e.p("a"))
+        .arg(e.p("b"))
+        .arg(e.dir("dst"))
+        .assert()
+        .success();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    if on_btrfs {
+        // Cloned despite being under the 256 KB threshold
+        assert_eq!(stderr.matches("reflink (FICLONE)").count(), 2, "{stderr}");
+    } else {
+        assert!(!stderr.contains("reflink (FICLONE)"), "{stderr}");
+    }
+    assert_eq!(content(&e.p("dst/a")), "tiny file");
+    assert_eq!(bytes(&e.p("dst/b")), vec![5u8; 4096]);
+}
+
+// ═══════════════════════════════════════════════════════════════════════════════
+// --reflink-fallback
+// ═══════════════════════════════════════════════════════════════════════════════
+
+#[test]
+fn engine_reflink_fallback_read_write() {
+    let e = Env::new();
+    let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    e.file("src", &data);
+
+    let assert = cp()
+        .arg("--reflink=auto")
+        .arg("--reflink-fallback=read-write")
+        .arg("--sparse=never")
+        .arg("--debug")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    // A CoW filesystem clones instead; otherwise the forced engine is reported
+    if !stderr.contains("FICLONE") {
+        assert!(
+            stderr.contains(
+                "copy method: read/write (reflink failed, copy_file_range, sendfile disabled by --reflink-fallback)"
+            ),
+            "{stderr}"
+        );
+    }
+    assert_eq!(bytes(&e.p("dst")), data);
+}
+
+#[test]
+#[cfg_attr(
+    target_os = "macos",
+    ignore = "sendfile(2) only writes to sockets on macOS"
+)]
+fn engine_reflink_fallback_sendfile_small_file() {
+    let e = Env::new();
+    e.file("src", "small file via sendfile");
+
+    cp().arg("--reflink-fallback=sendfile")
+        .arg("--sparse=never")
+        .arg("--debug")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "copy method: sendfile (reflink skipped for small file",
+        ));
+
+    assert_eq!(content(&e.p("dst")), "small file via sendfile");
+}
+
+#[test]
+fn engine_reflink_fallback_error_requires_clone() {
+    let e = Env::new();
+    e.file("src", "tiny, but the clone is still attempted");
+
+    let out = cp()
+        .arg("--reflink-fallback=error")
+        .arg("--debug")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+
+    // Like --reflink=always: clone or fail, never a plain copy
+    if out.status.success() {
+        assert!(stderr.contains("FICLONE"), "{stderr}");
+    } else {
+        assert!(stderr.contains("failed to clone"), "{stderr}");
+    }
+}
+
+// ─── EDQUOT is reported as a quota error, not a generic write error ─────────
+
+#[test]
+fn engine_write_error_identifies_quota() {
+    use cp::engine::write_error;
+    use cp::error::CpError;
+    use std::io::Error;
+    let path = std::path::Path::new("/mnt/quota/f");
+
+    let err = write_error(path, Error::from_raw_os_error(nix::libc::EDQUOT));
+    assert!(matches!(err, CpError::QuotaExceeded { .. }), "{err:?}");
+    assert_eq!(
+        err.to_string(),
+        "cannot write to '/mnt/quota/f': disk quota exceeded"
+    );
+
+    // A full disk stays a plain write error
+    let err = write_error(path, Error::from_raw_os_error(nix::libc::ENOSPC));
+    assert!(matches!(err, CpError::Write { .. }), "{err:?}");
+}
+
+// ─── --chunk-size ───────────────────────────────────────────────────────────
+
+#[test]
+fn engine_chunk_size_copies_large_file() {
+    let e = Env::new();
+    let data: Vec<u8> = (0..5 * 1024 * 1024 + 123)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    e.file("src", &data);
+
+    cp().arg("--chunk-size")
+        .arg("1M")
+        .arg("--sparse=never")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    assert_eq!(bytes(&e.p("dst")), data);
+
+    cp().arg("--chunk-size=0")
+        .arg(e.p("src"))
+        .arg(e.p("dst2"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid chunk size"));
+}
+
+#[test]
+fn engine_direct_copies_byte_identical() {
+    let e = Env::new();
+    // Unaligned size: the last partial block goes through without O_DIRECT
+    let data: Vec<u8> = (0..5 * 1024 * 1024 + 123)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    e.file("src", &data);
+    e.file("dst", "existing, longer than nothing");
+
+    let out = cp()
+        .arg("--direct")
+        .arg("--debug")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(bytes(&e.p("dst")), data);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("copy method: O_DIRECT") || stderr.contains("fadvise DONTNEED"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn engine_fadvise_copies_byte_identical() {
+    let e = Env::new();
+    // Just over the 64 MiB threshold, so the hints are given
+    let data: Vec<u8> = (0..64 * 1024 * 1024 + 4099)
+        .map(|i| (i % 241) as u8)
+        .collect();
+    e.file("src", &data);
+
+    for (args, dst) in [
+        (&["--fadvise"][..], "plain"),
+        (&["--fadvise", "--sparse=always"], "sparse"),
+    ] {
+        cp().args(args)
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .assert()
+            .success();
+        assert!(bytes(&e.p(dst)) == data, "{dst}");
+    }
+}
+
+#[test]
+fn engine_cow_verify() {
+    let e = Env::new();
+    let data = vec![0x42u8; 1024 * 1024];
+    e.file("src", &data);
+
+    // No clone here (tmpfs/ext4 refuse FICLONE): nothing to check, no warning
+    cp().args(["--reflink=auto", "--cow-verify"])
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr("");
+    assert_eq!(bytes(&e.p("dst")), data);
+
+    // What --cow-verify flags after a clone that didn't share: a full copy.
+    // Without FIEMAP the check can't tell and passes
+    use cp::util::verify_cow;
+    let shared = cp::engine::shared_bytes(&std::fs::File::open(e.p("dst")).unwrap());
+    if shared.is_ok() {
+        assert!(!verify_cow(&e.p("src"), &e.p("dst")));
+    }
+}
+
+/// Records every position the progress bar draws.
+#[derive(Debug, Clone, Default)]
+struct Positions(std::sync::Arc<std::sync::Mutex<Vec<u64>>>);
+
+impl indicatif::TermLike for Positions {
+    fn width(&self) -> u16 {
+        80
+    }
+    fn move_cursor_up(&self, _: usize) -> std::io::Result<()> {
+        Ok(())
+    }
+    fn move_cursor_down(&self, _: usize) -> std::io::Result<()> {
+        Ok(())
+    }
+    fn move_cursor_right(&self, _: usize) -> std::io::Result<()> {
+        Ok(())
+    }
+    fn move_cursor_left(&self, _: usize) -> std::io::Result<()> {
+        Ok(())
+    }
+    fn write_line(&self, s: &str) -> std::io::Result<()> {
+        self.write_str(s)
+    }
+    fn write_str(&self, s: &str) -> std::io::Result<()> {
+        if let Ok(pos) = s.trim().parse() {
+            self.0.lock().unwrap().push(pos);
+        }
+        Ok(())
+    }
+    fn clear_line(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+    fn flush(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+#[cfg_attr(target_os = "macos", ignore = "fcopyfile copies in one call")]
+fn engine_chunk_size_steps_progress() {
+    use cp::cli::{ReflinkFallback, ReflinkMode};
+    use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+    let e = Env::new();
+    let size = 4 * 1024 * 1024;
+    e.file("src", vec![7u8; size]);
+
+    let positions = |chunk: Option<usize>| {
+        let rec = Positions::default();
+        let target = ProgressDrawTarget::term_like_with_hz(Box::new(rec.clone()), 20);
+        let pb = ProgressBar::with_draw_target(Some(size as u64), target);
+        pb.set_style(ProgressStyle::with_template("{pos}").unwrap());
+        let src = std::fs::File::open(e.p("src")).unwrap();
+        let dst = std::fs::File::create(e.p("dst")).unwrap();
+        let method = cp::engine::copy_file_data(
+            &src,
+            &dst,
+            size as u64,
+            &e.p("src"),
+            &e.p("dst"),
+            ReflinkMode::Never,
+            ReflinkFallback::CopyFileRange,
+            None,
+            chunk,
+            &pb,
+        )
+        .unwrap();
+        assert_eq!(method, KERNEL_COPY);
+        assert_eq!(pb.position(), size as u64);
+        let mut seen = rec.0.lock().unwrap().clone();
+        seen.dedup();
+        seen
+    };
+
+    // One step per MiB instead of the whole file at once
+    assert_eq!(positions(Some(1024 * 1024)).len(), 4);
+    assert_eq!(positions(None).len(), 1);
+}
+
+// ─── --report-shared ────────────────────────────────────────────────────────
+
+#[test]
+fn engine_report_shared_counts_reflinked_bytes() {
+    let e = Env::new();
+    let size = 1024 * 1024;
+    e.file("src", vec![9u8; size]);
+    let on_btrfs = on_btrfs(e.path());
+
+    let reflink = if on_btrfs {
+        "--reflink=always"
+    } else {
+        "--reflink=never"
+    };
+    let assert = cp()
+        .arg("--report-shared")
+        .arg(reflink)
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert_eq!(bytes(&e.p("dst")), vec![9u8; size]);
+    let expected = if on_btrfs {
+        "shared 1.0 MiB of 1.0 MiB copied (100%), 0 B newly allocated"
+    } else {
+        "shared 0 B of 1.0 MiB copied (0%), 1.0 MiB newly allocated"
+    };
+    assert!(stderr.contains(expected), "{stderr}");
+}
+
+// ─── FICLONE support remembered per destination device ──────────────────────
+
+#[test]
+fn engine_ficlone_unsupported_remembered_per_device() {
+    use cp::cli::{ReflinkFallback, ReflinkMode};
+    use cp::engine::{copy_file_data, ficlone_known_unsupported};
+    use indicatif::ProgressBar;
+
+    let e = Env::new();
+    let size = 512 * 1024;
+    let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+    e.file("src", &data);
+
+    let copy = |dst: &str, reflink: ReflinkMode| {
+        let src = std::fs::File::open(e.p("src")).unwrap();
+        let file = std::fs::File::create(e.p(dst)).unwrap();
+        let method = copy_file_data(
+            &src,
+            &file,
+            size as u64,
+            &e.p("src"),
+            &e.p(dst),
+            reflink,
+            ReflinkFallback::Auto,
+            None,
+            None,
+            &ProgressBar::hidden(),
+        );
+        (method, file)
+    };
+
+    let (method, dst) = copy("a", ReflinkMode::Auto);
+    if method.unwrap().starts_with("reflink") {
+        // A CoW filesystem: nothing to remember
+        assert!(!ficlone_known_unsupported(&dst));
+        return;
+    }
+    assert!(ficlone_known_unsupported(&dst));
+
+    // Later copies to the device go straight to the data copy
+    for (name, reflink) in [("b", ReflinkMode::Auto), ("c", ReflinkMode::TryAlways)] {
+        assert_eq!(copy(name, reflink).0.unwrap(), KERNEL_COPY);
+        assert_eq!(bytes(&e.p(name)), data);
+    }
+    // --reflink=always still asks, and fails
+    assert!(copy("d", ReflinkMode::Always).0.is_err());
+
+    // Another device is not affected
+    if let Ok(shm) = tempfile::tempdir_in("/dev/shm") {
+        use std::os::unix::fs::MetadataExt;
+        let other = std::fs::File::create(shm.path().join("f")).unwrap();
+        if other.metadata().unwrap().dev() != dst.metadata().unwrap().dev() {
+            assert!(!ficlone_known_unsupported(&other));
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Tests — --list (list.rs)
+
+mod common;
+use common::*;
+
+use std::path::{Path, PathBuf};
+
+/// Parsed --list output: (type, src, dst, size) per line.
+fn list_output(args: &[&std::ffi::OsStr]) -> Vec<(String, PathBuf, PathBuf, u64)> {
+    let out = cp()
+        .args(args)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8(out)
+        .unwrap()
+        .lines()
+        .map(|l| {
+            let f: Vec<&str> = l.split('\t').collect();
+            assert_eq!(f.len(), 4, "{l}");
+            (
+                f[0].to_string(),
+                PathBuf::from(f[1]),
+                PathBuf::from(f[2]),
+                f[3].parse().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Every path under `root` (itself included), sorted, like `find root | sort`.
+fn find(root: &Path) -> Vec<PathBuf> {
+    let mut v: Vec<PathBuf> = walkdir::WalkDir::new(root)
+        .into_iter()
+        .map(|e| e.unwrap().into_path())
+        .collect();
+    v.sort();
+    v
+}
+
+#[test]
+fn list_matches_real_copy() {
+    let e = Env::new();
+    e.file("src/a.txt", "hello");
+    e.file("src/sub/b.bin", vec![7u8; 3000]);
+    e.dir("src/empty");
+    e.symlink("a.txt", "src/link");
+
+    let lines = list_output(&[
+        "-R".as_ref(),
+        "--list".as_ref(),
+        e.p("src").as_os_str(),
+        e.p("dst").as_os_str(),
+    ]);
+    assert!(!e.p("dst").exists(), "--list must not copy");
+
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    let mut listed: Vec<PathBuf> = lines.iter().map(|(_, _, dst, _)| dst.clone()).collect();
+    listed.sort();
+    assert_eq!(listed, find(&e.p("dst")));
+
+    for (kind, src, dst, size) in &lines {
+        let expected = if is_symlink(src) {
+            "symlink"
+        } else if src.is_dir() {
+            "dir"
+        } else {
+            "file"
+        };
+        assert_eq!(kind, expected, "{}", src.display());
+        let expected_size = if kind == "file" { file_size(dst) } else { 0 };
+        assert_eq!(*size, expected_size, "{}", dst.display());
+    }
+}
+
+#[test]
+fn list_applies_copy_filters() {
+    let e = Env::new();
+    e.file("src/same", "same");
+    e.file("src/new", "new");
+    e.dir("src/hollow");
+    e.file("dst/same", "SAME");
+
+    let lines = list_output(&[
+        "-R".as_ref(),
+        "-n".as_ref(),
+        "--no-empty-dirs".as_ref(),
+        "--list".as_ref(),
+        e.p("src").as_os_str(),
+        e.p("dst").as_os_str(),
+    ]);
+
+    // dst exists: src is copied into dst/src
+    let listed: Vec<PathBuf> = lines.iter().map(|(_, _, dst, _)| dst.clone()).collect();
+    assert!(listed.contains(&e.p("dst/src/new")));
+    assert!(!listed.contains(&e.p("dst/src/hollow")));
+
+    // Into the existing tree itself, -n leaves `same` alone
+    let lines = list_output(&[
+        "-n".as_ref(),
+        "--list".as_ref(),
+        e.p("src/same").as_os_str(),
+        e.p("dst/same").as_os_str(),
+    ]);
+    assert!(lines.is_empty());
+    assert_eq!(content(&e.p("dst/same")), "SAME");
+}
+
+#[test]
+fn list_honors_max_depth() {
+    let e = Env::new();
+    e.file("src/top", "top");
+    e.file("src/a/b/c/f", "deep");
+
+    let lines = list_output(&[
+        "-R".as_ref(),
+        "--list".as_ref(),
+        "--max-depth=1".as_ref(),
+        e.p("src").as_os_str(),
+        e.p("dst").as_os_str(),
+    ]);
+
+    cp().arg("-R")
+        .arg("--max-depth=1")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    let mut listed: Vec<PathBuf> = lines.iter().map(|(_, _, dst, _)| dst.clone()).collect();
+    listed.sort();
+    assert!(!listed.contains(&e.p("dst/a/b/c/f")));
+    assert_eq!(listed, find(&e.p("dst")));
+}
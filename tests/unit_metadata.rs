@@ -100,6 +100,37 @@ fn meta_symlink_timestamps() {
     assert_eq!(symlink_mtime(&e.p("dst/link")), 1_400_000_000);
 }
 
+#[test]
+fn meta_dangling_symlink_timestamps() {
+    let e = Env::new();
+    e.dir("src");
+    e.symlink("nowhere", "src/dangling");
+    e.set_symlink_mtime("src/dangling", 1_400_000_000);
+
+    // Raw directory fast path, walkdir path (--backup with nothing to back
+    // up), and a dangling link named on the command line
+    for (extra, dst) in [(None, "fast"), (Some("--backup=numbered"), "walk")] {
+        cp().arg("-a")
+            .args(extra)
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .assert()
+            .success();
+    }
+    cp().arg("-a")
+        .arg(e.p("src/dangling"))
+        .arg(e.p("single"))
+        .assert()
+        .success();
+
+    for rel in ["fast/dangling", "walk/dangling", "single"] {
+        let dst = e.p(rel);
+        assert!(is_symlink(&dst), "{rel}");
+        assert!(!dst.exists(), "{rel} should still dangle");
+        assert_eq!(symlink_mtime(&dst), 1_400_000_000, "{rel}");
+    }
+}
+
 // ─── --debug outputs copy method ─────────────────────────────────────────────
 
 #[test]
@@ -169,6 +200,186 @@ fn meta_acl_preserved() {
     assert!(posix_acl::PosixACL::read_acl(e.p("dst")).is_ok());
 }
 
+// ─── Directory timestamps are applied after ownership/mode/xattr/ACL ────────
+
+#[test]
+fn meta_dir_timestamps_survive_acl_and_xattr() {
+    let e = Env::new();
+    e.file("src/sub/f", "content");
+    if let Err(err) = posix_acl::PosixACL::read_acl(e.p("src/sub")) {
+        let msg = err.to_string();
+        if msg.contains("not supported") || msg.contains("No data available") {
+            eprintln!("SKIP: filesystem does not support ACL");
+            return;
+        }
+    }
+    let _ = xattr::set(e.p("src/sub"), "user.test", b"value");
+    e.chmod("src/sub", 0o750);
+    e.set_mtime("src/sub", 1_500_000_000);
+    e.set_mtime("src", 1_500_000_100);
+
+    // Raw fast path, then the walkdir path (-n)
+    for (flags, out) in [("-a", "raw"), ("-an", "walk")] {
+        cp().arg(flags)
+            .arg("--preserve=all")
+            .arg(e.p("src"))
+            .arg(e.p(out))
+            .assert()
+            .success();
+
+        assert_eq!(mode(&e.p(&format!("{out}/sub"))), 0o750);
+        assert_eq!(mtime(&e.p(&format!("{out}/sub"))), 1_500_000_000, "{out}");
+        assert_eq!(mtime(&e.p(out)), 1_500_000_100, "{out}");
+    }
+}
+
+// ─── Read-only directory modes are applied after the tree is populated ─────
+
+#[test]
+fn meta_readonly_dir_mode_applied_last() {
+    let e = Env::new();
+    e.file("src/subdir/inner/f", "deep");
+    e.file("src/subdir/g", "shallow");
+    e.chmod("src/subdir", 0o500);
+    e.dir("out");
+    e.chmod("out", 0o777);
+    if nix::unistd::geteuid().is_root() {
+        // The copy runs as nobody, which must own (and so read) the source
+        for rel in [
+            "src",
+            "src/subdir",
+            "src/subdir/inner",
+            "src/subdir/inner/f",
+            "src/subdir/g",
+        ] {
+            std::os::unix::fs::chown(e.p(rel), Some(65534), Some(65534)).unwrap();
+        }
+    }
+
+    // Raw fast path, walkdir path (--backup with nothing to back up), -p
+    for (args, dst) in [
+        (&["-a"][..], "out/fast"),
+        (&["-a", "--backup=numbered"], "out/walk"),
+        (&["-R", "--preserve=mode"], "out/mode"),
+    ] {
+        cp_unprivileged(&e)
+            .args(args)
+            .arg(e.p("src"))
+            .arg(e.p(dst))
+            .assert()
+            .success();
+
+        let subdir = e.p(&format!("{dst}/subdir"));
+        assert_eq!(mode(&subdir) & 0o7777, 0o500, "{dst}");
+        assert_eq!(content(&subdir.join("inner/f")), "deep");
+        assert_eq!(content(&subdir.join("g")), "shallow");
+        e.chmod(&format!("{dst}/subdir"), 0o755); // cleanup
+    }
+    e.chmod("src/subdir", 0o755);
+}
+
+// ─── Unsupported xattr/ACL is cached per file type ──────────────────────────
+
+#[test]
+fn meta_symlink_enotsup_keeps_regular_files_enabled() {
+    use cp::metadata::{acl_supported, note_acl_error, note_xattr_error, xattr_supported};
+
+    // What a filesystem rejecting lsetxattr on links only reports
+    let enotsup = std::io::Error::from_raw_os_error(nix::libc::ENOTSUP);
+    assert!(note_xattr_error(&enotsup, true));
+    assert!(!xattr_supported(true));
+    assert!(xattr_supported(false));
+
+    assert!(note_acl_error("Operation not supported", true));
+    assert!(!acl_supported(true));
+    assert!(acl_supported(false));
+
+    // Other errors disable nothing
+    let eacces = std::io::Error::from_raw_os_error(nix::libc::EACCES);
+    assert!(!note_xattr_error(&eacces, false));
+    assert!(xattr_supported(false));
+}
+
+// ─── Timestamps: nanoseconds via utimensat, microseconds via utimes ─────────
+
+#[test]
+fn meta_set_times_keeps_nanoseconds() {
+    use std::os::unix::fs::MetadataExt;
+    let e = Env::new();
+    let f = e.file("f", "x");
+    let ts = nix::libc::timespec {
+        tv_sec: 1_500_000_000,
+        tv_nsec: 123_456_789,
+    };
+
+    cp::metadata::set_times_path(&f, ts, ts, false).unwrap();
+    let m = std::fs::metadata(&f).unwrap();
+    assert_eq!((m.mtime(), m.mtime_nsec()), (1_500_000_000, 123_456_789));
+}
+
+#[test]
+fn meta_futimes_fallback_truncates_to_microseconds() {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+    let e = Env::new();
+    let f = e.file("f", "x");
+    let ts = nix::libc::timespec {
+        tv_sec: 1_500_000_000,
+        tv_nsec: 123_456_789,
+    };
+
+    let file = std::fs::OpenOptions::new().write(true).open(&f).unwrap();
+    cp::metadata::futimes_fallback(file.as_raw_fd(), ts, ts).unwrap();
+    drop(file);
+    let m = std::fs::metadata(&f).unwrap();
+    assert_eq!((m.mtime(), m.mtime_nsec()), (1_500_000_000, 123_456_000));
+}
+
+// ─── xattrs the destination refuses are skipped with one warning ───────────
+
+#[test]
+fn meta_xattr_security_ima_skipped_with_warning() {
+    let e = Env::new();
+    let src = e.file("src/f", "content");
+    if xattr::set(&src, "security.ima", b"\x01\x02").is_err() {
+        eprintln!("SKIP: cannot set security.ima");
+        return;
+    }
+    let _ = xattr::set(&src, "user.kept", b"1");
+    e.chmod("src", 0o777);
+    e.chmod("src/f", 0o644);
+    e.dir("out");
+    e.chmod("out", 0o777);
+
+    // walkdir path, then the raw directory fast path
+    for (flags, src, dst, copied) in [
+        (&[][..], "src/f", "out/g", "out/g"),
+        (&["-R"][..], "src", "out/d", "out/d/f"),
+    ] {
+        cp_unprivileged(&e)
+            .args(flags)
+            .arg("--preserve=xattr")
+            .arg(e.p(src))
+            .arg(e.p(dst))
+            .assert()
+            .success()
+            .stderr(predicates::str::contains(format!(
+                "could not preserve 1 xattr on '{}'",
+                e.p(copied).display()
+            )));
+        assert_eq!(content(&e.p(copied)), "content");
+    }
+
+    cp_unprivileged(&e)
+        .arg("--preserve=xattr")
+        .arg("--strict-xattr")
+        .arg(&src)
+        .arg(e.p("out/strict"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("extended attributes"));
+}
+
 // ═══════════════════════════════════════════════════════════════════════════════
 // Edge case tests
 // ═══════════════════════════════════════════════════════════════════════════════
@@ -191,3 +402,342 @@ fn meta_preserve_all_except_mode() {
     // Mode should NOT match source (was excluded by --no-preserve=mode)
     assert_ne!(mode(&e.p("dst")), 0o751);
 }
+
+// ─── Ownership: fchownat, relative to the directory fd where there is one ──
+
+#[test]
+fn meta_ownership_preserved_for_files_dirs_and_symlinks() {
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("SKIP: chown needs root");
+        return;
+    }
+    let e = Env::new();
+    e.file("src/f", "content");
+    e.symlink("f", "src/link");
+    for rel in ["src", "src/f"] {
+        std::os::unix::fs::chown(e.p(rel), Some(1234), Some(5678)).unwrap();
+    }
+    std::os::unix::fs::lchown(e.p("src/link"), Some(4321), Some(8765)).unwrap();
+
+    cp().arg("-a")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    cp().arg("-P")
+        .arg("--preserve=ownership")
+        .arg(e.p("src/link"))
+        .arg(e.p("single"))
+        .assert()
+        .success();
+
+    let owner = |rel: &str| {
+        use std::os::unix::fs::MetadataExt;
+        let m = std::fs::symlink_metadata(e.p(rel)).unwrap();
+        (m.uid(), m.gid())
+    };
+    assert_eq!(owner("dst"), (1234, 5678));
+    assert_eq!(owner("dst/f"), (1234, 5678));
+    // The link itself, not its target
+    assert_eq!(owner("dst/link"), (4321, 8765));
+    assert_eq!(owner("single"), (4321, 8765));
+}
+
+// ─── --preserve=timestamps=ctime warns once ────────────────────────────────
+
+#[test]
+fn meta_preserve_ctime_warns_once() {
+    let e = Env::new();
+    for i in 0..100 {
+        e.file(&format!("src/f{i}"), "x");
+    }
+    e.file("src/sub/g", "y");
+    e.set_mtime("src/sub/g", 1_000_000_000);
+
+    let assert = cp()
+        .arg("-R")
+        .arg("--preserve=timestamps=ctime")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr);
+    assert_eq!(
+        stderr
+            .matches("cp: warning: ctime cannot be preserved; it will reflect the time of the copy operation")
+            .count(),
+        1,
+        "{stderr}"
+    );
+    // The other timestamps still are
+    assert_eq!(mtime(&e.p("dst/sub/g")), 1_000_000_000);
+}
+
+// ─── --preserve=flags ───────────────────────────────────────────────────────
+
+const FS_IOC_GETFLAGS: nix::libc::c_ulong = 0x80086601;
+const FS_IOC_SETFLAGS: nix::libc::c_ulong = 0x40086602;
+const FS_NODUMP_FL: nix::libc::c_int = 0x40;
+
+fn inode_flags(p: &std::path::Path) -> Option<nix::libc::c_int> {
+    use std::os::unix::io::AsRawFd;
+    let f = std::fs::File::open(p).unwrap();
+    let mut flags = 0;
+    (unsafe { nix::libc::ioctl(f.as_raw_fd(), FS_IOC_GETFLAGS, &mut flags) } == 0).then_some(flags)
+}
+
+#[test]
+fn meta_preserve_flags_copies_nodump() {
+    use std::os::unix::io::AsRawFd;
+    let e = Env::new();
+    let src = e.file("tree/f", "content");
+    e.file("tree/plain", "no flags");
+    let Some(flags) = inode_flags(&src) else {
+        eprintln!("SKIP: no inode flags on this filesystem");
+        return;
+    };
+    for p in [src, e.p("tree")] {
+        let f = std::fs::File::open(&p).unwrap();
+        let flags = flags | FS_NODUMP_FL;
+        assert_eq!(
+            unsafe { nix::libc::ioctl(f.as_raw_fd(), FS_IOC_SETFLAGS, &flags) },
+            0
+        );
+    }
+    let nodump = |rel: &str| inode_flags(&e.p(rel)).unwrap() & FS_NODUMP_FL != 0;
+
+    // Single file (walkdir), the raw directory fast path, then a directory
+    // on the walkdir path (-u)
+    cp().arg("--preserve=flags")
+        .arg(e.p("tree/f"))
+        .arg(e.p("single"))
+        .assert()
+        .success();
+    cp().arg("-R")
+        .arg("--preserve=flags")
+        .arg(e.p("tree"))
+        .arg(e.p("copy"))
+        .assert()
+        .success();
+    cp().arg("-R")
+        .arg("-u")
+        .arg("--preserve=flags")
+        .arg(e.p("tree"))
+        .arg(e.p("slow"))
+        .assert()
+        .success();
+    cp().arg("-a")
+        .arg(e.p("tree"))
+        .arg(e.p("archive"))
+        .assert()
+        .success();
+
+    assert!(nodump("single"));
+    assert!(nodump("copy/f"));
+    assert!(nodump("copy"));
+    assert!(!nodump("copy/plain"));
+    assert!(nodump("slow/f"));
+    assert!(nodump("slow"));
+    // Not part of -a / --preserve=all
+    assert!(!nodump("archive/f"));
+}
+
+// ─── Overwriting without -p keeps the destination's mode ─────────────────────
+
+#[test]
+fn meta_overwrite_keeps_existing_dest_mode() {
+    let e = Env::new();
+    e.file_mode("src/f", "new", 0o644);
+    e.file_mode("single", "old", 0o600);
+    for out in ["raw", "walk"] {
+        e.file_mode(&format!("{out}/src/f"), "old", 0o600);
+    }
+
+    // Single file, raw directory fast path, walkdir path
+    cp().arg(e.p("src/f")).arg(e.p("single")).assert().success();
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("raw"))
+        .assert()
+        .success();
+    cp().arg("-R")
+        .arg("--update=all")
+        .arg(e.p("src"))
+        .arg(e.p("walk"))
+        .assert()
+        .success();
+
+    // The file is truncated in place: O_CREAT's mode only applies to a new one
+    for dst in ["single", "raw/src/f", "walk/src/f"] {
+        assert_eq!(content(&e.p(dst)), "new", "{dst}");
+        assert_eq!(mode(&e.p(dst)), 0o600, "{dst}");
+    }
+
+    // --remove-destination makes a new file, which takes the usual mode
+    e.file_mode("single", "old", 0o600);
+    cp().arg("--remove-destination")
+        .arg(e.p("src/f"))
+        .arg(e.p("single"))
+        .assert()
+        .success();
+    assert_ne!(mode(&e.p("single")), 0o600);
+}
+
+// ─── --chown ─────────────────────────────────────────────────────────────────
+
+#[test]
+fn meta_chown_numeric_sets_every_destination() {
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("SKIP: chown needs root");
+        return;
+    }
+    let e = Env::new();
+    e.file("src/f", "content");
+    e.file("src/sub/g", "deep");
+    e.symlink("f", "src/link");
+    nix::unistd::mkfifo(
+        &e.p("src/fifo"),
+        nix::sys::stat::Mode::from_bits_truncate(0o644),
+    )
+    .unwrap();
+
+    cp().arg("-R")
+        .arg("--chown=1000:1000")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    // Group only: the owner is left alone
+    cp().arg("--chown=:1001")
+        .arg(e.p("src/f"))
+        .arg(e.p("single"))
+        .assert()
+        .success();
+
+    let owner = |rel: &str| {
+        use std::os::unix::fs::MetadataExt;
+        let m = std::fs::symlink_metadata(e.p(rel)).unwrap();
+        (m.uid(), m.gid())
+    };
+    for rel in [
+        "dst",
+        "dst/f",
+        "dst/sub",
+        "dst/sub/g",
+        "dst/link",
+        "dst/fifo",
+    ] {
+        assert_eq!(owner(rel), (1000, 1000), "{rel}");
+    }
+    // The link was changed, not its target in the source
+    assert_eq!(owner("src/f"), (0, 0));
+    assert_eq!(owner("single"), (0, 1001));
+}
+
+#[test]
+fn meta_chown_resolves_names() {
+    if !nix::unistd::geteuid().is_root() {
+        eprintln!("SKIP: chown needs root");
+        return;
+    }
+    let Ok(Some(user)) = nix::unistd::User::from_name("nobody") else {
+        eprintln!("SKIP: no 'nobody' user");
+        return;
+    };
+    let e = Env::new();
+    e.file("src", "content");
+
+    // USER: takes the user's login group
+    cp().arg("--chown=nobody:")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+    let m = std::fs::metadata(e.p("dst")).unwrap();
+    use std::os::unix::fs::MetadataExt;
+    assert_eq!((m.uid(), m.gid()), (user.uid.as_raw(), user.gid.as_raw()));
+
+    cp().arg("--chown=no-such-user-here")
+        .arg(e.p("src"))
+        .arg(e.p("other"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "invalid user 'no-such-user-here'",
+        ));
+    assert!(!e.p("other").exists());
+}
+
+#[test]
+fn meta_chown_without_privilege_fails_clearly() {
+    let e = Env::new();
+    e.file("src", "content");
+    e.dir("out");
+    e.chmod("src", 0o644);
+    e.chmod("out", 0o777);
+
+    // Giving a file away takes CAP_CHOWN, which the nobody user lacks
+    cp_unprivileged(&e)
+        .arg("--chown=0:0")
+        .arg(e.p("src"))
+        .arg(e.p("out/dst"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(format!(
+            "cannot change ownership of '{}'",
+            e.p("out/dst").display()
+        )))
+        .stderr(predicates::str::contains("Operation not permitted"));
+}
+
+// ─── Non-UTF-8 names: metadata follows the bytes, not a lossy conversion ────
+
+#[test]
+fn meta_preserved_for_latin1_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let e = Env::new();
+    // "café/résumé" in Latin-1: not valid UTF-8
+    let dir = e.p("src").join(OsStr::from_bytes(b"caf\xe9"));
+    let file = dir.join(OsStr::from_bytes(b"r\xe9sum\xe9"));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&file, "content").unwrap();
+    let is_root = nix::unistd::geteuid().is_root();
+    if is_root {
+        std::os::unix::fs::chown(&dir, Some(1234), Some(5678)).unwrap();
+        std::os::unix::fs::chown(&file, Some(4321), Some(8765)).unwrap();
+    }
+    e.chmod("src", 0o755);
+    std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o751)).unwrap();
+    std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o750)).unwrap();
+    for p in [&file, &dir] {
+        filetime::set_file_mtime(p, filetime::FileTime::from_unix_time(1_500_000_000, 0)).unwrap();
+    }
+
+    // Raw fast path, then walkdir
+    for (args, out) in [
+        (&["-a"][..], "fast"),
+        (&["-a", "--backup=numbered"], "walk"),
+    ] {
+        cp().args(args)
+            .arg(e.p("src"))
+            .arg(e.p(out))
+            .assert()
+            .success()
+            .stderr("");
+
+        let dst_dir = e.p(out).join(OsStr::from_bytes(b"caf\xe9"));
+        let dst_file = dst_dir.join(OsStr::from_bytes(b"r\xe9sum\xe9"));
+        assert_eq!(std::fs::read(&dst_file).unwrap(), b"content", "{out}");
+        for (src, dst) in [(&file, &dst_file), (&dir, &dst_dir)] {
+            let (s, d) = (src.metadata().unwrap(), dst.metadata().unwrap());
+            assert_eq!(d.mode(), s.mode(), "{out}: {dst:?}");
+            assert_eq!(d.mtime(), 1_500_000_000, "{out}: {dst:?}");
+            if is_root {
+                assert_eq!((d.uid(), d.gid()), (s.uid(), s.gid()), "{out}: {dst:?}");
+            }
+        }
+    }
+}
@@ -328,3 +328,62 @@ fn opts_debug_implies_verbose() {
         .success()
         .stdout(predicates::str::contains("->"));
 }
+
+// ─── --preserve / --no-preserve: the last one given wins ─────────────────────
+
+#[test]
+fn opts_preserve_no_preserve_last_wins() {
+    use cp::cli::Cli;
+    use cp::options::CopyOptions;
+
+    let opts = |args: &[&str]| {
+        let argv = ["cp"].iter().chain(args).chain(&["src", "dst"]);
+        CopyOptions::from_cli(&Cli::parse_ordered_from(argv))
+    };
+
+    let o = opts(&[
+        "--preserve=mode",
+        "--no-preserve=all",
+        "--preserve=timestamps",
+    ]);
+    assert!(o.preserve_timestamps);
+    assert!(!o.preserve_mode && !o.preserve_ownership && !o.preserve_links);
+
+    let o = opts(&["--no-preserve=all", "--preserve=mode"]);
+    assert!(o.preserve_mode && !o.preserve_timestamps);
+
+    // A bare --preserve is mode,ownership,timestamps at its own position
+    let o = opts(&[
+        "-a",
+        "--no-preserve=all",
+        "--preserve",
+        "--no-preserve=ownership",
+    ]);
+    assert!(o.preserve_mode && o.preserve_timestamps);
+    assert!(!o.preserve_ownership && !o.preserve_xattr);
+
+    // Comma lists keep their order within the command line
+    let o = opts(&[
+        "--no-preserve=mode,timestamps",
+        "--preserve=timestamps,links",
+    ]);
+    assert!(o.preserve_timestamps && o.preserve_links && !o.preserve_mode);
+}
+
+#[test]
+fn opts_no_preserve_all_then_preserve_timestamps() {
+    let e = Env::new();
+    e.file_mode("src", "content", 0o751);
+    e.set_mtime("src", 1_500_000_000);
+
+    cp().arg("--preserve=mode")
+        .arg("--no-preserve=all")
+        .arg("--preserve=timestamps")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(mtime(&e.p("dst")), 1_500_000_000);
+    assert_ne!(mode(&e.p("dst")), 0o751);
+}
@@ -184,3 +184,219 @@ fn parallel_data_integrity() {
         assert_eq!(&bytes(&e.p(rel)), data, "integrity mismatch: {rel}");
     }
 }
+
+// ─── Hard link verification (-a -v) ──────────────────────────────────────────
+
+#[test]
+fn parallel_hard_link_group_mismatch_warns() {
+    let e = Env::new();
+    populate(&e, 100);
+    e.file("src/z0", "shared");
+    e.hardlink("src/z0", "src/z1");
+    e.hardlink("src/z0", "src/z2");
+    // In name order z0 is the group's first copy, so z1 and z2 are deferred
+    // links; a non-empty directory in the way loses z2 (even for root)
+    e.dir("dst/src/z2/blocker");
+
+    cp().arg("-a")
+        .arg("-v")
+        .arg("--sort=name")
+        .arg("--ignore-errors-from=*/z2")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "has 3 links in source but 2 in destination",
+        ));
+}
+
+#[test]
+fn parallel_hard_link_outside_tree_no_warning() {
+    use predicates::prelude::*;
+
+    let e = Env::new();
+    populate(&e, 100);
+    // Links to files outside the copied tree are not part of the copy
+    e.hardlink("src/f_0007", "outside");
+    e.hardlink("src/f_0008", "src/link_0008");
+    e.hardlink("src/f_0008", "outside_too");
+
+    cp().arg("-a")
+        .arg("-v")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("hardlink group").not());
+}
+
+#[test]
+fn parallel_hard_link_group_complete_no_warning() {
+    use predicates::prelude::*;
+
+    let e = Env::new();
+    populate(&e, 100);
+    for i in 0..10 {
+        e.hardlink(&format!("src/f_{i:04}"), &format!("src/link_{i:04}"));
+    }
+
+    cp().arg("-a")
+        .arg("-v")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("hardlink group").not());
+}
+
+#[test]
+fn parallel_hard_link_first_occurrence_failure_falls_back_to_copy() {
+    let e = Env::new();
+    populate(&e, 100);
+    e.file("src/l0", "shared");
+    for i in 1..5 {
+        e.hardlink("src/l0", &format!("src/l{i}"));
+    }
+    // Non-empty directories in the way make l0..l3 fail even for root;
+    // whichever of them is copied first leaves its group without a target
+    for i in 0..4 {
+        e.dir(&format!("dst/src/l{i}/blocker"));
+    }
+
+    cp().arg("-R")
+        .arg("--preserve=links")
+        .arg("--ignore-errors-from=*/l[0-3]")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("warning"));
+
+    assert_eq!(content(&e.p("dst/src/l4")), "shared");
+    assert_eq!(content(&e.p("dst/src/f_0050")), "data_50");
+}
+
+/// The same on the walkdir path (-u), with parallel copies and sequential
+/// ones (verbose lines in tree order).
+#[test]
+fn walkdir_hard_link_first_occurrence_failure_falls_back_to_copy() {
+    for extra in [&["-u"][..], &["-u", "-v", "--verbose-order=tree"]] {
+        let e = Env::new();
+        populate(&e, 100);
+        e.file("src/l0", "shared");
+        for i in 1..5 {
+            e.hardlink("src/l0", &format!("src/l{i}"));
+        }
+        // Older than the source, so -u doesn't skip them
+        for i in 0..4 {
+            e.dir(&format!("dst/src/l{i}/blocker"));
+            e.set_mtime(&format!("dst/src/l{i}"), 1_000_000_000);
+        }
+
+        cp().arg("-R")
+            .args(extra)
+            .arg("--preserve=links")
+            .arg("--ignore-errors-from=*/l[0-3]")
+            .arg(e.p("src"))
+            .arg(e.p("dst"))
+            .assert()
+            .success()
+            .stderr(predicates::str::contains("warning"));
+
+        assert_eq!(content(&e.p("dst/src/l4")), "shared", "{extra:?}");
+        assert_eq!(content(&e.p("dst/src/f_0050")), "data_50", "{extra:?}");
+    }
+}
+
+// ─── Walkdir (slow) path: parallel vs sequential ─────────────────────────────
+
+/// Snapshot of a flat directory: sorted (name, content) pairs.
+fn snapshot(dir: &std::path::Path) -> Vec<(String, String)> {
+    let mut v: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| {
+            let e = e.unwrap();
+            (
+                e.file_name().to_string_lossy().into_owned(),
+                content(&e.path()),
+            )
+        })
+        .collect();
+    v.sort();
+    v
+}
+
+#[test]
+fn parallel_walkdir_matches_sequential() {
+    let e = Env::new();
+    populate(&e, 500);
+
+    // -n forces the walkdir path and allows parallel copies
+    cp().arg("-Rn")
+        .arg(e.p("src"))
+        .arg(e.p("par"))
+        .assert()
+        .success();
+
+    // -i keeps the walkdir path sequential (no prompts: destination is fresh)
+    cp().arg("-Ri")
+        .arg(e.p("src"))
+        .arg(e.p("seq"))
+        .assert()
+        .success();
+
+    assert_eq!(file_count(&e.p("par")), 500);
+    assert_eq!(snapshot(&e.p("par")), snapshot(&e.p("seq")));
+}
+
+#[test]
+fn parallel_walkdir_jobs_and_hard_links() {
+    let e = Env::new();
+    populate(&e, 200);
+    for i in 0..20 {
+        e.hardlink(&format!("src/f_{i:04}"), &format!("src/link_{i:04}"));
+    }
+
+    cp().arg("-Rn")
+        .arg("--preserve=links")
+        .arg("--jobs=3")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(file_count(&e.p("dst")), 220);
+    for i in 0..20 {
+        assert_eq!(
+            ino(&e.p(&format!("dst/f_{i:04}"))),
+            ino(&e.p(&format!("dst/link_{i:04}"))),
+            "hardlink pair {i} should share inode"
+        );
+    }
+}
+
+// ─── Many top-level sources (as from xargs) ──────────────────────────────────
+
+#[test]
+fn parallel_many_command_line_sources() {
+    let e = Env::new();
+    populate(&e, 3000);
+    e.dir("dst");
+
+    let mut sources: Vec<_> = (0..3000).map(|i| e.p(&format!("src/f_{i:04}"))).collect();
+    sources.push(e.p("src/missing"));
+
+    let started = std::time::Instant::now();
+    cp().args(&sources)
+        .arg(e.p("dst"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("missing"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(30));
+
+    assert_eq!(file_count(&e.p("dst")), 3000);
+    for i in [0, 1234, 2999] {
+        assert_eq!(content(&e.p(&format!("dst/f_{i:04}"))), format!("data_{i}"));
+    }
+}
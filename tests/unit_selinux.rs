@@ -0,0 +1,223 @@
+//! Tests — SELinux context assignment (selinux.rs, requires `--features selinux`)
+#![cfg(feature = "selinux")]
+
+mod common;
+use common::*;
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use cp::selinux::{
+    ContextBackend, SharedBackend, apply_context, copy_context, preserve_context_with,
+};
+
+/// Mock backend recording every `set_context` call.
+struct MockSelinux {
+    enabled: bool,
+    labels: Mutex<Vec<(PathBuf, String)>>,
+    set: Mutex<Vec<(PathBuf, String)>>,
+    /// errno `set_context` fails with, if any
+    set_errno: Option<i32>,
+}
+
+impl MockSelinux {
+    fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            labels: Mutex::new(Vec::new()),
+            set: Mutex::new(Vec::new()),
+            set_errno: None,
+        }
+    }
+}
+
+impl ContextBackend for MockSelinux {
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn default_context(&self, _path: &Path, _mode: u32) -> io::Result<String> {
+        Ok("system_u:object_r:default_t:s0".into())
+    }
+
+    fn get_context(&self, path: &Path) -> io::Result<String> {
+        match self.labels.lock().unwrap().iter().find(|(p, _)| p == path) {
+            Some((_, c)) => Ok(c.clone()),
+            None => Err(io::Error::from_raw_os_error(nix::libc::ENODATA)),
+        }
+    }
+
+    fn set_context(&self, path: &Path, context: &str) -> io::Result<()> {
+        if let Some(errno) = self.set_errno {
+            return Err(io::Error::from_raw_os_error(errno));
+        }
+        self.set
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), context.to_string()));
+        Ok(())
+    }
+}
+
+#[test]
+fn selinux_explicit_label_applied() {
+    use std::os::unix::net::UnixListener;
+
+    let e = Env::new();
+    e.file("src/f", "data");
+    e.file("src/sub/g", "data");
+    let _listener = UnixListener::bind(e.p("src/sock")).unwrap();
+    let label = "user_u:object_r:tmp_t:s0";
+
+    let mock = Arc::new(MockSelinux::new(true));
+    let mut opts = lib_opts(&["-R", &format!("--context={label}")]);
+    opts.selinux_backend = SharedBackend(mock.clone());
+    cp::dir::copy_directory(&e.p("src"), &e.p("dst"), &opts).unwrap();
+
+    // Every file and directory the copy created, and nothing for the socket
+    let mut set = mock.set.lock().unwrap().clone();
+    set.sort();
+    let expected: Vec<_> = ["dst", "dst/f", "dst/sub", "dst/sub/g"]
+        .iter()
+        .map(|p| (e.p(p), label.to_string()))
+        .collect();
+    assert_eq!(set, expected);
+}
+
+#[test]
+fn selinux_preserve_context_copies_directory_labels() {
+    let e = Env::new();
+    e.file("src/sub/f", "data");
+    let mock = Arc::new(MockSelinux::new(true));
+    for (p, label) in [
+        ("src", "dir_t"),
+        ("src/sub", "sub_t"),
+        ("src/sub/f", "file_t"),
+    ] {
+        let context = format!("user_u:object_r:{label}:s0");
+        mock.labels.lock().unwrap().push((e.p(p), context));
+    }
+
+    // Without -a, nothing else asks for the directories' metadata
+    let mut opts = lib_opts(&["-R", "--preserve=context"]);
+    opts.selinux_backend = SharedBackend(mock.clone());
+    cp::dir::copy_directory(&e.p("src"), &e.p("dst"), &opts).unwrap();
+
+    let mut set = mock.set.lock().unwrap().clone();
+    set.sort();
+    let expected: Vec<_> = [
+        ("dst", "dir_t"),
+        ("dst/sub", "sub_t"),
+        ("dst/sub/f", "file_t"),
+    ]
+    .iter()
+    .map(|(p, label)| (e.p(p), format!("user_u:object_r:{label}:s0")))
+    .collect();
+    assert_eq!(set, expected);
+}
+
+#[test]
+fn selinux_empty_label_uses_default() {
+    let e = Env::new();
+    e.file("dst.txt", "data");
+
+    let mock = MockSelinux::new(true);
+    apply_context(&mock, &e.p("dst.txt"), "").unwrap();
+
+    assert_eq!(
+        mock.set.lock().unwrap()[0].1,
+        "system_u:object_r:default_t:s0"
+    );
+}
+
+#[test]
+fn selinux_disabled_is_noop() {
+    let e = Env::new();
+    e.file("dst.txt", "data");
+
+    let mock = MockSelinux::new(false);
+    apply_context(&mock, &e.p("dst.txt"), "user_u:object_r:tmp_t:s0").unwrap();
+
+    assert!(mock.set.lock().unwrap().is_empty());
+}
+
+#[test]
+fn selinux_copy_context_from_source() {
+    let e = Env::new();
+    e.file("src.txt", "data");
+    e.file("dst.txt", "data");
+
+    let mock = MockSelinux::new(true);
+    mock.labels
+        .lock()
+        .unwrap()
+        .push((e.p("src.txt"), "user_u:object_r:custom_t:s0".into()));
+    copy_context(&mock, &e.p("src.txt"), &e.p("dst.txt")).unwrap();
+
+    let set = mock.set.lock().unwrap();
+    assert_eq!(set[0].0, e.p("dst.txt"));
+    assert_eq!(set[0].1, "user_u:object_r:custom_t:s0");
+}
+
+#[test]
+fn selinux_copy_context_unlabeled_source_skipped() {
+    let e = Env::new();
+    e.file("src.txt", "data");
+    e.file("dst.txt", "data");
+
+    let mock = MockSelinux::new(true);
+    copy_context(&mock, &e.p("src.txt"), &e.p("dst.txt")).unwrap();
+
+    assert!(mock.set.lock().unwrap().is_empty());
+}
+
+#[test]
+fn selinux_context_failure_fails_only_explicit_preserve() {
+    let e = Env::new();
+    e.file("src.txt", "data");
+    e.file("dst.txt", "data");
+    let mut mock = MockSelinux::new(true);
+    mock.labels
+        .lock()
+        .unwrap()
+        .push((e.p("src.txt"), "user_u:object_r:tmp_t:s0".into()));
+    mock.set_errno = Some(nix::libc::EACCES);
+
+    let opts = |flag: &str| {
+        let cli = cp::cli::Cli::parse_ordered_from(["cp", flag, "src", "dst"]);
+        cp::options::CopyOptions::from_cli(&cli)
+    };
+    // -a: a warning, the copy goes on
+    preserve_context_with(&mock, &e.p("src.txt"), &e.p("dst.txt"), &opts("-a")).unwrap();
+    // Asked for by name: an error
+    let explicit = opts("--preserve=context");
+    assert!(preserve_context_with(&mock, &e.p("src.txt"), &e.p("dst.txt"), &explicit).is_err());
+}
+
+/// Real labels: only meaningful on an SELinux-enabled host.
+#[test]
+fn selinux_archive_preserves_context() {
+    if !std::path::Path::new("/sys/fs/selinux/enforce").exists() {
+        eprintln!("SKIP: SELinux not enabled");
+        return;
+    }
+
+    let e = Env::new();
+    e.file("src.txt", "data");
+    let label = xattr::get(e.p("src.txt"), "security.selinux")
+        .ok()
+        .flatten()
+        .expect("labeled source");
+
+    cp().arg("-a")
+        .arg(e.p("src.txt"))
+        .arg(e.p("dst.txt"))
+        .assert()
+        .success();
+
+    assert_eq!(
+        xattr::get(e.p("dst.txt"), "security.selinux").unwrap(),
+        Some(label)
+    );
+}
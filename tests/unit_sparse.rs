@@ -183,7 +183,7 @@ fn sparse_below_threshold() {
         .assert()
         .success()
         // Should NOT use sparse method, but copy_file_range
-        .stderr(predicates::str::contains("copy_file_range"));
+        .stderr(predicates::str::contains(KERNEL_COPY));
 
     assert_eq!(bytes(&e.p("dst")), data);
 }
@@ -203,3 +203,202 @@ fn sparse_at_threshold_boundary() {
     assert_eq!(file_size(&e.p("src")), file_size(&e.p("dst")));
     assert_eq!(bytes(&e.p("src")), bytes(&e.p("dst")));
 }
+
+#[test]
+fn sparse_detect_ratio_skips_files_with_small_holes() {
+    let e = Env::new();
+    // 64 KiB hole between two 512 KiB data regions: about 6% holes
+    let half = 512 * 1024;
+    let data = vec![0x5Au8; half as usize];
+    sparse_file(&e, "src", &[(0, &data), (half + 64 * 1024, &data)], 0);
+
+    let debug = |ratio: Option<&str>, dst: &str| {
+        let mut cmd = cp();
+        cmd.arg("--sparse=auto").arg("--debug");
+        if let Some(r) = ratio {
+            cmd.arg("--sparse-detect-ratio").arg(r);
+        }
+        let out = cmd.arg(e.p("src")).arg(e.p(dst)).output().unwrap();
+        assert!(out.status.success());
+        assert_eq!(bytes(&e.p(dst)), bytes(&e.p("src")));
+        String::from_utf8_lossy(&out.stderr).contains("sparse (SEEK_HOLE/SEEK_DATA)")
+    };
+
+    assert!(debug(None, "default"), "any hole triggers a sparse copy");
+    assert!(!debug(Some("0.5"), "ratio"), "6% holes are under 0.5");
+    assert!(debug(Some("0.05"), "low_ratio"));
+
+    cp().arg("--sparse-detect-ratio=1.5")
+        .arg(e.p("src"))
+        .arg(e.p("bad"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("invalid ratio"));
+}
+
+#[test]
+fn sparse_heuristic_follows_zero_density() {
+    let e = Env::new();
+    // 1 MiB written out in full: 80% zeros up front, then data. No holes for
+    // SEEK_HOLE to find, but the 64 KiB sample is all zeros
+    let mut mostly_zero = vec![0u8; 1024 * 1024];
+    mostly_zero[819 * 1024..].fill(0x77);
+    e.file("zeros", &mostly_zero);
+    e.file("dense", vec![0xABu8; 1024 * 1024]);
+
+    let debug = |src: &str, dst: &str| {
+        let out = cp()
+            .arg("--sparse=heuristic")
+            .arg("--debug")
+            .arg(e.p(src))
+            .arg(e.p(dst))
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        assert_eq!(bytes(&e.p(dst)), bytes(&e.p(src)));
+        String::from_utf8_lossy(&out.stderr).contains("sparse (zero detection)")
+    };
+
+    assert!(debug("zeros", "zeros_dst"));
+    assert!(blocks(&e.p("zeros_dst")) < blocks(&e.p("zeros")));
+    assert!(!debug("dense", "dense_dst"));
+    assert_eq!(blocks(&e.p("dense_dst")), blocks(&e.p("dense")));
+}
+
+#[test]
+fn sparse_large_data_regions_copied_exactly() {
+    let e = Env::new();
+    // 64 MiB: three 8 MiB data regions, several copy_file_range chunks'
+    // worth of sparse buffer, with a patterned byte so misplaced data shows
+    let region = 8 * 1024 * 1024usize;
+    let data: Vec<Vec<u8>> = (0..3u8)
+        .map(|k| (0..region).map(|i| (i % 251) as u8 ^ k).collect())
+        .collect();
+    let total = 64 * 1024 * 1024u64;
+    sparse_file(
+        &e,
+        "src",
+        &[
+            (0, &data[0]),
+            (24 * 1024 * 1024, &data[1]),
+            (48 * 1024 * 1024, &data[2]),
+        ],
+        total,
+    );
+
+    cp().arg("--sparse=auto")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(file_size(&e.p("dst")), total);
+    assert!(blocks(&e.p("dst")) <= blocks(&e.p("src")) + 64);
+    assert!(bytes(&e.p("src")) == bytes(&e.p("dst")));
+}
+
+#[test]
+fn sparse_no_sparse_on_copy_file_range_skips_detection() {
+    use predicates::prelude::*;
+
+    let e = Env::new();
+    sparse_file(
+        &e,
+        "src",
+        &[(4 * 1024 * 1024, &[0x5A; 8192])],
+        16 * 1024 * 1024,
+    );
+
+    cp().arg("--sparse=auto")
+        .arg("--debug")
+        .arg("--no-sparse-on-copy-file-range")
+        .arg(e.p("src"))
+        .arg(e.p("skipped"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains(
+            "sparse: skipped (copy_file_range handles holes natively)",
+        ));
+    cp().arg("--sparse=auto")
+        .arg("--debug")
+        .arg(e.p("src"))
+        .arg(e.p("scanned"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("sparse: skipped").not());
+
+    // Whether the holes survive is up to the filesystem; the data must not
+    assert!(bytes(&e.p("src")) == bytes(&e.p("skipped")));
+    assert!(bytes(&e.p("src")) == bytes(&e.p("scanned")));
+
+    // A forced read/write engine never skips the scan
+    cp().arg("--debug")
+        .arg("--no-sparse-on-copy-file-range")
+        .arg("--reflink-fallback=read-write")
+        .arg(e.p("src"))
+        .arg(e.p("rw"))
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("sparse: skipped").not());
+}
+
+#[test]
+fn sparse_hole_support_probe() {
+    use cp::sparse::dest_supports_holes;
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        use cp::sparse::fs_type_supports_holes;
+        use nix::sys::statfs::{EXT4_SUPER_MAGIC, FsType, MSDOS_SUPER_MAGIC, TMPFS_MAGIC};
+
+        assert!(!fs_type_supports_holes(MSDOS_SUPER_MAGIC));
+        assert!(!fs_type_supports_holes(FsType(0x2011_BAB0 as _))); // exFAT
+        assert!(fs_type_supports_holes(EXT4_SUPER_MAGIC));
+        assert!(fs_type_supports_holes(TMPFS_MAGIC));
+    }
+    #[cfg(target_os = "macos")]
+    {
+        use cp::sparse::fs_name_supports_holes;
+
+        assert!(!fs_name_supports_holes("msdos"));
+        assert!(!fs_name_supports_holes("exfat"));
+        assert!(!fs_name_supports_holes("hfs"));
+        assert!(fs_name_supports_holes("apfs"));
+    }
+
+    let e = Env::new();
+    let f = std::fs::File::create(e.p("probe")).unwrap();
+    assert!(dest_supports_holes(&f));
+    // Cached for the device: asking again gives the same answer
+    assert!(dest_supports_holes(&f));
+}
+
+#[test]
+fn sparse_auto_to_tmpfs() {
+    let shm = std::path::Path::new("/dev/shm");
+    if !shm.is_dir() {
+        eprintln!("SKIP: no /dev/shm");
+        return;
+    }
+    let e = Env::new();
+    sparse_file(
+        &e,
+        "src",
+        &[(2 * 1024 * 1024, &[0x7E; 4096])],
+        8 * 1024 * 1024,
+    );
+    let dst_dir = tempfile::TempDir::new_in(shm).unwrap();
+    let dst = dst_dir.path().join("dst");
+
+    // tmpfs keeps holes, so the sparse copy is used
+    cp().arg("--sparse=auto")
+        .arg("--debug")
+        .arg(e.p("src"))
+        .arg(&dst)
+        .assert()
+        .success()
+        .stderr(predicates::str::contains("SEEK_HOLE/SEEK_DATA"));
+
+    assert!(bytes(&e.p("src")) == bytes(&dst));
+    assert!(blocks(&dst) < 8 * 1024 * 1024 / 512);
+}
@@ -0,0 +1,165 @@
+//! Tests — tar source extraction (tar_src.rs, --from-format=tar)
+
+mod common;
+use common::*;
+
+use std::path::Path;
+
+const MTIME: u64 = 1_600_000_000;
+
+fn header(kind: tar::EntryType, mode: u32, size: u64) -> tar::Header {
+    let mut h = tar::Header::new_gnu();
+    h.set_entry_type(kind);
+    h.set_mode(mode);
+    h.set_size(size);
+    h.set_mtime(MTIME);
+    h.set_uid(0);
+    h.set_gid(0);
+    h
+}
+
+/// Archive with a directory, a file, a symlink to it and a hard link to it.
+fn build_archive(path: &Path) {
+    let mut b = tar::Builder::new(std::fs::File::create(path).unwrap());
+
+    let mut h = header(tar::EntryType::Directory, 0o750, 0);
+    b.append_data(&mut h, "dir/", std::io::empty()).unwrap();
+
+    let data = b"archived data";
+    let mut h = header(tar::EntryType::Regular, 0o640, data.len() as u64);
+    b.append_data(&mut h, "dir/file.txt", &data[..]).unwrap();
+
+    let mut h = header(tar::EntryType::Symlink, 0o777, 0);
+    b.append_link(&mut h, "dir/link", "file.txt").unwrap();
+
+    let mut h = header(tar::EntryType::Link, 0o640, 0);
+    b.append_link(&mut h, "dir/hard", "dir/file.txt").unwrap();
+
+    b.finish().unwrap();
+}
+
+#[test]
+fn tar_extract_preserves_modes_and_links() {
+    let e = Env::new();
+    build_archive(&e.p("a.tar"));
+
+    cp().arg("-a")
+        .arg("--from-format=tar")
+        .arg(e.p("a.tar"))
+        .arg(e.p("out"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("out/dir/file.txt")), "archived data");
+    assert_eq!(mode(&e.p("out/dir/file.txt")), 0o640);
+    assert_eq!(mode(&e.p("out/dir")), 0o750);
+    assert_eq!(
+        std::fs::read_link(e.p("out/dir/link")).unwrap(),
+        Path::new("file.txt")
+    );
+    assert_eq!(ino(&e.p("out/dir/hard")), ino(&e.p("out/dir/file.txt")));
+
+    let mtime = |p: &Path| {
+        std::fs::symlink_metadata(p)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    };
+    assert_eq!(mtime(&e.p("out/dir/file.txt")), MTIME);
+    assert_eq!(mtime(&e.p("out/dir")), MTIME);
+}
+
+#[test]
+fn tar_extract_without_preserve_copies_hard_links() {
+    let e = Env::new();
+    build_archive(&e.p("a.tar"));
+    e.dir("out");
+
+    cp().arg("--from-format=tar")
+        .arg(e.p("a.tar"))
+        .arg(e.p("out"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("out/dir/hard")), "archived data");
+    assert_ne!(ino(&e.p("out/dir/hard")), ino(&e.p("out/dir/file.txt")));
+}
+
+#[test]
+fn tar_extract_rejects_parent_traversal() {
+    let e = Env::new();
+    {
+        let mut b = tar::Builder::new(std::fs::File::create(e.p("evil.tar")).unwrap());
+        let data = b"escaped";
+        let mut h = header(tar::EntryType::Regular, 0o644, data.len() as u64);
+        // set_path refuses "..", so write the name bytes directly
+        h.as_old_mut().name[..10].copy_from_slice(b"../escaped");
+        h.set_cksum();
+        b.append(&h, &data[..]).unwrap();
+        b.finish().unwrap();
+    }
+
+    cp().arg("--from-format=tar")
+        .arg(e.p("evil.tar"))
+        .arg(e.p("out"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("unsafe entry path"));
+
+    assert!(!e.p("escaped").exists());
+}
+
+#[test]
+fn tar_extract_rejects_hard_link_through_symlink() {
+    let e = Env::new();
+    e.file("secret", "SECRET");
+    {
+        let mut b = tar::Builder::new(std::fs::File::create(e.p("evil.tar")).unwrap());
+        let mut h = header(tar::EntryType::Symlink, 0o777, 0);
+        b.append_link(&mut h, "evil", e.p("secret")).unwrap();
+        let mut h = header(tar::EntryType::Link, 0o644, 0);
+        b.append_link(&mut h, "x", "evil").unwrap();
+        b.finish().unwrap();
+    }
+
+    cp().arg("--from-format=tar")
+        .arg(e.p("evil.tar"))
+        .arg(e.p("out"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("hard link to symlink"));
+
+    assert!(!e.p("out/x").exists());
+}
+
+#[test]
+fn tar_extract_replaces_symlink_with_directory() {
+    let e = Env::new();
+    e.dir("victim");
+    e.chmod("victim", 0o700);
+    {
+        let mut b = tar::Builder::new(std::fs::File::create(e.p("evil.tar")).unwrap());
+        let mut h = header(tar::EntryType::Symlink, 0o777, 0);
+        b.append_link(&mut h, "evil", e.p("victim")).unwrap();
+        let mut h = header(tar::EntryType::Directory, 0o777, 0);
+        h.set_mtime(1000);
+        b.append_data(&mut h, "evil/", std::io::empty()).unwrap();
+        b.finish().unwrap();
+    }
+
+    cp().arg("-p")
+        .arg("--from-format=tar")
+        .arg(e.p("evil.tar"))
+        .arg(e.p("out"))
+        .assert()
+        .success();
+
+    assert_eq!(mode(&e.p("victim")), 0o700);
+    assert_ne!(mtime(&e.p("victim")), 1000);
+    assert!(!is_symlink(&e.p("out/evil")));
+    assert_eq!(mtime(&e.p("out/evil")), 1000);
+    assert_eq!(mode(&e.p("out/evil")), 0o777);
+}
@@ -0,0 +1,129 @@
+//! Tests — per-step I/O timeout (timeout.rs, --io-timeout)
+
+mod common;
+use common::*;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use cp::timeout::with_idle_timeout;
+
+#[test]
+fn timeout_fast_operation_returns_value() {
+    let r = with_idle_timeout(Duration::from_secs(5), || false, || 42);
+    assert_eq!(r.unwrap(), 42);
+}
+
+#[test]
+fn timeout_stalled_operation_aborts() {
+    // Stand-in for a stalled NFS read: sleeps far longer than the timeout
+    let start = Instant::now();
+    let r = with_idle_timeout(
+        Duration::from_secs(1),
+        || false,
+        || {
+            std::thread::sleep(Duration::from_secs(10));
+        },
+    );
+    assert!(r.is_err());
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn timeout_deadline_moves_with_progress() {
+    // Twice the timeout in all, but never idle for long
+    let ticks = Arc::new(AtomicU64::new(0));
+    let worker = Arc::clone(&ticks);
+    let mut seen = 0;
+    let progressed = || {
+        let now = ticks.load(Ordering::Relaxed);
+        std::mem::replace(&mut seen, now) != now
+    };
+    let r = with_idle_timeout(Duration::from_millis(500), progressed, move || {
+        for _ in 0..10 {
+            std::thread::sleep(Duration::from_millis(100));
+            worker.fetch_add(1, Ordering::Relaxed);
+        }
+        7
+    });
+    assert_eq!(r.unwrap(), 7);
+}
+
+#[test]
+fn timeout_stalled_copy_stops_writing() {
+    use std::io::Write;
+
+    let e = Env::new();
+    let fifo = e.p("fifo");
+    nix::unistd::mkfifo(&fifo, nix::sys::stat::Mode::from_bits_truncate(0o644)).unwrap();
+    // Read-write: open doesn't wait for a reader, and the FIFO never hits EOF
+    let mut writer = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&fifo)
+        .unwrap();
+    let src = std::fs::File::open(&fifo).unwrap();
+    let dst = std::fs::File::create(e.p("dst")).unwrap();
+
+    let r = cp::engine::copy_unsized(
+        &src,
+        &dst,
+        &fifo,
+        &e.p("dst"),
+        Some(Duration::from_secs(1)),
+        &indicatif::ProgressBar::hidden(),
+    );
+    assert!(matches!(r, Err(cp::error::CpError::Timeout { .. })));
+
+    // The stalled read returns now; what it got must not reach the copy
+    writer.write_all(b"late").unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(bytes(&e.p("dst")), b"");
+}
+
+#[test]
+fn timeout_cli_zero_rejected() {
+    let e = Env::new();
+    e.file("src", "x");
+
+    cp().arg("--io-timeout=0")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .failure();
+    assert!(!e.p("dst").exists());
+}
+
+#[test]
+fn timeout_cli_copy_completes() {
+    let e = Env::new();
+    let data: Vec<u8> = (0..=255u8).cycle().take(300_000).collect();
+    e.file("src.bin", &data);
+
+    cp().arg("--io-timeout")
+        .arg("5")
+        .arg(e.p("src.bin"))
+        .arg(e.p("dst.bin"))
+        .assert()
+        .success();
+
+    assert_eq!(bytes(&e.p("dst.bin")), data);
+}
+
+#[test]
+fn timeout_cli_recursive_copy_completes() {
+    let e = Env::new();
+    e.file("src/a.txt", "a");
+    e.file("src/sub/b.txt", "b");
+
+    cp().arg("-R")
+        .arg("--io-timeout=5")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dst/a.txt")), "a");
+    assert_eq!(content(&e.p("dst/sub/b.txt")), "b");
+}
@@ -74,6 +74,119 @@ fn build_dest_path_parents_mode() {
     assert_eq!(result, PathBuf::from("/tmp/dir/a/b/c"));
 }
 
+#[test]
+fn build_dest_path_strip_prefix() {
+    use cp::util::build_dest_path;
+    use std::path::Path;
+
+    let strip = Some(Path::new("/a/b"));
+    assert_eq!(
+        build_dest_path(
+            Path::new("/a/b/c/file.txt"),
+            Path::new("/dst"),
+            true,
+            false,
+            strip
+        )
+        .unwrap(),
+        PathBuf::from("/dst/c/file.txt")
+    );
+    // The prefix wins over --parents
+    assert_eq!(
+        build_dest_path(
+            Path::new("/a/b/c/d/f"),
+            Path::new("/dst"),
+            true,
+            true,
+            strip
+        )
+        .unwrap(),
+        PathBuf::from("/dst/c/d/f")
+    );
+    assert!(build_dest_path(Path::new("/x/c/f"), Path::new("/dst"), true, false, strip).is_err());
+}
+
+#[test]
+fn strip_prefix_cli_multi_level() {
+    let e = Env::new();
+    e.dir("dest");
+    let deep = e.file("a/b/c/d/file.txt", "deep");
+    let shallow = e.file("a/b/top.txt", "top");
+    let other = e.file("elsewhere/x.txt", "x");
+
+    cp().arg("--strip-prefix")
+        .arg(e.p("a/b"))
+        .arg(&deep)
+        .arg(&shallow)
+        .arg(&other)
+        .arg(e.p("dest"))
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("is not under --strip-prefix"));
+
+    // Matching sources are still copied
+    assert_eq!(content(&e.p("dest/c/d/file.txt")), "deep");
+    assert_eq!(content(&e.p("dest/top.txt")), "top");
+    assert!(!e.p("dest/x.txt").exists());
+}
+
+#[test]
+fn strip_prefix_cli_directory() {
+    let e = Env::new();
+    e.dir("dest");
+    e.file("a/b/c/tree/f", "in tree");
+
+    cp().arg("-R")
+        .arg("--strip-prefix")
+        .arg(e.p("a"))
+        .arg(e.p("a/b/c/tree"))
+        .arg(e.p("dest"))
+        .assert()
+        .success();
+
+    assert_eq!(content(&e.p("dest/b/c/tree/f")), "in tree");
+}
+
+// ─── path properties ────────────────────────────────────────────────────────
+
+/// Arbitrary strings, plus short ones made mostly of `/` and `.` so that
+/// slashes and dot components turn up often.
+fn any_path() -> impl proptest::strategy::Strategy<Value = PathBuf> {
+    use proptest::prelude::*;
+
+    prop_oneof![any::<String>(), "[a./]{0,12}"].prop_map(PathBuf::from)
+}
+
+proptest::proptest! {
+    #[test]
+    fn prop_strip_trailing_slashes(p in any_path()) {
+        use cp::util::strip_trailing_slashes;
+
+        let stripped = strip_trailing_slashes(&p);
+        let s = stripped.to_str().unwrap();
+        let trimmed = p.to_str().unwrap().trim_end_matches('/');
+        proptest::prop_assert_eq!(s, if trimmed.is_empty() { "/" } else { trimmed });
+        proptest::prop_assert!(s == "/" || !s.ends_with('/'));
+        proptest::prop_assert_eq!(strip_trailing_slashes(&stripped), stripped.clone());
+    }
+
+    #[test]
+    fn prop_build_dest_path(src in any_path(), dst in any_path(), is_dir: bool, parents: bool) {
+        use cp::util::build_dest_path;
+
+        // A source without a name ("/", "/a/..") is joined whole, and an
+        // absolute one replaces dst
+        proptest::prop_assume!(src.file_name().is_some());
+        let out = build_dest_path(&src, &dst, is_dir, parents, None).unwrap();
+        proptest::prop_assert!(out.starts_with(&dst));
+        if is_dir {
+            proptest::prop_assert_eq!(out.file_name(), src.file_name());
+        } else {
+            proptest::prop_assert_eq!(&out, &dst);
+        }
+    }
+}
+
 // ─── resolve_target ─────────────────────────────────────────────────────────
 
 #[test]
@@ -130,6 +243,26 @@ fn same_file_hard_link() {
         .stderr(predicates::str::contains("same file"));
 }
 
+#[test]
+fn same_file_through_symlinks() {
+    let e = Env::new();
+    e.file("a", "x");
+    e.symlink(e.p("a"), "to_a");
+
+    // Destination link to the source, then a source link (-P keeps it
+    // unresolved in the first stat) onto its own target
+    for (flags, src, dst) in [("-f", "a", "to_a"), ("-P", "to_a", "a")] {
+        cp().arg(flags)
+            .arg(e.p(src))
+            .arg(e.p(dst))
+            .assert()
+            .failure()
+            .stderr(predicates::str::contains("same file"));
+    }
+    assert_eq!(content(&e.p("a")), "x");
+    assert!(is_symlink(&e.p("to_a")));
+}
+
 #[test]
 fn same_file_self() {
     let e = Env::new();
@@ -224,3 +357,163 @@ fn resolve_target_t_flag() {
     assert_eq!(content(&e.p("target/src2")), "b");
     assert_eq!(content(&e.p("target/src3")), "c");
 }
+
+#[test]
+fn resolve_target_t_relative_and_symlinked_dir() {
+    let e = Env::new();
+    e.file("work/src1", "a");
+    e.file("work/sub/src2", "b");
+    e.dir("work/target");
+    e.symlink("target", "work/link");
+
+    // Relative -t DIR and sources both resolve against the working directory
+    for dir in ["target", "target/", "link", "link/"] {
+        cp().current_dir(e.p("work"))
+            .args(["-t", dir, "src1", "sub/src2"])
+            .assert()
+            .success();
+    }
+
+    assert_eq!(content(&e.p("work/target/src1")), "a");
+    assert_eq!(content(&e.p("work/target/src2")), "b");
+    assert!(is_symlink(&e.p("work/link")));
+    assert!(!e.p("work/sub/target").exists());
+}
+
+#[test]
+fn resolve_target_t_missing_or_file() {
+    let e = Env::new();
+    e.file("src", "a");
+    e.file("file", "f");
+
+    cp().current_dir(e.path())
+        .args(["-t", "newdir/", "src"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "target directory 'newdir/': No such file or directory",
+        ));
+    assert!(!e.p("newdir").exists());
+
+    cp().current_dir(e.path())
+        .args(["-t", "file", "src"])
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains(
+            "target directory 'file': Not a directory",
+        ));
+    assert_eq!(content(&e.p("file")), "f");
+}
+
+// ─── human_size ─────────────────────────────────────────────────────────────
+
+#[test]
+fn human_size_units() {
+    use cp::util::human_size;
+
+    assert_eq!(human_size(0), "0 B");
+    assert_eq!(human_size(1023), "1023 B");
+    assert_eq!(human_size(1024), "1.0 KiB");
+    assert_eq!(human_size(4_404_019), "4.2 MiB");
+    assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+}
+
+// ─── build_link_dest_path ───────────────────────────────────────────────────
+
+#[test]
+fn link_dest_path_mirrors_destination() {
+    use cp::util::build_link_dest_path;
+    use std::path::{Path, PathBuf};
+
+    assert_eq!(
+        build_link_dest_path(Path::new("new/a/b"), Path::new("prev"), Path::new("new")),
+        PathBuf::from("prev/a/b")
+    );
+    // Outside the root: fall back to the file name
+    assert_eq!(
+        build_link_dest_path(Path::new("other/f"), Path::new("prev"), Path::new("new")),
+        PathBuf::from("prev/f")
+    );
+}
+
+// ─── timestamp resolution ───────────────────────────────────────────────────
+
+#[test]
+fn truncate_time_rounds_down() {
+    use cp::util::truncate_time;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let t = UNIX_EPOCH + Duration::new(1_000_001, 500_000_000);
+    assert_eq!(truncate_time(t, 0), t);
+    assert_eq!(
+        truncate_time(t, 1),
+        UNIX_EPOCH + Duration::from_secs(1_000_001)
+    );
+    assert_eq!(
+        truncate_time(t, 2),
+        UNIX_EPOCH + Duration::from_secs(1_000_000)
+    );
+}
+
+#[test]
+fn filesystem_timestamp_resolution_tmp_is_exact() {
+    let e = Env::new();
+    // Test directories never live on FAT
+    assert_eq!(cp::util::filesystem_timestamp_resolution(e.path()), 0);
+}
+
+// ─── EINTR retry ────────────────────────────────────────────────────────────
+
+#[test]
+fn retry_eintr_retries_interrupted_call() {
+    use cp::util::retry_eintr;
+
+    let mut calls = 0;
+    let ret = retry_eintr(|| {
+        calls += 1;
+        if calls == 1 {
+            nix::errno::Errno::set_raw(nix::libc::EINTR);
+            -1
+        } else {
+            42
+        }
+    });
+    assert_eq!((ret, calls), (42, 2));
+
+    // Any other error is returned as is
+    let mut calls = 0;
+    let ret = retry_eintr(|| {
+        calls += 1;
+        nix::errno::Errno::set_raw(nix::libc::EIO);
+        -1
+    });
+    assert_eq!((ret, calls), (-1, 1));
+}
+
+#[test]
+fn read_retry_retries_interrupted_read() {
+    use std::io::{self, Read};
+
+    /// Fails with EINTR once, then reads from `data`.
+    struct Flaky<'a> {
+        interrupted: bool,
+        data: &'a [u8],
+    }
+    impl Read for Flaky<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.interrupted {
+                self.interrupted = true;
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    let mut reader = Flaky {
+        interrupted: false,
+        data: b"payload",
+    };
+    let mut buf = [0u8; 16];
+    let n = cp::util::read_retry(&mut reader, &mut buf).unwrap();
+    assert_eq!(&buf[..n], b"payload");
+}
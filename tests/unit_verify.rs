@@ -0,0 +1,71 @@
+//! Tests — --hardlink-ref-count-check (verify.rs)
+
+mod common;
+use common::*;
+
+use cp::verify::{HardlinkMismatch, check_hardlink_counts};
+
+/// 4 inodes × 20 names (parallel path) plus a 3-name group in a subdirectory.
+fn hardlink_tree(e: &Env) {
+    for i in 0..4 {
+        e.file(&format!("src/f{i}_00"), format!("data {i}"));
+        for j in 1..20 {
+            e.hardlink(&format!("src/f{i}_00"), &format!("src/f{i}_{j:02}"));
+        }
+    }
+    e.file("src/sub/a", "shared");
+    e.hardlink("src/sub/a", "src/sub/b");
+    e.hardlink("src/sub/a", "src/c");
+}
+
+#[test]
+fn verify_hardlink_check_passes_after_archive_copy() {
+    let e = Env::new();
+    hardlink_tree(&e);
+
+    cp().arg("-a")
+        .arg("--hardlink-ref-count-check")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success()
+        .stderr("");
+
+    assert!(check_hardlink_counts(&e.p("src"), &e.p("dst"), &lib_opts(&["-a"])).is_empty());
+}
+
+#[test]
+fn verify_hardlink_check_catches_broken_groups() {
+    let e = Env::new();
+    hardlink_tree(&e);
+    cp().arg("-a")
+        .arg(e.p("src"))
+        .arg(e.p("dst"))
+        .assert()
+        .success();
+
+    // What a lost hard link map entry would leave behind: one name of the
+    // group copied on its own instead of linked
+    std::fs::remove_file(e.p("dst/sub/b")).unwrap();
+    std::fs::copy(e.p("dst/sub/a"), e.p("dst/sub/b")).unwrap();
+
+    let mismatches = check_hardlink_counts(&e.p("src"), &e.p("dst"), &lib_opts(&["-a"]));
+    assert_eq!(
+        mismatches,
+        vec![HardlinkMismatch {
+            ino: ino(&e.p("src/sub/a")),
+            src_links: 3,
+            dst_links: 2,
+        }]
+    );
+
+    // Copied without --preserve=links, every group falls apart
+    cp().arg("-R")
+        .arg(e.p("src"))
+        .arg(e.p("flat"))
+        .assert()
+        .success();
+    let mismatches = check_hardlink_counts(&e.p("src"), &e.p("flat"), &lib_opts(&["-a"]));
+    assert_eq!(mismatches.len(), 5);
+    assert!(mismatches.iter().all(|m| m.dst_links == 1));
+}